@@ -26,6 +26,25 @@ fn put_and_take(b: &mut Bencher) {
     });
 }
 
+#[bench]
+fn put_and_take_unchecked(b: &mut Bencher) {
+    let mut stash = UniqueStash::with_capacity(6);
+    b.iter(|| {
+        let t1 = stash.put("something");
+        let t2 = stash.put("something");
+        let _ = unsafe { test::black_box(stash.take_unchecked(t1)) };
+        let t3 = stash.put("something");
+        let t4 = stash.put("something");
+        let t5 = stash.put("something");
+        let _ = unsafe { test::black_box(stash.take_unchecked(t4)) };
+        let t6 = stash.put("something");
+        let _ = unsafe { test::black_box(stash.take_unchecked(t3)) };
+        let _ = unsafe { test::black_box(stash.take_unchecked(t2)) };
+        let _ = unsafe { test::black_box(stash.take_unchecked(t5)) };
+        let _ = unsafe { test::black_box(stash.take_unchecked(t6)) };
+    });
+}
+
 #[bench]
 fn put_and_take_block(b: &mut Bencher) {
     let mut stash = UniqueStash::with_capacity(10);
@@ -93,6 +112,16 @@ fn get(b: &mut Bencher) {
     });
 }
 
+#[bench]
+fn get_unchecked(b: &mut Bencher) {
+    let (stash, tickets) = setup();
+    b.iter(|| {
+        for &t in tickets.iter() {
+            test::black_box(unsafe { stash.get_unchecked(t) });
+        }
+    });
+}
+
 #[bench]
 fn get_mut(b: &mut Bencher) {
     let (mut stash, tickets) = setup();
@@ -103,6 +132,16 @@ fn get_mut(b: &mut Bencher) {
     });
 }
 
+#[bench]
+fn get_unchecked_mut(b: &mut Bencher) {
+    let (mut stash, tickets) = setup();
+    b.iter(|| {
+        for &t in tickets.iter() {
+            test::black_box(unsafe { stash.get_unchecked_mut(t) });
+        }
+    });
+}
+
 #[bench]
 fn ops_index(b: &mut Bencher) {
     let (stash, tickets) = setup();
@@ -1,18 +1,22 @@
 extern crate stash;
 
-use std::u16;
-
 use stash::Stash;
 
 #[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
 struct SmallIndex(u16);
 
 impl stash::Index for SmallIndex {
+    const BITS: u32 = 16;
+
     fn from_usize(idx: usize) -> Self {
+        Self::try_from_usize(idx).expect("index type overflowing!")
+    }
+
+    fn try_from_usize(idx: usize) -> Option<Self> {
         if idx > u16::MAX as usize {
-            panic!("index type overflowing!");
+            return None;
         }
-        SmallIndex(idx as u16)
+        Some(SmallIndex(idx as u16))
     }
 
     fn into_usize(self) -> usize {
@@ -0,0 +1,186 @@
+//! The allocator API used by `Stash` and `UniqueStash`.
+//!
+//! With the `allocator-api2` feature, this re-exports `allocator_api2`'s `Allocator`, `Global`,
+//! and `Vec`. Without it, `Global` is the only allocator and `Vec` wraps the standard `Vec`.
+
+#[cfg(feature = "allocator-api2")]
+pub(crate) use allocator_api2::alloc::{Allocator, Global};
+#[cfg(feature = "allocator-api2")]
+pub(crate) use allocator_api2::vec;
+
+#[cfg(not(feature = "allocator-api2"))]
+pub(crate) use self::inner::{Allocator, Global};
+
+/// Convert a `Vec` into a standard `Vec` (e.g., to hand it to rayon).
+///
+/// Without the `allocator-api2` feature, this just unwraps the standard `Vec`. With it, the
+/// elements are moved into a new allocation as a standard `Vec` can only live in the global
+/// allocator.
+#[cfg(all(feature = "allocator-api2", feature = "rayon"))]
+pub(crate) fn into_std_vec<T, A: Allocator>(vec: vec::Vec<T, A>) -> alloc::vec::Vec<T> {
+    vec.into_iter().collect()
+}
+
+/// Convert a `Vec` into a standard `Vec` (e.g., to hand it to rayon).
+///
+/// Without the `allocator-api2` feature, this just unwraps the standard `Vec`. With it, the
+/// elements are moved into a new allocation as a standard `Vec` can only live in the global
+/// allocator.
+#[cfg(all(not(feature = "allocator-api2"), feature = "rayon"))]
+pub(crate) fn into_std_vec<T, A: Allocator>(vec: vec::Vec<T, A>) -> alloc::vec::Vec<T> {
+    vec.inner
+}
+
+#[cfg(not(feature = "allocator-api2"))]
+mod inner {
+    /// An allocator. Enable the `allocator-api2` feature to use allocators other than `Global`.
+    ///
+    /// This trait can't be implemented outside of this crate.
+    pub trait Allocator {}
+
+    /// The global memory allocator.
+    #[derive(Copy, Clone, Default, Debug)]
+    pub struct Global;
+
+    impl Allocator for Global {}
+}
+
+#[cfg(not(feature = "allocator-api2"))]
+pub(crate) mod vec {
+    use core::iter::{FromIterator, FusedIterator};
+    use core::ops::{Deref, DerefMut};
+
+    use super::{Allocator, Global};
+
+    /// A standard `Vec` tagged with its (global) allocator.
+    #[derive(Clone)]
+    pub struct Vec<T, A: Allocator = Global> {
+        pub(super) inner: alloc::vec::Vec<T>,
+        alloc: A,
+    }
+
+    impl<T> Vec<T> {
+        #[inline]
+        pub const fn new() -> Self {
+            Vec::new_in(Global)
+        }
+
+        #[inline]
+        pub fn with_capacity(capacity: usize) -> Self {
+            Vec::with_capacity_in(capacity, Global)
+        }
+    }
+
+    impl<T, A: Allocator> Vec<T, A> {
+        #[inline]
+        pub const fn new_in(alloc: A) -> Self {
+            Vec {
+                inner: alloc::vec::Vec::new(),
+                alloc,
+            }
+        }
+
+        #[inline]
+        pub fn with_capacity_in(capacity: usize, alloc: A) -> Self {
+            Vec {
+                inner: alloc::vec::Vec::with_capacity(capacity),
+                alloc,
+            }
+        }
+
+        #[inline]
+        pub fn allocator(&self) -> &A {
+            &self.alloc
+        }
+    }
+
+    impl<T, A: Allocator> Deref for Vec<T, A> {
+        type Target = alloc::vec::Vec<T>;
+
+        #[inline]
+        fn deref(&self) -> &Self::Target {
+            &self.inner
+        }
+    }
+
+    impl<T, A: Allocator> DerefMut for Vec<T, A> {
+        #[inline]
+        fn deref_mut(&mut self) -> &mut Self::Target {
+            &mut self.inner
+        }
+    }
+
+    impl<T> FromIterator<T> for Vec<T> {
+        #[inline]
+        fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+            Vec {
+                inner: iter.into_iter().collect(),
+                alloc: Global,
+            }
+        }
+    }
+
+    impl<T, A: Allocator> IntoIterator for Vec<T, A> {
+        type Item = T;
+        type IntoIter = IntoIter<T, A>;
+
+        #[inline]
+        fn into_iter(self) -> IntoIter<T, A> {
+            IntoIter {
+                inner: self.inner.into_iter(),
+                _alloc: self.alloc,
+            }
+        }
+    }
+
+    impl<'a, T, A: Allocator> IntoIterator for &'a Vec<T, A> {
+        type Item = &'a T;
+        type IntoIter = core::slice::Iter<'a, T>;
+
+        #[inline]
+        fn into_iter(self) -> Self::IntoIter {
+            self.inner.iter()
+        }
+    }
+
+    impl<'a, T, A: Allocator> IntoIterator for &'a mut Vec<T, A> {
+        type Item = &'a mut T;
+        type IntoIter = core::slice::IterMut<'a, T>;
+
+        #[inline]
+        fn into_iter(self) -> Self::IntoIter {
+            self.inner.iter_mut()
+        }
+    }
+
+    /// A by-value iterator over a `Vec`.
+    pub struct IntoIter<T, A: Allocator = Global> {
+        inner: alloc::vec::IntoIter<T>,
+        _alloc: A,
+    }
+
+    impl<T, A: Allocator> Iterator for IntoIter<T, A> {
+        type Item = T;
+
+        #[inline]
+        fn next(&mut self) -> Option<T> {
+            self.inner.next()
+        }
+
+        #[inline]
+        fn size_hint(&self) -> (usize, Option<usize>) {
+            self.inner.size_hint()
+        }
+    }
+
+    impl<T, A: Allocator> DoubleEndedIterator for IntoIter<T, A> {
+        #[inline]
+        fn next_back(&mut self) -> Option<T> {
+            self.inner.next_back()
+        }
+    }
+
+    impl<T, A: Allocator> ExactSizeIterator for IntoIter<T, A> {}
+
+    impl<T, A: Allocator> FusedIterator for IntoIter<T, A> {}
+}
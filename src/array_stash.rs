@@ -0,0 +1,409 @@
+//! A fixed-capacity `Stash` that stores its slots inline and never allocates.
+
+use core::array;
+use core::fmt;
+use core::iter;
+use core::marker;
+use core::mem;
+use core::ops;
+use core::slice;
+
+use crate::error::LookupError;
+use crate::index::Index;
+use crate::stash::entry::{self, Entry};
+
+/// Iterator over the `(index, &value)` pairs.
+pub struct Iter<'a, V: 'a, Ix: Index> {
+    inner: iter::Enumerate<slice::Iter<'a, Entry<V>>>,
+    len: usize,
+    _marker: marker::PhantomData<fn() -> Ix>,
+}
+
+/// Iterator over the `(index, &mut value)` pairs.
+pub struct IterMut<'a, V: 'a, Ix: Index> {
+    inner: iter::Enumerate<slice::IterMut<'a, Entry<V>>>,
+    len: usize,
+    _marker: marker::PhantomData<fn() -> Ix>,
+}
+
+/// Iterator over the `(index, value)` pairs.
+pub struct IntoIter<V, const N: usize, Ix: Index> {
+    inner: iter::Enumerate<array::IntoIter<Entry<V>, N>>,
+    len: usize,
+    _marker: marker::PhantomData<fn() -> Ix>,
+}
+
+/// Iterator over references to the values in the stash.
+pub struct Values<'a, V: 'a> {
+    inner: slice::Iter<'a, Entry<V>>,
+    len: usize,
+}
+
+/// Iterator over mutable references to the values in the stash.
+pub struct ValuesMut<'a, V: 'a> {
+    inner: slice::IterMut<'a, Entry<V>>,
+    len: usize,
+}
+
+impl_iter!(Values, (<'a, V>), &'a V, entry::value_ref, ());
+impl_iter!(ValuesMut, (<'a, V>), &'a mut V, entry::value_mut, ());
+
+impl_iter!(Iter, (<'a, V, Ix>), (Ix, &'a V), entry::value_index_ref, (where Ix: Index));
+impl_iter!(IterMut, (<'a, V, Ix>), (Ix, &'a mut V), entry::value_index_mut, (where Ix: Index));
+
+// `impl_iter!` can't declare const parameters.
+impl<V, const N: usize, Ix: Index> Iterator for IntoIter<V, N, Ix> {
+    type Item = (Ix, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let item = self.inner.find_map(entry::value_index);
+        if item.is_some() {
+            self.len -= 1;
+        }
+        item
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<V, const N: usize, Ix: Index> ExactSizeIterator for IntoIter<V, N, Ix> {}
+
+impl<V, const N: usize, Ix: Index> DoubleEndedIterator for IntoIter<V, N, Ix> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let item = (&mut self.inner).rev().find_map(entry::value_index);
+        if item.is_some() {
+            self.len -= 1;
+        }
+        item
+    }
+}
+
+/// A `Stash` with a fixed capacity of `N` values, stored inline.
+///
+/// `ArrayStash` never allocates and, as `new` is a `const fn`, can be used in `static` items.
+/// (The crate as a whole still depends on `alloc`, so a global allocator is required.) Keys are assigned exactly as they would be by a
+/// `Stash` subjected to the same sequence of operations (and come with the same guarantees) as
+/// long as the stash doesn't fill up.
+///
+/// # Examples
+///
+/// ```
+/// use stash::ArrayStash;
+/// use std::sync::Mutex;
+///
+/// static HANDLES: Mutex<ArrayStash<&str, 2>> = Mutex::new(ArrayStash::new());
+///
+/// let mut handles = HANDLES.lock().unwrap();
+/// let a = handles.put("a").unwrap();
+/// handles.put("b").unwrap();
+/// assert_eq!(handles.put("c"), Err("c"));
+///
+/// assert_eq!(handles.take(a), Some("a"));
+/// assert_eq!(handles.put("c"), Ok(a));
+/// ```
+pub struct ArrayStash<V, const N: usize, Ix = usize> {
+    data: [Entry<V>; N],
+    /// The number of slots in use (vacant or not). Slots past this are never read.
+    used: usize,
+    size: usize,
+    next_free: usize,
+    _marker: marker::PhantomData<fn(Ix) -> Ix>,
+}
+
+impl<V, const N: usize, Ix> ArrayStash<V, N, Ix>
+where
+    Ix: Index,
+{
+    /// Constructs a new, empty `ArrayStash`.
+    pub const fn new() -> Self {
+        ArrayStash {
+            data: [const { Entry::Empty(0) }; N],
+            used: 0,
+            size: 0,
+            next_free: 0,
+            _marker: marker::PhantomData,
+        }
+    }
+
+    /// Returns the number of elements the stash can hold (`N`).
+    #[inline]
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Returns the number of items in the stash.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.size
+    }
+
+    /// Check if this `ArrayStash` is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    /// Check if this `ArrayStash` is full (i.e., `put` would fail).
+    #[inline]
+    pub fn is_full(&self) -> bool {
+        self.size == N
+    }
+
+    /// Get the index that would be returned from next call to `put`, or `None` if the stash is
+    /// full.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the size of the `ArrayStash` would overflow the `Ix` index type.
+    pub fn next_index(&self) -> Option<Ix> {
+        if self.is_full() {
+            None
+        } else {
+            Some(Ix::from_usize(self.next_free))
+        }
+    }
+
+    /// Put a value into the stash.
+    ///
+    /// Returns the index at which this value was stored, or gives the value back if the stash
+    /// is full.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the size of the `ArrayStash` would overflow the `Ix` index type.
+    pub fn put(&mut self, value: V) -> Result<Ix, V> {
+        if self.is_full() {
+            return Err(value);
+        }
+        // create index first so the potential panic would happen before any modification
+        let idx = Ix::from_usize(self.next_free);
+        let loc = self.next_free;
+        self.next_free = if loc == self.used {
+            self.data[loc] = Entry::Full(value);
+            self.used += 1;
+            self.used
+        } else {
+            match mem::replace(&mut self.data[loc], Entry::Full(value)) {
+                Entry::Empty(next_free) => next_free,
+                Entry::Full(_) => unreachable!("corrupt free list"),
+            }
+        };
+        self.size += 1;
+        Ok(idx)
+    }
+
+    /// Iterate over the items in this `ArrayStash`.
+    ///
+    /// Returns an iterator that yields `(index, &value)` pairs.
+    #[inline]
+    pub fn iter(&self) -> Iter<'_, V, Ix> {
+        Iter {
+            len: self.size,
+            inner: self.data[..self.used].iter().enumerate(),
+            _marker: marker::PhantomData,
+        }
+    }
+
+    /// Mutably iterate over the items in this `ArrayStash`.
+    ///
+    /// Returns an iterator that yields `(index, &mut value)` pairs.
+    #[inline]
+    pub fn iter_mut(&mut self) -> IterMut<'_, V, Ix> {
+        IterMut {
+            len: self.size,
+            inner: self.data[..self.used].iter_mut().enumerate(),
+            _marker: marker::PhantomData,
+        }
+    }
+
+    /// Iterate over the values in this `ArrayStash` by reference.
+    #[inline]
+    pub fn values(&self) -> Values<'_, V> {
+        Values {
+            len: self.size,
+            inner: self.data[..self.used].iter(),
+        }
+    }
+
+    /// Mutably iterate over the values in this `ArrayStash` by reference.
+    #[inline]
+    pub fn values_mut(&mut self) -> ValuesMut<'_, V> {
+        ValuesMut {
+            len: self.size,
+            inner: self.data[..self.used].iter_mut(),
+        }
+    }
+
+    /// Take an item from a slot (if non empty).
+    #[inline]
+    pub fn take(&mut self, index: Ix) -> Option<V> {
+        self.try_take(index).ok()
+    }
+
+    /// Take an item from a slot, reporting why it failed if the slot is empty or doesn't exist.
+    pub fn try_take(&mut self, index: Ix) -> Result<V, LookupError> {
+        let take_index = index.into_usize();
+        match self.data[..self.used].get_mut(take_index) {
+            None => Err(LookupError::OutOfBounds),
+            Some(entry) => match mem::replace(entry, Entry::Empty(self.next_free)) {
+                Entry::Empty(free_slot) => {
+                    *entry = Entry::Empty(free_slot);
+                    Err(LookupError::Vacant)
+                }
+                Entry::Full(value) => {
+                    self.next_free = take_index;
+                    self.size -= 1;
+                    Ok(value)
+                }
+            },
+        }
+    }
+
+    /// Check if `index` refers to a value in this stash.
+    #[inline]
+    pub fn contains_key(&self, index: Ix) -> bool {
+        self.get(index).is_some()
+    }
+
+    /// Get a reference to the value at `index`.
+    #[inline]
+    pub fn get(&self, index: Ix) -> Option<&V> {
+        self.try_get(index).ok()
+    }
+
+    /// Get a reference to the value at `index`, reporting why it failed if the slot is empty or
+    /// doesn't exist.
+    pub fn try_get(&self, index: Ix) -> Result<&V, LookupError> {
+        match self.data[..self.used].get(index.into_usize()) {
+            Some(Entry::Full(v)) => Ok(v),
+            Some(Entry::Empty(_)) => Err(LookupError::Vacant),
+            None => Err(LookupError::OutOfBounds),
+        }
+    }
+
+    /// Get a mutable reference to the value at `index`.
+    #[inline]
+    pub fn get_mut(&mut self, index: Ix) -> Option<&mut V> {
+        self.try_get_mut(index).ok()
+    }
+
+    /// Get a mutable reference to the value at `index`, reporting why it failed if the slot is
+    /// empty or doesn't exist.
+    pub fn try_get_mut(&mut self, index: Ix) -> Result<&mut V, LookupError> {
+        match self.data[..self.used].get_mut(index.into_usize()) {
+            Some(Entry::Full(v)) => Ok(v),
+            Some(Entry::Empty(_)) => Err(LookupError::Vacant),
+            None => Err(LookupError::OutOfBounds),
+        }
+    }
+
+    /// Clear the stash. Cleared stash will give the same keys as a new stash for subsequent
+    /// puts.
+    pub fn clear(&mut self) {
+        for (i, entry) in self.data[..self.used].iter_mut().enumerate() {
+            // Skip if empty.
+            if let Entry::Empty(_) = *entry {
+                continue;
+            }
+            let next_free = mem::replace(&mut self.next_free, i);
+            let value = mem::replace(entry, Entry::Empty(next_free));
+            self.size -= 1;
+            // Drop last, that way a panic just stops this half way through.
+            drop(value);
+        }
+        self.used = 0;
+        self.next_free = 0;
+    }
+}
+
+impl<V, const N: usize, Ix> IntoIterator for ArrayStash<V, N, Ix>
+where
+    Ix: Index,
+{
+    type Item = (Ix, V);
+    type IntoIter = IntoIter<V, N, Ix>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter {
+            len: self.size,
+            inner: IntoIterator::into_iter(self.data).enumerate(),
+            _marker: marker::PhantomData,
+        }
+    }
+}
+
+impl<'a, V, const N: usize, Ix> IntoIterator for &'a ArrayStash<V, N, Ix>
+where
+    Ix: Index,
+{
+    type Item = (Ix, &'a V);
+    type IntoIter = Iter<'a, V, Ix>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, V, const N: usize, Ix> IntoIterator for &'a mut ArrayStash<V, N, Ix>
+where
+    Ix: Index,
+{
+    type Item = (Ix, &'a mut V);
+    type IntoIter = IterMut<'a, V, Ix>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+impl<V, const N: usize, Ix> Clone for ArrayStash<V, N, Ix>
+where
+    V: Clone,
+{
+    fn clone(&self) -> Self {
+        ArrayStash {
+            data: self.data.clone(),
+            used: self.used,
+            size: self.size,
+            next_free: self.next_free,
+            _marker: marker::PhantomData,
+        }
+    }
+}
+
+impl<V, const N: usize, Ix> fmt::Debug for ArrayStash<V, N, Ix>
+where
+    V: fmt::Debug,
+    Ix: fmt::Debug + Index,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(self).finish()
+    }
+}
+
+impl<V, const N: usize, Ix: Index> ops::Index<Ix> for ArrayStash<V, N, Ix> {
+    type Output = V;
+    #[inline]
+    fn index(&self, index: Ix) -> &V {
+        self.get(index).expect("index out of bounds")
+    }
+}
+
+impl<V, const N: usize, Ix: Index> ops::IndexMut<Ix> for ArrayStash<V, N, Ix> {
+    #[inline]
+    fn index_mut(&mut self, index: Ix) -> &mut V {
+        self.get_mut(index).expect("index out of bounds")
+    }
+}
+
+impl<V, const N: usize, Ix: Index> Default for ArrayStash<V, N, Ix> {
+    #[inline]
+    fn default() -> Self {
+        ArrayStash::new()
+    }
+}
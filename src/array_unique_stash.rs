@@ -0,0 +1,383 @@
+//! A fixed-capacity `UniqueStash` that stores its slots inline and never allocates.
+
+use core::array;
+use core::fmt;
+use core::iter;
+use core::mem;
+use core::ops::{Index, IndexMut};
+use core::slice;
+
+use crate::error::LookupError;
+use crate::tag::{version_mismatch, Tag};
+use crate::unique_stash::entry::{self, Entry, VerEntry};
+
+/// Iterator over the `(index, &value)` pairs.
+pub struct Iter<'a, V: 'a> {
+    inner: iter::Enumerate<slice::Iter<'a, VerEntry<V>>>,
+    len: usize,
+}
+
+/// Iterator over the `(index, &mut value)` pairs.
+pub struct IterMut<'a, V: 'a> {
+    inner: iter::Enumerate<slice::IterMut<'a, VerEntry<V>>>,
+    len: usize,
+}
+
+/// Iterator over the `(index, value)` pairs.
+pub struct IntoIter<V, const N: usize> {
+    inner: iter::Enumerate<array::IntoIter<VerEntry<V>, N>>,
+    len: usize,
+}
+
+/// Iterator over references to the values in the stash.
+pub struct Values<'a, V: 'a> {
+    inner: slice::Iter<'a, VerEntry<V>>,
+    len: usize,
+}
+
+/// Iterator over mutable references to the values in the stash.
+pub struct ValuesMut<'a, V: 'a> {
+    inner: slice::IterMut<'a, VerEntry<V>>,
+    len: usize,
+}
+
+impl_iter!(Values, (<'a, V>), &'a V, entry::value_ref, ());
+impl_iter!(ValuesMut, (<'a, V>), &'a mut V, entry::value_mut, ());
+
+impl_iter!(Iter, (<'a, V>), (Tag, &'a V), entry::value_index_ref, ());
+impl_iter!(IterMut, (<'a, V>), (Tag, &'a mut V), entry::value_index_mut, ());
+
+// `impl_iter!` can't declare const parameters.
+impl<V, const N: usize> Iterator for IntoIter<V, N> {
+    type Item = (Tag, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let item = self.inner.find_map(entry::value_index);
+        if item.is_some() {
+            self.len -= 1;
+        }
+        item
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<V, const N: usize> ExactSizeIterator for IntoIter<V, N> {}
+
+impl<V, const N: usize> DoubleEndedIterator for IntoIter<V, N> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let item = (&mut self.inner).rev().find_map(entry::value_index);
+        if item.is_some() {
+            self.len -= 1;
+        }
+        item
+    }
+}
+
+/// A `UniqueStash` with a fixed capacity of `N` values, stored inline.
+///
+/// `ArrayUniqueStash` never allocates and, as `new` is a `const fn`, can be used in `static`
+/// items. (The crate as a whole still depends on `alloc`, so a global allocator is required.)
+/// Tags are assigned exactly as they would
+/// be by a `UniqueStash` subjected to the same sequence of operations (and are never reused) as
+/// long as the stash doesn't fill up.
+///
+/// # Examples
+///
+/// ```
+/// use stash::ArrayUniqueStash;
+///
+/// let mut stash: ArrayUniqueStash<&str, 1> = ArrayUniqueStash::new();
+/// let a = stash.put("a").unwrap();
+/// assert_eq!(stash.put("b"), Err("b"));
+///
+/// assert_eq!(stash.take(a), Some("a"));
+/// let b = stash.put("b").unwrap();
+/// assert_ne!(a, b);
+/// assert_eq!(stash.get(a), None);
+/// ```
+pub struct ArrayUniqueStash<V, const N: usize> {
+    data: [VerEntry<V>; N],
+    /// The number of slots in use (vacant or not). Slots past this are never read.
+    used: usize,
+    size: usize,
+    next_free: usize,
+}
+
+impl<V, const N: usize> ArrayUniqueStash<V, N> {
+    /// Constructs a new, empty `ArrayUniqueStash`.
+    pub const fn new() -> Self {
+        ArrayUniqueStash {
+            data: [const {
+                VerEntry {
+                    version: 0,
+                    entry: Entry::Empty(0),
+                }
+            }; N],
+            used: 0,
+            size: 0,
+            next_free: 0,
+        }
+    }
+
+    /// Returns the number of elements the stash can hold (`N`).
+    #[inline]
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Returns the number of items in the stash.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.size
+    }
+
+    /// Check if this `ArrayUniqueStash` is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    /// Check if this `ArrayUniqueStash` is full (i.e., `put` would fail).
+    #[inline]
+    pub fn is_full(&self) -> bool {
+        self.size == N
+    }
+
+    /// Put a value into the stash.
+    ///
+    /// Returns the tag at which this value was stored, or gives the value back if the stash is
+    /// full.
+    pub fn put(&mut self, value: V) -> Result<Tag, V> {
+        if self.is_full() {
+            return Err(value);
+        }
+        let loc = self.next_free;
+        let entry = &mut self.data[loc];
+        self.next_free = if loc == self.used {
+            entry.entry = Entry::Full(value);
+            self.used += 1;
+            self.used
+        } else {
+            match mem::replace(&mut entry.entry, Entry::Full(value)) {
+                Entry::Empty(next_free) => next_free,
+                Entry::Full(_) => unreachable!("corrupt free list"),
+            }
+        };
+        self.size += 1;
+        Ok(Tag {
+            idx: loc,
+            ver: entry.version,
+        })
+    }
+
+    /// Iterate over the items in this `ArrayUniqueStash`.
+    ///
+    /// Returns an iterator that yields `(index, &value)` pairs.
+    #[inline]
+    pub fn iter(&self) -> Iter<'_, V> {
+        Iter {
+            len: self.size,
+            inner: self.data[..self.used].iter().enumerate(),
+        }
+    }
+
+    /// Mutably iterate over the items in this `ArrayUniqueStash`.
+    ///
+    /// Returns an iterator that yields `(index, &mut value)` pairs.
+    #[inline]
+    pub fn iter_mut(&mut self) -> IterMut<'_, V> {
+        IterMut {
+            len: self.size,
+            inner: self.data[..self.used].iter_mut().enumerate(),
+        }
+    }
+
+    /// Iterate over the values in this `ArrayUniqueStash` by reference.
+    #[inline]
+    pub fn values(&self) -> Values<'_, V> {
+        Values {
+            len: self.size,
+            inner: self.data[..self.used].iter(),
+        }
+    }
+
+    /// Mutably iterate over the values in this `ArrayUniqueStash` by reference.
+    #[inline]
+    pub fn values_mut(&mut self) -> ValuesMut<'_, V> {
+        ValuesMut {
+            len: self.size,
+            inner: self.data[..self.used].iter_mut(),
+        }
+    }
+
+    /// Take an item from a slot (if non empty).
+    #[inline]
+    pub fn take(&mut self, index: Tag) -> Option<V> {
+        self.try_take(index).ok()
+    }
+
+    /// Take an item from a slot, reporting why it failed if the tag is stale, refers to a vacant
+    /// slot, or is out of bounds.
+    pub fn try_take(&mut self, index: Tag) -> Result<V, LookupError> {
+        match self.data[..self.used].get_mut(index.idx) {
+            Some(VerEntry { version, entry }) if *version == index.ver => {
+                match mem::replace(entry, Entry::Empty(self.next_free)) {
+                    Entry::Full(value) => {
+                        *version += 1;
+                        self.next_free = index.idx;
+                        self.size -= 1;
+                        Ok(value)
+                    }
+                    empty => {
+                        // Just put it back.
+                        *entry = empty;
+                        Err(LookupError::FutureVersion)
+                    }
+                }
+            }
+            Some(VerEntry { version, .. }) => Err(version_mismatch(*version, index)),
+            None => Err(LookupError::OutOfBounds),
+        }
+    }
+
+    /// Check if `index` refers to a value in this stash.
+    #[inline]
+    pub fn contains(&self, index: Tag) -> bool {
+        self.get(index).is_some()
+    }
+
+    /// Get a reference to the value at `index`.
+    #[inline]
+    pub fn get(&self, index: Tag) -> Option<&V> {
+        self.try_get(index).ok()
+    }
+
+    /// Get a reference to the value at `index`, reporting why it failed if the tag is stale,
+    /// refers to a vacant slot, or is out of bounds.
+    pub fn try_get(&self, index: Tag) -> Result<&V, LookupError> {
+        match self.data[..self.used].get(index.idx) {
+            Some(VerEntry {
+                version,
+                entry: Entry::Full(value),
+            }) if *version == index.ver => Ok(value),
+            Some(VerEntry { version, .. }) => Err(version_mismatch(*version, index)),
+            None => Err(LookupError::OutOfBounds),
+        }
+    }
+
+    /// Get a mutable reference to the value at `index`.
+    #[inline]
+    pub fn get_mut(&mut self, index: Tag) -> Option<&mut V> {
+        self.try_get_mut(index).ok()
+    }
+
+    /// Get a mutable reference to the value at `index`, reporting why it failed if the tag is
+    /// stale, refers to a vacant slot, or is out of bounds.
+    pub fn try_get_mut(&mut self, index: Tag) -> Result<&mut V, LookupError> {
+        match self.data[..self.used].get_mut(index.idx) {
+            Some(VerEntry {
+                version,
+                entry: Entry::Full(value),
+            }) if *version == index.ver => Ok(value),
+            Some(VerEntry { version, .. }) => Err(version_mismatch(*version, index)),
+            None => Err(LookupError::OutOfBounds),
+        }
+    }
+
+    /// Clear the stash.
+    ///
+    /// Tags of the removed values stay invalid: slots are reused with new versions.
+    pub fn clear(&mut self) {
+        for (i, item) in self.data[..self.used].iter_mut().enumerate() {
+            // Skip if empty. We do it this way so that panics on drop don't
+            // mess up the datastructure.
+            if let Entry::Empty(_) = item.entry {
+                continue;
+            }
+            item.version += 1;
+            let value = mem::replace(&mut item.entry, Entry::Empty(self.next_free));
+            self.next_free = i;
+            self.size -= 1;
+            // Drop last, that way a panic just stops this half way through.
+            drop(value);
+        }
+    }
+}
+
+impl<V, const N: usize> IntoIterator for ArrayUniqueStash<V, N> {
+    type Item = (Tag, V);
+    type IntoIter = IntoIter<V, N>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter {
+            len: self.size,
+            inner: IntoIterator::into_iter(self.data).enumerate(),
+        }
+    }
+}
+
+impl<'a, V, const N: usize> IntoIterator for &'a ArrayUniqueStash<V, N> {
+    type Item = (Tag, &'a V);
+    type IntoIter = Iter<'a, V>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, V, const N: usize> IntoIterator for &'a mut ArrayUniqueStash<V, N> {
+    type Item = (Tag, &'a mut V);
+    type IntoIter = IterMut<'a, V>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+impl<V: Clone, const N: usize> Clone for ArrayUniqueStash<V, N> {
+    fn clone(&self) -> Self {
+        ArrayUniqueStash {
+            data: self.data.clone(),
+            used: self.used,
+            size: self.size,
+            next_free: self.next_free,
+        }
+    }
+}
+
+impl<V, const N: usize> fmt::Debug for ArrayUniqueStash<V, N>
+where
+    V: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(self).finish()
+    }
+}
+
+impl<V, const N: usize> Index<Tag> for ArrayUniqueStash<V, N> {
+    type Output = V;
+    #[inline]
+    fn index(&self, index: Tag) -> &V {
+        self.get(index).expect("index out of bounds")
+    }
+}
+
+impl<V, const N: usize> IndexMut<Tag> for ArrayUniqueStash<V, N> {
+    #[inline]
+    fn index_mut(&mut self, index: Tag) -> &mut V {
+        self.get_mut(index).expect("index out of bounds")
+    }
+}
+
+impl<V, const N: usize> Default for ArrayUniqueStash<V, N> {
+    #[inline]
+    fn default() -> Self {
+        ArrayUniqueStash::new()
+    }
+}
@@ -0,0 +1,76 @@
+//! Differences between two stashes.
+//!
+//! `Stash::diff` (and `UniqueStash::diff`) compute the changes needed to turn one stash into
+//! another and `apply_patch` applies them. Patches carry both the values that changed and, when
+//! it changed, the order in which vacant slots will be reused, so a patched stash assigns the
+//! same keys as the stash it was diffed against.
+//!
+//! # Examples
+//!
+//! ```
+//! use stash::Stash;
+//!
+//! let mut primary = Stash::new();
+//! let a = primary.put("a");
+//! let mut replica = primary.clone();
+//!
+//! let before = primary.clone();
+//! primary.take(a);
+//! primary.put("b");
+//! primary.put("c");
+//!
+//! replica.apply_patch(before.diff(&primary)).unwrap();
+//! assert_eq!(replica.put("d"), primary.put("d"));
+//! assert!(replica.iter().eq(primary.iter()));
+//! ```
+
+use alloc::vec::Vec;
+use core::error::Error;
+use core::fmt;
+
+/// A single change between two stashes.
+///
+/// `Ix` is the stash's key type: the index type for a `Stash` and `Tag` for a `UniqueStash`.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum Change<Ix, V> {
+    /// A value was added at `index` (which was vacant).
+    Added { index: Ix, value: V },
+    /// The value at `index` was removed.
+    Removed { index: Ix },
+    /// The value at `index` was replaced with `value`.
+    Modified { index: Ix, value: V },
+    /// The vacant slots changed.
+    ///
+    /// The stash now has `len` slots (vacant or not) and its vacant slots will be reused in the
+    /// given `order`. For a `UniqueStash`, each key in `order` is the tag the slot will be
+    /// reused with.
+    FreeList { len: usize, order: Vec<Ix> },
+}
+
+/// An error returned when a patch doesn't apply to a stash.
+///
+/// The stash is left unmodified.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum PatchError {
+    /// The change to the slot at `index` doesn't match the slot's contents (e.g., adding a value
+    /// to a full slot, removing a value from a vacant one, or adding a value with a version the
+    /// slot has already used), or the slot was changed more than once.
+    Conflict { index: usize },
+    /// The patch doesn't describe a valid set of vacant slots: the free list is missing (but
+    /// values were added or removed), it doesn't list every vacant slot exactly once, or (for a
+    /// `UniqueStash`) it rolls back the version of a vacant slot or removes slots.
+    InvalidFreeList,
+}
+
+impl Error for PatchError {}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PatchError::Conflict { index } => {
+                write!(f, "patch conflicts with the slot at index {}", index)
+            }
+            PatchError::InvalidFreeList => f.write_str("patch has an invalid free list"),
+        }
+    }
+}
@@ -0,0 +1,41 @@
+//! Error types shared by the stashes.
+
+use core::error::Error;
+use core::fmt;
+
+/// The reason a key lookup failed. Returned by the `try_*` methods on `Stash` and
+/// `UniqueStash`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum LookupError {
+    /// The key refers to a slot past the end of the stash. Such a key was never handed out by
+    /// this stash.
+    OutOfBounds,
+    /// The key refers to a vacant slot (e.g., its value has already been taken).
+    Vacant,
+    /// The tag refers to a value that has since been taken (`UniqueStash` only).
+    Stale {
+        /// The version of the slot the tag refers to. Tags for this slot must have this version to
+        /// be valid.
+        current_version: u64,
+    },
+    /// The tag's version is newer than that of the slot it refers to so this stash can't have
+    /// issued it (`UniqueStash` only).
+    FutureVersion,
+}
+
+impl Error for LookupError {}
+
+impl fmt::Display for LookupError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LookupError::OutOfBounds => f.write_str("key out of bounds"),
+            LookupError::Vacant => f.write_str("key refers to a vacant slot"),
+            LookupError::Stale { current_version } => write!(
+                f,
+                "tag is stale (the current version is {})",
+                current_version
+            ),
+            LookupError::FutureVersion => f.write_str("tag has a version from the future"),
+        }
+    }
+}
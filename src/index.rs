@@ -1,10 +1,34 @@
 /// Every index type to be used with Stash needs to implement this trait
 pub trait Index {
+    /// The number of (low) bits of a `usize` this index type can represent.
+    ///
+    /// `ShardedStash` stores the shard number in the highest of these bits. The default assumes
+    /// the full range of a `usize` so implementors with a restricted range **must** override it
+    /// (e.g., with `16` for an index wrapping a `u16`). Otherwise, `ShardedStash` will hand out
+    /// keys the index type can't represent.
+    const BITS: u32 = usize::BITS;
+
     /// Create an index from `usize`.
     ///
     /// This method should panic if `idx` is out of acceptable range.
     fn from_usize(idx: usize) -> Self;
 
+    /// Create an index from `usize`, returning `None` if `idx` is out of acceptable range.
+    ///
+    /// The default implementation returns `None` if `idx` doesn't fit in `BITS` bits and calls
+    /// `from_usize` otherwise. Index types whose range isn't described by `BITS` should override
+    /// it.
+    fn try_from_usize(idx: usize) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        if Self::BITS < usize::BITS && idx >> Self::BITS != 0 {
+            None
+        } else {
+            Some(Self::from_usize(idx))
+        }
+    }
+
     /// Turn this index into `usize`
     fn into_usize(self) -> usize;
 }
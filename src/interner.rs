@@ -0,0 +1,332 @@
+//! A `Stash` that deduplicates its values.
+
+use std::borrow::Borrow;
+use std::collections::hash_map::{self, HashMap, RandomState};
+use std::fmt;
+use std::hash::{BuildHasher, Hash};
+use std::marker;
+use std::ops;
+
+use crate::index::Index;
+use crate::stash::{self, Stash};
+
+#[derive(Clone)]
+struct Slot<V> {
+    value: V,
+    /// The number of times the value has been interned (and not released).
+    refs: usize,
+    /// The next slot holding a value with the same hash.
+    next: Option<usize>,
+}
+
+/// Iterator over the `(index, &value)` pairs.
+pub struct Iter<'a, V: 'a, Ix: Index> {
+    inner: stash::Iter<'a, Slot<V>, usize>,
+    _marker: marker::PhantomData<fn() -> Ix>,
+}
+
+impl<'a, V, Ix: Index> Iterator for Iter<'a, V, Ix> {
+    type Item = (Ix, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (idx, slot) = self.inner.next()?;
+        Some((Ix::from_usize(idx), &slot.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, V, Ix: Index> ExactSizeIterator for Iter<'a, V, Ix> {}
+
+impl<'a, V, Ix: Index> DoubleEndedIterator for Iter<'a, V, Ix> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let (idx, slot) = self.inner.next_back()?;
+        Some((Ix::from_usize(idx), &slot.value))
+    }
+}
+
+/// A `Stash` that stores each distinct value once (e.g., a string/symbol interner).
+///
+/// Interning a value that's already present returns its existing key and bumps its reference
+/// count. Otherwise, the value is put into an inner `Stash` so keys are assigned exactly as
+/// `Stash` would assign them (and come with the same guarantees): they stay small and, given the
+/// same sequence of operations, deterministic.
+///
+/// Values are looked up by hash. Only the hash of each value is stored in the index so values
+/// don't need to be `Clone` and aren't stored twice.
+///
+/// # Examples
+///
+/// ```
+/// use stash::Interner;
+///
+/// let mut symbols = Interner::new();
+/// let foo = symbols.intern(String::from("foo"));
+/// let bar = symbols.intern(String::from("bar"));
+/// assert_eq!(symbols.intern(String::from("foo")), foo);
+/// assert_eq!(symbols.len(), 2);
+///
+/// assert_eq!(symbols.lookup("bar"), Some(bar));
+/// assert_eq!(symbols.resolve(foo).map(String::as_str), Some("foo"));
+///
+/// // "foo" has been interned twice so it takes two releases to remove it.
+/// assert_eq!(symbols.release(foo), None);
+/// assert_eq!(symbols.release(foo).as_deref(), Some("foo"));
+/// assert_eq!(symbols.lookup("foo"), None);
+/// ```
+pub struct Interner<V, Ix = usize, S = RandomState> {
+    slots: Stash<Slot<V>>,
+    /// Maps a hash to the first slot in the chain of slots holding values with that hash.
+    heads: HashMap<u64, usize>,
+    hasher: S,
+    _marker: marker::PhantomData<fn(Ix) -> Ix>,
+}
+
+impl<V> Interner<V, usize>
+where
+    V: Hash + Eq,
+{
+    /// Constructs a new, empty `Interner<V, usize>`.
+    ///
+    /// This is a convenience method. Use `Interner::default` for a constructor that is generic in
+    /// the type of index used.
+    #[inline]
+    pub fn new() -> Self {
+        Interner::default()
+    }
+}
+
+impl<V, Ix, S> Interner<V, Ix, S>
+where
+    V: Hash + Eq,
+    Ix: Index,
+    S: BuildHasher,
+{
+    /// Constructs a new, empty `Interner` which will use the given hash builder to hash values.
+    pub fn with_hasher(hasher: S) -> Self {
+        Interner {
+            slots: Stash::new(),
+            heads: HashMap::new(),
+            hasher,
+            _marker: marker::PhantomData,
+        }
+    }
+
+    /// The number of distinct values in the interner.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Check if the interner is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    /// Intern a value.
+    ///
+    /// Returns the key of the equal value already in the interner (incrementing its reference
+    /// count) or, if there is none, the key at which this value was stored.
+    pub fn intern(&mut self, value: V) -> Ix {
+        let hash = self.hasher.hash_one(&value);
+        if let Some(idx) = self.find(hash, &value) {
+            self.slots[idx].refs += 1;
+            return Ix::from_usize(idx);
+        }
+        let idx = self.slots.next_index();
+        // Check that the key fits before touching the stash.
+        let key = Ix::from_usize(idx);
+        let next = match self.heads.entry(hash) {
+            hash_map::Entry::Occupied(mut head) => Some(head.insert(idx)),
+            hash_map::Entry::Vacant(head) => {
+                head.insert(idx);
+                None
+            }
+        };
+        self.slots.put(Slot {
+            value,
+            refs: 1,
+            next,
+        });
+        key
+    }
+
+    /// Get the key of the value equal to `value`, if any.
+    ///
+    /// Unlike `intern`, this doesn't change the value's reference count.
+    pub fn lookup<Q>(&self, value: &Q) -> Option<Ix>
+    where
+        V: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.find(self.hasher.hash_one(value), value)
+            .map(Ix::from_usize)
+    }
+
+    /// Get a reference to the value at `index`.
+    #[inline]
+    pub fn resolve(&self, index: Ix) -> Option<&V> {
+        self.slots.get(index.into_usize()).map(|slot| &slot.value)
+    }
+
+    /// Check if `index` refers to a value in this interner.
+    #[inline]
+    pub fn contains_key(&self, index: Ix) -> bool {
+        self.slots.contains_key(index.into_usize())
+    }
+
+    /// Get the number of times the value at `index` has been interned and not released.
+    #[inline]
+    pub fn ref_count(&self, index: Ix) -> Option<usize> {
+        self.slots.get(index.into_usize()).map(|slot| slot.refs)
+    }
+
+    /// Release one reference to the value at `index`.
+    ///
+    /// Removes and returns the value if that was the last reference. Returns `None` if references
+    /// remain or if `index` doesn't refer to a value.
+    pub fn release(&mut self, index: Ix) -> Option<V> {
+        let idx = index.into_usize();
+        let slot = self.slots.get_mut(idx)?;
+        slot.refs -= 1;
+        if slot.refs > 0 {
+            return None;
+        }
+        Some(self.remove_slot(idx))
+    }
+
+    /// Remove the value at `index` regardless of its reference count.
+    pub fn remove(&mut self, index: Ix) -> Option<V> {
+        let idx = index.into_usize();
+        if self.slots.contains_key(idx) {
+            Some(self.remove_slot(idx))
+        } else {
+            None
+        }
+    }
+
+    /// Iterate over the values in this interner.
+    ///
+    /// Returns an iterator that yields `(index, &value)` pairs.
+    #[inline]
+    pub fn iter(&self) -> Iter<'_, V, Ix> {
+        Iter {
+            inner: self.slots.iter(),
+            _marker: marker::PhantomData,
+        }
+    }
+
+    /// Remove every value from the interner.
+    pub fn clear(&mut self) {
+        self.heads.clear();
+        self.slots.clear();
+    }
+
+    /// Find the slot holding `value`.
+    fn find<Q>(&self, hash: u64, value: &Q) -> Option<usize>
+    where
+        V: Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        let mut next = self.heads.get(&hash).copied();
+        while let Some(idx) = next {
+            let slot = &self.slots[idx];
+            if slot.value.borrow() == value {
+                return Some(idx);
+            }
+            next = slot.next;
+        }
+        None
+    }
+
+    /// Take the (full) slot at `idx` out of the stash and unlink it from its hash chain.
+    fn remove_slot(&mut self, idx: usize) -> V {
+        let slot = self.slots.take(idx).expect("slot is full");
+        let hash = self.hasher.hash_one(&slot.value);
+        let head = self.heads.get_mut(&hash).expect("hash chain is missing");
+        if *head == idx {
+            match slot.next {
+                Some(next) => *head = next,
+                None => drop(self.heads.remove(&hash)),
+            }
+        } else {
+            let mut prev = *head;
+            while self.slots[prev].next != Some(idx) {
+                prev = self.slots[prev]
+                    .next
+                    .expect("slot is missing from its hash chain");
+            }
+            self.slots[prev].next = slot.next;
+        }
+        slot.value
+    }
+}
+
+impl<'a, V, Ix, S> IntoIterator for &'a Interner<V, Ix, S>
+where
+    V: Hash + Eq,
+    Ix: Index,
+    S: BuildHasher,
+{
+    type Item = (Ix, &'a V);
+    type IntoIter = Iter<'a, V, Ix>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<V, Ix, S> ops::Index<Ix> for Interner<V, Ix, S>
+where
+    V: Hash + Eq,
+    Ix: Index,
+    S: BuildHasher,
+{
+    type Output = V;
+    #[inline]
+    fn index(&self, index: Ix) -> &V {
+        self.resolve(index).expect("index out of bounds")
+    }
+}
+
+impl<V, Ix, S> Clone for Interner<V, Ix, S>
+where
+    V: Clone,
+    S: Clone,
+{
+    fn clone(&self) -> Self {
+        Interner {
+            slots: self.slots.clone(),
+            heads: self.heads.clone(),
+            hasher: self.hasher.clone(),
+            _marker: marker::PhantomData,
+        }
+    }
+}
+
+impl<V, Ix, S> fmt::Debug for Interner<V, Ix, S>
+where
+    V: fmt::Debug + Hash + Eq,
+    Ix: fmt::Debug + Index,
+    S: BuildHasher,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(self).finish()
+    }
+}
+
+impl<V, Ix, S> Default for Interner<V, Ix, S>
+where
+    V: Hash + Eq,
+    Ix: Index,
+    S: BuildHasher + Default,
+{
+    #[inline]
+    fn default() -> Self {
+        Interner::with_hasher(S::default())
+    }
+}
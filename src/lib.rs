@@ -12,12 +12,45 @@
 //! A stash can be serialized and deserialized with serde, preserving its _existing_ key/value
 //! mapping. This can be used to save/restore a stash to persistant storage.
 //!
+//! By default, stashes serialize as a dense sequence of every slot (vacant or not). A sparse map
+//! of keys to values can be selected with [`stash::sparse`](stash/sparse/index.html) (or
+//! [`unique_stash::sparse`](unique_stash/sparse/index.html)) and `#[serde(with = "...")]`. Human
+//! readable deserializers (e.g., JSON) accept both formats.
+//!
 //! However, in general, stashes make no guarantees on how keys are assigned. If stash **A** is
 //! serialized then deserialized into stash **B**, values inserted into stash **A** will likely be
 //! assigned different keys than values inserted into stash **B**.
+//!
+//! If you need the deserialized stash to assign exactly the same keys (e.g., to ship snapshots to a
+//! replica), serialize it with the versioned [`stash::exact`](stash/exact/index.html) or
+//! [`unique_stash::exact`](unique_stash/exact/index.html) format instead (e.g., with
+//! `#[serde(with = "stash::stash::exact")]`). This format also records the order in which vacant
+//! slots will be reused.
+//!
+//! # Parallel iteration
+//!
+//! With the `rayon` feature, `Stash` and `UniqueStash` provide `par_iter`, `par_iter_mut`,
+//! `par_values_mut`, and `into_par_iter`, which split the stash's slots between rayon's worker
+//! threads.
+//!
+//! # Without a heap
+//!
+//! `ArrayStash` and `ArrayUniqueStash` have a fixed capacity and keep their slots inline so they
+//! never allocate and can be used in `static` items.
+//!
+//! # Custom allocators
+//!
+//! `Stash` and `UniqueStash` take an optional allocator parameter. With the `allocator-api2`
+//! feature, they can be built in any [`allocator_api2`](https://docs.rs/allocator-api2)
+//! `Allocator` (e.g., a bump arena) with `new_in` and `with_capacity_in`. With the `nightly`
+//! feature (which implies `allocator-api2`), this is the standard library's (unstable)
+//! `Allocator` trait instead. Without either, the global allocator is the only option.
 #![cfg_attr(not(any(feature = "std", test)), no_std)]
+#![cfg_attr(feature = "nightly", feature(allocator_api))]
 
 extern crate alloc;
+#[cfg(feature = "allocator-api2")]
+extern crate allocator_api2;
 extern crate unreachable;
 
 #[cfg(test)]
@@ -30,16 +63,58 @@ extern crate serde;
 #[macro_use]
 extern crate serde_derive;
 
+#[cfg(feature = "rayon")]
+extern crate rayon;
+
 #[macro_use]
 mod iter_macro;
 
+mod allocator;
+
+pub mod array_stash;
+pub mod array_unique_stash;
+pub mod diff;
+pub mod error;
 pub mod index;
+#[cfg(feature = "std")]
+pub mod interner;
+#[cfg(feature = "serialization")]
+pub mod limits;
+pub mod persistent_stash;
+pub mod rc_stash;
+#[cfg(feature = "std")]
+pub mod sharded_stash;
 pub mod stash;
+pub mod tag;
 pub mod unique_stash;
 
+#[doc(inline)]
+pub use crate::array_stash::ArrayStash;
+#[doc(inline)]
+pub use crate::array_unique_stash::ArrayUniqueStash;
+#[doc(inline)]
+pub use crate::error::LookupError;
 #[doc(inline)]
 pub use crate::index::Index;
+#[cfg(feature = "std")]
+#[doc(inline)]
+pub use crate::interner::Interner;
+#[cfg(feature = "serialization")]
+#[doc(inline)]
+pub use crate::limits::Limits;
+#[doc(inline)]
+pub use crate::persistent_stash::PersistentStash;
+#[doc(inline)]
+pub use crate::rc_stash::RcStash;
+#[cfg(feature = "std")]
+#[doc(inline)]
+pub use crate::sharded_stash::ShardedStash;
 #[doc(inline)]
 pub use crate::stash::Stash;
 #[doc(inline)]
-pub use crate::unique_stash::{Tag, UniqueStash};
+pub use crate::tag::Tag;
+#[cfg(all(target_has_atomic = "64", target_has_atomic = "ptr"))]
+#[doc(inline)]
+pub use crate::unique_stash::ConcurrentUniqueStash;
+#[doc(inline)]
+pub use crate::unique_stash::UniqueStash;
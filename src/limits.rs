@@ -0,0 +1,88 @@
+//! Limits enforced when deserializing stashes.
+
+use core::cmp;
+use core::mem;
+
+/// Never preallocate more than this many bytes based on a size hint alone.
+const MAX_PREALLOC_BYTES: usize = 1024 * 1024;
+
+/// The most slots a stash in a sparse format may have when deserialized without explicit limits.
+const DEFAULT_MAX_SPARSE_SLOTS: usize = 1 << 20;
+
+/// Limits enforced when deserializing a stash from an untrusted source.
+///
+/// By default (i.e., through the `Deserialize` implementations and the `deserialize` functions of
+/// the alternative serialization formats), a deserialized stash may have as many slots and entries
+/// as the input describes (as long as the stash's index type can address them). The exception is
+/// the sparse format, which can describe a large number of vacant slots in very little space: a
+/// stash in the sparse format may have at most 2^20 slots unless limits are passed explicitly.
+///
+/// Pass limits to `Stash::deserialize_with_limits`, `UniqueStash::deserialize_with_limits`, or the
+/// `deserialize_with_limits` functions of the alternative serialization formats.
+///
+/// # Examples
+///
+/// ```
+/// use stash::{Limits, Stash};
+///
+/// let limits = Limits::new().max_slots(1024).max_len(512);
+///
+/// let mut de = serde_json::Deserializer::from_str(r#"{"len": 1000000, "entries": {}}"#);
+/// assert!(Stash::<u32>::deserialize_with_limits(&mut de, limits).is_err());
+///
+/// let mut de = serde_json::Deserializer::from_str(r#"{"len": 3, "entries": {"2": 7}}"#);
+/// let stash = Stash::<u32>::deserialize_with_limits(&mut de, limits).unwrap();
+/// assert_eq!(stash[2], 7);
+/// ```
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Limits {
+    pub(crate) max_slots: usize,
+    pub(crate) max_len: usize,
+    /// The most slots a stash in a sparse format may have (see `Limits::implicit`).
+    pub(crate) max_sparse_slots: usize,
+}
+
+impl Limits {
+    /// No limits.
+    pub const fn new() -> Self {
+        Limits {
+            max_slots: usize::MAX,
+            max_len: usize::MAX,
+            max_sparse_slots: usize::MAX,
+        }
+    }
+
+    /// The limits used when the caller didn't pass any: no limits, except for the number of
+    /// slots of a stash in a sparse format, which isn't bounded by the size of the input.
+    pub(crate) const fn implicit() -> Self {
+        Limits {
+            max_sparse_slots: DEFAULT_MAX_SPARSE_SLOTS,
+            ..Limits::new()
+        }
+    }
+
+    /// Limit the number of slots (occupied or vacant) in the deserialized stash.
+    pub const fn max_slots(mut self, max_slots: usize) -> Self {
+        self.max_slots = max_slots;
+        self
+    }
+
+    /// Limit the number of values in the deserialized stash.
+    pub const fn max_len(mut self, max_len: usize) -> Self {
+        self.max_len = max_len;
+        self
+    }
+
+    /// The number of `T`s to preallocate given an (untrusted) size hint.
+    pub(crate) fn initial_capacity<T>(&self, hint: Option<usize>) -> usize {
+        let max_prealloc = MAX_PREALLOC_BYTES / cmp::max(mem::size_of::<T>(), 1);
+        cmp::min(cmp::min(hint.unwrap_or(8), self.max_slots), max_prealloc)
+    }
+}
+
+impl Default for Limits {
+    #[inline]
+    fn default() -> Self {
+        Limits::new()
+    }
+}
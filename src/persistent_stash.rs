@@ -0,0 +1,478 @@
+//! A `Stash` with cheap, immutable snapshots.
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt;
+use core::marker;
+use core::mem;
+use core::ops;
+
+use crate::index::Index;
+use crate::stash::entry::{self, Entry};
+
+/// The number of slots in a leaf (and children of a branch), as a power of two.
+const NODE_BITS: usize = 6;
+const NODE_SIZE: usize = 1 << NODE_BITS;
+const NODE_MASK: usize = NODE_SIZE - 1;
+
+/// A node of the radix tree the slots are stored in.
+///
+/// Every leaf is at the same depth: nodes `height` levels above the leaves are branches, the
+/// rest are leaves. All nodes but the rightmost one on each level are full.
+#[derive(Clone)]
+enum Node<V> {
+    Branch(Vec<Arc<Node<V>>>),
+    Leaf(Vec<Entry<V>>),
+}
+
+impl<V> Node<V> {
+    /// An empty node `height` levels above the leaves.
+    fn empty(height: usize) -> Node<V> {
+        if height == 0 {
+            Node::Leaf(Vec::with_capacity(NODE_SIZE))
+        } else {
+            Node::Branch(Vec::with_capacity(NODE_SIZE))
+        }
+    }
+
+    /// Get the entry at `idx` in the subtree rooted at this node, `height` levels above the
+    /// leaves. `idx` must be in bounds.
+    fn get(&self, height: usize, idx: usize) -> &Entry<V> {
+        let mut node = self;
+        let mut level = height;
+        loop {
+            match node {
+                Node::Branch(children) => {
+                    node = &children[(idx >> (NODE_BITS * level)) & NODE_MASK];
+                    level -= 1;
+                }
+                Node::Leaf(entries) => return &entries[idx & NODE_MASK],
+            }
+        }
+    }
+}
+
+/// A `Stash` that can be snapshotted in `O(1)`.
+///
+/// Slots are stored in a radix tree of reference counted nodes (with up to 64 slots or children
+/// each) that are shared between a stash and its snapshots. Modifying a slot only copies the
+/// shared nodes on the path from the root to that slot, i.e., `O(log n)` nodes, so readers can
+/// keep iterating over a frozen snapshot while writers keep mutating the stash.
+///
+/// Keys are assigned exactly as they would be by a `Stash` subjected to the same sequence of
+/// operations (and come with the same guarantees).
+///
+/// As shared nodes have to be copied before they can be modified, all modifications (except
+/// `clear`) require `V: Clone`.
+///
+/// # Examples
+///
+/// ```
+/// use stash::PersistentStash;
+///
+/// let mut stash = PersistentStash::new();
+/// let a = stash.put("a");
+///
+/// let snapshot = stash.snapshot();
+/// stash.take(a);
+/// let b = stash.put("b");
+///
+/// assert_eq!(a, b);
+/// assert_eq!(stash[b], "b");
+/// assert_eq!(snapshot[a], "a");
+/// ```
+pub struct PersistentStash<V, Ix = usize> {
+    root: Arc<Node<V>>,
+    /// The number of levels of branches above the leaves.
+    height: usize,
+    slots: usize,
+    size: usize,
+    next_free: usize,
+    _marker: marker::PhantomData<fn(Ix) -> Ix>,
+}
+
+/// Iterator over the `(index, &entry)` pairs of every slot.
+struct Slots<'a, V: 'a> {
+    root: &'a Node<V>,
+    height: usize,
+    front: usize,
+    back: usize,
+}
+
+impl<'a, V> Slots<'a, V> {
+    fn get(&self, idx: usize) -> (usize, &'a Entry<V>) {
+        (idx, self.root.get(self.height, idx))
+    }
+}
+
+impl<'a, V> Iterator for Slots<'a, V> {
+    type Item = (usize, &'a Entry<V>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.front == self.back {
+            return None;
+        }
+        self.front += 1;
+        Some(self.get(self.front - 1))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.back - self.front;
+        (len, Some(len))
+    }
+}
+
+impl<'a, V> DoubleEndedIterator for Slots<'a, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.front == self.back {
+            return None;
+        }
+        self.back -= 1;
+        Some(self.get(self.back))
+    }
+}
+
+/// Iterator over the `(index, &value)` pairs.
+pub struct Iter<'a, V: 'a, Ix: Index> {
+    inner: Slots<'a, V>,
+    len: usize,
+    _marker: marker::PhantomData<fn() -> Ix>,
+}
+
+/// Iterator over references to the values in the stash.
+pub struct Values<'a, V: 'a> {
+    inner: Slots<'a, V>,
+    len: usize,
+}
+
+impl_iter!(Iter, (<'a, V, Ix>), (Ix, &'a V), entry::value_index_ref, (where Ix: Index));
+impl_iter!(Values, (<'a, V>), &'a V, |(_, e)| entry::value_ref(e), ());
+
+impl<V> PersistentStash<V, usize> {
+    /// Constructs a new, empty `PersistentStash<V, usize>`.
+    ///
+    /// This is a convenience method. Use `PersistentStash::default` for
+    /// a constructor that is generic in the type of index used.
+    #[inline]
+    pub fn new() -> Self {
+        PersistentStash::default()
+    }
+}
+
+impl<V, Ix> PersistentStash<V, Ix>
+where
+    Ix: Index,
+{
+    /// Take an `O(1)` snapshot of this stash.
+    ///
+    /// The snapshot is an independent stash: it won't observe changes made to this stash (and
+    /// vice versa). It can also be modified, in which case it only copies the nodes it touches.
+    #[inline]
+    pub fn snapshot(&self) -> Self {
+        PersistentStash {
+            root: self.root.clone(),
+            height: self.height,
+            slots: self.slots,
+            size: self.size,
+            next_free: self.next_free,
+            _marker: marker::PhantomData,
+        }
+    }
+
+    /// Get the number of items in the stash.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.size
+    }
+
+    /// Check if this `PersistentStash<V, Ix>` is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    /// Get the index that would be returned from next call to `put`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the size of the `PersistentStash<V, Ix>` would overflow the `Ix` index type.
+    pub fn next_index(&self) -> Ix {
+        Ix::from_usize(self.next_free)
+    }
+
+    /// Iterate over the items in this `PersistentStash<V, Ix>`.
+    ///
+    /// Returns an iterator that yields `(index, &value)` pairs.
+    #[inline]
+    pub fn iter(&self) -> Iter<'_, V, Ix> {
+        Iter {
+            len: self.size,
+            inner: self.slots(),
+            _marker: marker::PhantomData,
+        }
+    }
+
+    /// Iterate over the values in this `PersistentStash<V, Ix>` by reference.
+    #[inline]
+    pub fn values(&self) -> Values<'_, V> {
+        Values {
+            len: self.size,
+            inner: self.slots(),
+        }
+    }
+
+    /// Check if there's a value at `index`.
+    #[inline]
+    pub fn contains_key(&self, index: Ix) -> bool {
+        self.get(index).is_some()
+    }
+
+    /// Get a reference to the value at `index`.
+    #[inline]
+    pub fn get(&self, index: Ix) -> Option<&V> {
+        match self.entry(index.into_usize()) {
+            Some(Entry::Full(v)) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Clear the stash. Cleared stash will give the same keys as a
+    /// new stash for subsequent puts.
+    ///
+    /// Snapshots are unaffected.
+    pub fn clear(&mut self) {
+        self.root = Arc::new(Node::Leaf(Vec::new()));
+        self.height = 0;
+        self.slots = 0;
+        self.size = 0;
+        self.next_free = 0;
+    }
+
+    fn slots(&self) -> Slots<'_, V> {
+        Slots {
+            root: &self.root,
+            height: self.height,
+            front: 0,
+            back: self.slots,
+        }
+    }
+
+    fn entry(&self, idx: usize) -> Option<&Entry<V>> {
+        if idx < self.slots {
+            Some(self.root.get(self.height, idx))
+        } else {
+            None
+        }
+    }
+}
+
+impl<V, Ix> PersistentStash<V, Ix>
+where
+    V: Clone,
+    Ix: Index,
+{
+    /// Put a value into the stash.
+    ///
+    /// Returns the index at which this value was stored.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the size of the `PersistentStash<V, Ix>` would overflow the `Ix` index type.
+    pub fn put(&mut self, value: V) -> Ix {
+        let loc = self.next_free;
+        debug_assert!(loc <= self.slots);
+        let index = Ix::from_usize(loc);
+
+        if loc == self.slots {
+            self.push_entry(Entry::Full(value));
+            self.next_free = self.slots;
+        } else {
+            match mem::replace(self.entry_mut(loc), Entry::Full(value)) {
+                Entry::Empty(next_free) => self.next_free = next_free,
+                Entry::Full(_) => panic!("expected no entry"),
+            }
+        }
+        self.size += 1;
+        index
+    }
+
+    /// Take an item from a slot (if non empty).
+    pub fn take(&mut self, index: Ix) -> Option<V> {
+        let take_index = index.into_usize();
+        // Check first so we don't copy a chunk for nothing.
+        if let Some(Entry::Full(_)) = self.entry(take_index) {
+            let next_free = self.next_free;
+            match mem::replace(self.entry_mut(take_index), Entry::Empty(next_free)) {
+                Entry::Full(value) => {
+                    self.next_free = take_index;
+                    self.size -= 1;
+                    Some(value)
+                }
+                Entry::Empty(_) => unreachable!(),
+            }
+        } else {
+            None
+        }
+    }
+
+    /// Get a mutable reference to the value at `index`.
+    ///
+    /// Copies the chunk containing `index` if it's shared with a snapshot.
+    pub fn get_mut(&mut self, index: Ix) -> Option<&mut V> {
+        let idx = index.into_usize();
+        match self.entry(idx) {
+            Some(Entry::Full(_)) => match self.entry_mut(idx) {
+                Entry::Full(v) => Some(v),
+                Entry::Empty(_) => unreachable!(),
+            },
+            _ => None,
+        }
+    }
+
+    /// Get a unique reference to the entry at `idx`, copying the nodes on the path to it if
+    /// necessary. `idx` must be in bounds.
+    fn entry_mut(&mut self, idx: usize) -> &mut Entry<V> {
+        let mut node = Arc::make_mut(&mut self.root);
+        let mut level = self.height;
+        loop {
+            match node {
+                Node::Branch(children) => {
+                    node = Arc::make_mut(&mut children[(idx >> (NODE_BITS * level)) & NODE_MASK]);
+                    level -= 1;
+                }
+                Node::Leaf(entries) => return &mut entries[idx & NODE_MASK],
+            }
+        }
+    }
+
+    /// Append a slot, copying the nodes on the path to it if necessary.
+    fn push_entry(&mut self, entry: Entry<V>) {
+        // Add a level if the tree is full. The shift only overflows once the tree can hold more
+        // slots than fit in memory.
+        let bits = NODE_BITS * (self.height + 1);
+        if bits < usize::BITS as usize && self.slots == 1 << bits {
+            let old = mem::replace(&mut self.root, Arc::new(Node::empty(self.height + 1)));
+            match Arc::get_mut(&mut self.root) {
+                Some(Node::Branch(children)) => children.push(old),
+                _ => unreachable!(),
+            }
+            self.height += 1;
+        }
+
+        let idx = self.slots;
+        let mut node = Arc::make_mut(&mut self.root);
+        let mut level = self.height;
+        loop {
+            match node {
+                Node::Branch(children) => {
+                    let child = (idx >> (NODE_BITS * level)) & NODE_MASK;
+                    if child == children.len() {
+                        children.push(Arc::new(Node::empty(level - 1)));
+                    }
+                    node = Arc::make_mut(&mut children[child]);
+                    level -= 1;
+                }
+                Node::Leaf(entries) => {
+                    entries.push(entry);
+                    break;
+                }
+            }
+        }
+        self.slots += 1;
+    }
+}
+
+impl<V, Ix: Index> Clone for PersistentStash<V, Ix> {
+    /// Equivalent to `snapshot`.
+    #[inline]
+    fn clone(&self) -> Self {
+        self.snapshot()
+    }
+}
+
+impl<'a, V, Ix: Index> IntoIterator for &'a PersistentStash<V, Ix> {
+    type Item = (Ix, &'a V);
+    type IntoIter = Iter<'a, V, Ix>;
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<V, Ix> fmt::Debug for PersistentStash<V, Ix>
+where
+    V: fmt::Debug,
+    Ix: fmt::Debug + Index,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(self).finish()
+    }
+}
+
+impl<V, Ix: Index> ops::Index<Ix> for PersistentStash<V, Ix> {
+    type Output = V;
+    #[inline]
+    fn index(&self, index: Ix) -> &V {
+        self.get(index).expect("index out of bounds")
+    }
+}
+
+impl<V: Clone, Ix: Index> ops::IndexMut<Ix> for PersistentStash<V, Ix> {
+    #[inline]
+    fn index_mut(&mut self, index: Ix) -> &mut V {
+        self.get_mut(index).expect("index out of bounds")
+    }
+}
+
+impl<V, Ix: Index> Default for PersistentStash<V, Ix> {
+    #[inline]
+    fn default() -> Self {
+        PersistentStash {
+            root: Arc::new(Node::Leaf(Vec::new())),
+            height: 0,
+            slots: 0,
+            size: 0,
+            next_free: 0,
+            _marker: marker::PhantomData,
+        }
+    }
+}
+
+impl<V, Ix: Index> From<crate::Stash<V, Ix>> for PersistentStash<V, Ix> {
+    /// Convert a `Stash` into a `PersistentStash`, preserving key assignment.
+    fn from(stash: crate::Stash<V, Ix>) -> Self {
+        let (data, size, next_free) = stash.into_raw_parts();
+        let slots = data.len();
+        // Build the tree bottom up, one level at a time.
+        let mut nodes = group(data.into_iter(), Node::Leaf);
+        let mut height = 0;
+        while nodes.len() > 1 {
+            nodes = group(nodes.into_iter(), Node::Branch);
+            height += 1;
+        }
+        PersistentStash {
+            root: nodes
+                .pop()
+                .unwrap_or_else(|| Arc::new(Node::Leaf(Vec::new()))),
+            height,
+            slots,
+            size,
+            next_free,
+            _marker: marker::PhantomData,
+        }
+    }
+}
+
+/// Split `items` into full nodes (except for the last one).
+fn group<T, V, I>(mut items: I, node: fn(Vec<T>) -> Node<V>) -> Vec<Arc<Node<V>>>
+where
+    I: ExactSizeIterator<Item = T>,
+{
+    let mut nodes = Vec::with_capacity(items.len().div_ceil(NODE_SIZE));
+    while items.len() > 0 {
+        let mut children = Vec::with_capacity(NODE_SIZE);
+        children.extend(items.by_ref().take(NODE_SIZE));
+        nodes.push(Arc::new(node(children)));
+    }
+    nodes
+}
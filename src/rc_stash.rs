@@ -0,0 +1,280 @@
+//! A `Stash` of reference-counted values.
+
+use core::fmt;
+use core::ops;
+
+use crate::index::Index;
+use crate::stash::{self, Stash};
+
+#[derive(Clone)]
+struct Counted<V> {
+    value: V,
+    refs: usize,
+}
+
+/// Iterator over the `(index, &value)` pairs.
+pub struct Iter<'a, V: 'a, Ix: Index> {
+    inner: stash::Iter<'a, Counted<V>, Ix>,
+}
+
+impl<'a, V, Ix: Index> Iterator for Iter<'a, V, Ix> {
+    type Item = (Ix, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (idx, counted) = self.inner.next()?;
+        Some((idx, &counted.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, V, Ix: Index> ExactSizeIterator for Iter<'a, V, Ix> {}
+
+impl<'a, V, Ix: Index> DoubleEndedIterator for Iter<'a, V, Ix> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let (idx, counted) = self.inner.next_back()?;
+        Some((idx, &counted.value))
+    }
+}
+
+/// Iterator over references to the values in the stash.
+pub struct Values<'a, V: 'a> {
+    inner: stash::Values<'a, Counted<V>>,
+}
+
+impl<'a, V> Iterator for Values<'a, V> {
+    type Item = &'a V;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|counted| &counted.value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, V> ExactSizeIterator for Values<'a, V> {}
+
+impl<'a, V> DoubleEndedIterator for Values<'a, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|counted| &counted.value)
+    }
+}
+
+/// A `Stash` that removes values when their reference count drops to zero.
+///
+/// `put` stores a value with a reference count of one, `retain` increments it, and `release`
+/// decrements it. The value is removed (and its slot returned to the free list) exactly when the
+/// count reaches zero so keys are assigned just like `Stash` would assign them if `take` were
+/// called at that point.
+///
+/// Retaining or releasing a key that doesn't refer to a value is a bug (e.g., a double release)
+/// and panics when debug assertions are enabled.
+///
+/// # Examples
+///
+/// ```
+/// use stash::RcStash;
+///
+/// let mut stash = RcStash::new();
+/// let conn = stash.put("connection");
+/// stash.retain(conn);
+/// assert_eq!(stash.ref_count(conn), Some(2));
+///
+/// assert_eq!(stash.release(conn), None);
+/// assert_eq!(stash[conn], "connection");
+/// assert_eq!(stash.release(conn), Some("connection"));
+/// assert!(stash.is_empty());
+/// ```
+pub struct RcStash<V, Ix = usize> {
+    inner: Stash<Counted<V>, Ix>,
+}
+
+impl<V> RcStash<V, usize> {
+    /// Constructs a new, empty `RcStash<V, usize>`.
+    ///
+    /// This is a convenience method. Use `RcStash::default` for a constructor that is generic in
+    /// the type of index used.
+    ///
+    /// The stash will not allocate until elements are put onto it.
+    #[inline]
+    pub const fn new() -> Self {
+        RcStash {
+            inner: Stash::new(),
+        }
+    }
+
+    /// Constructs a new, empty `RcStash<V, usize>` with the specified capacity.
+    ///
+    /// See `Stash::with_capacity`.
+    #[inline]
+    pub fn with_capacity(capacity: usize) -> Self {
+        RcStash {
+            inner: Stash::with_capacity(capacity),
+        }
+    }
+}
+
+impl<V, Ix> RcStash<V, Ix>
+where
+    Ix: Index,
+{
+    /// The number of items in the stash.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Check if the stash is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// Put a value into the stash with a reference count of one.
+    ///
+    /// Returns the index at which this value was stored.
+    #[inline]
+    pub fn put(&mut self, value: V) -> Ix {
+        self.inner.put(Counted { value, refs: 1 })
+    }
+
+    /// Increment the reference count of the value at `index`.
+    ///
+    /// Returns `false` (and panics when debug assertions are enabled) if `index` doesn't refer to
+    /// a value.
+    pub fn retain(&mut self, index: Ix) -> bool {
+        match self.inner.get_mut(index) {
+            Some(counted) => {
+                counted.refs += 1;
+                true
+            }
+            None => {
+                debug_assert!(false, "retained a vacant RcStash key");
+                false
+            }
+        }
+    }
+
+    /// Decrement the reference count of the value at `index`.
+    ///
+    /// Removes and returns the value if this was the last reference. Returns `None` if references
+    /// remain or (panicking when debug assertions are enabled) if `index` doesn't refer to a value.
+    pub fn release(&mut self, index: Ix) -> Option<V> {
+        let idx = index.into_usize();
+        match self.inner.get_mut(Ix::from_usize(idx)) {
+            Some(counted) if counted.refs > 1 => {
+                counted.refs -= 1;
+                None
+            }
+            Some(_) => self.inner.take(Ix::from_usize(idx)).map(|c| c.value),
+            None => {
+                debug_assert!(false, "released a vacant RcStash key");
+                None
+            }
+        }
+    }
+
+    /// Get the reference count of the value at `index`.
+    #[inline]
+    pub fn ref_count(&self, index: Ix) -> Option<usize> {
+        self.inner.get(index).map(|counted| counted.refs)
+    }
+
+    /// Take the value at `index` out of the stash regardless of its reference count.
+    #[inline]
+    pub fn take(&mut self, index: Ix) -> Option<V> {
+        self.inner.take(index).map(|counted| counted.value)
+    }
+
+    /// Check if `index` refers to a value in this stash.
+    #[inline]
+    pub fn contains_key(&self, index: Ix) -> bool {
+        self.inner.contains_key(index)
+    }
+
+    /// Get a reference to the value at `index`.
+    #[inline]
+    pub fn get(&self, index: Ix) -> Option<&V> {
+        self.inner.get(index).map(|counted| &counted.value)
+    }
+
+    /// Get a mutable reference to the value at `index`.
+    #[inline]
+    pub fn get_mut(&mut self, index: Ix) -> Option<&mut V> {
+        self.inner.get_mut(index).map(|counted| &mut counted.value)
+    }
+
+    /// Iterate over the items in this `RcStash<V>`.
+    ///
+    /// Returns an iterator that yields `(index, &value)` pairs.
+    #[inline]
+    pub fn iter(&self) -> Iter<'_, V, Ix> {
+        Iter {
+            inner: self.inner.iter(),
+        }
+    }
+
+    /// Iterate over the values in this `RcStash<V>` by reference.
+    #[inline]
+    pub fn values(&self) -> Values<'_, V> {
+        Values {
+            inner: self.inner.values(),
+        }
+    }
+}
+
+impl<'a, V, Ix: Index> IntoIterator for &'a RcStash<V, Ix> {
+    type Item = (Ix, &'a V);
+    type IntoIter = Iter<'a, V, Ix>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<V, Ix: Index> ops::Index<Ix> for RcStash<V, Ix> {
+    type Output = V;
+    #[inline]
+    fn index(&self, index: Ix) -> &V {
+        self.get(index).expect("index out of bounds")
+    }
+}
+
+impl<V, Ix: Index> ops::IndexMut<Ix> for RcStash<V, Ix> {
+    #[inline]
+    fn index_mut(&mut self, index: Ix) -> &mut V {
+        self.get_mut(index).expect("index out of bounds")
+    }
+}
+
+impl<V: Clone, Ix: Clone> Clone for RcStash<V, Ix> {
+    fn clone(&self) -> Self {
+        RcStash {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<V, Ix> fmt::Debug for RcStash<V, Ix>
+where
+    V: fmt::Debug,
+    Ix: fmt::Debug + Index,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(self).finish()
+    }
+}
+
+impl<V, Ix: Index> Default for RcStash<V, Ix> {
+    #[inline]
+    fn default() -> Self {
+        RcStash {
+            inner: Stash::default(),
+        }
+    }
+}
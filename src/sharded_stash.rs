@@ -0,0 +1,241 @@
+//! A `Stash` split into independently locked shards.
+
+use alloc::vec::Vec;
+use std::cell::Cell;
+use std::fmt;
+use std::marker;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::thread;
+
+use crate::index::Index;
+use crate::stash::Stash;
+
+/// A `Stash` that can be shared between threads, split into `Mutex`-protected shards.
+///
+/// Each thread puts values into "its" shard so threads inserting concurrently rarely contend on
+/// the same lock. The shard number is stored in the highest of the key's `Index::BITS` bits so
+/// lookups by key go straight to the right shard.
+///
+/// Keys are only unique and reused within a shard: unlike `Stash`, keys are neither dense nor
+/// deterministic.
+///
+/// # Examples
+///
+/// ```
+/// use stash::ShardedStash;
+/// use std::thread;
+///
+/// let stash = ShardedStash::with_shards(4);
+/// let keys: Vec<usize> = thread::scope(|s| {
+///     let stash = &stash;
+///     let workers: Vec<_> = (0..4).map(|i| s.spawn(move || stash.put(i))).collect();
+///     workers.into_iter().map(|w| w.join().unwrap()).collect()
+/// });
+/// assert_eq!(stash.len(), 4);
+/// assert_eq!(*stash.get(keys[2]).unwrap(), 2);
+/// assert_eq!(stash.take(keys[2]), Some(2));
+/// assert!(stash.get(keys[2]).is_none());
+/// ```
+pub struct ShardedStash<V, Ix = usize> {
+    shards: Vec<Mutex<Stash<V>>>,
+    /// The number of low bits of a key holding the index within the shard.
+    local_bits: u32,
+    _marker: marker::PhantomData<fn(Ix) -> Ix>,
+}
+
+/// A locked reference to a value in a `ShardedStash`. See `ShardedStash::get`.
+///
+/// The value's shard is locked while the guard exists.
+pub struct Guard<'a, V: 'a> {
+    shard: MutexGuard<'a, Stash<V>>,
+    idx: usize,
+}
+
+/// Get the shard the current thread puts values into (modulo the number of shards).
+fn thread_shard() -> usize {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    thread_local! {
+        static SHARD: Cell<Option<usize>> = const { Cell::new(None) };
+    }
+    SHARD.with(|shard| {
+        shard.get().unwrap_or_else(|| {
+            let next = NEXT.fetch_add(1, Ordering::Relaxed);
+            shard.set(Some(next));
+            next
+        })
+    })
+}
+
+/// Lock a shard. The stash can't be left inconsistent by a panic so poisoning is ignored.
+fn lock<V>(shard: &Mutex<Stash<V>>) -> MutexGuard<'_, Stash<V>> {
+    shard.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+impl<V, Ix> ShardedStash<V, Ix>
+where
+    Ix: Index,
+{
+    /// Constructs a new, empty `ShardedStash` with one shard per available CPU (rounded up to
+    /// a power of two).
+    pub fn new() -> Self {
+        Self::with_shards(thread::available_parallelism().map_or(1, |n| n.get()))
+    }
+
+    /// Constructs a new, empty `ShardedStash` with (at least) `shards` shards.
+    ///
+    /// The number of shards is rounded up to a power of two.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `shards` is zero, if the shard number wouldn't leave any room for the index
+    /// within the shard in `Ix::BITS` bits, or if `Ix` can't represent every `Ix::BITS`-bit key
+    /// (i.e., `Ix` doesn't override `Index::BITS` to match its range).
+    pub fn with_shards(shards: usize) -> Self {
+        assert!(shards > 0, "a ShardedStash needs at least one shard");
+        let shards = shards.next_power_of_two();
+        let shard_bits = shards.trailing_zeros();
+        assert!(shard_bits < Ix::BITS, "too many shards for the index type");
+        // The key of the last slot of the last shard.
+        let max_key = usize::MAX >> (usize::BITS - Ix::BITS);
+        assert!(
+            Ix::try_from_usize(max_key).is_some(),
+            "the index type can't represent {}-bit keys (see `Index::BITS`)",
+            Ix::BITS
+        );
+        ShardedStash {
+            shards: (0..shards).map(|_| Mutex::new(Stash::new())).collect(),
+            local_bits: Ix::BITS - shard_bits,
+            _marker: marker::PhantomData,
+        }
+    }
+
+    /// Get the number of shards.
+    #[inline]
+    pub fn shards(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Get the number of items in the stash.
+    ///
+    /// Locks every shard in turn so, if other threads are modifying the stash, this is only an
+    /// approximation.
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(|shard| lock(shard).len()).sum()
+    }
+
+    /// Check if the stash is empty. See `len`.
+    pub fn is_empty(&self) -> bool {
+        self.shards.iter().all(|shard| lock(shard).is_empty())
+    }
+
+    /// Put a value into the current thread's shard.
+    ///
+    /// Returns the index at which this value was stored.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the shard is full (i.e., the index within the shard doesn't fit in the bits
+    /// left over by the shard number).
+    pub fn put(&self, value: V) -> Ix {
+        let shard_idx = thread_shard() & (self.shards.len() - 1);
+        let mut shard = lock(&self.shards[shard_idx]);
+        let local = shard.next_index();
+        assert!(
+            local.checked_shr(self.local_bits).unwrap_or(0) == 0,
+            "ShardedStash shard is full"
+        );
+        shard.put(value);
+        Ix::from_usize(shard_idx.checked_shl(self.local_bits).unwrap_or(0) | local)
+    }
+
+    /// Get a locked reference to the value at `index`.
+    ///
+    /// Keeps the value's shard locked until the guard is dropped.
+    pub fn get(&self, index: Ix) -> Option<Guard<'_, V>> {
+        let (shard, idx) = self.locate(index)?;
+        let shard = lock(&self.shards[shard]);
+        if shard.contains_key(idx) {
+            Some(Guard { shard, idx })
+        } else {
+            None
+        }
+    }
+
+    /// Get a mutable reference to the value at `index`.
+    pub fn get_mut(&mut self, index: Ix) -> Option<&mut V> {
+        let (shard, idx) = self.locate(index)?;
+        self.shards[shard]
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get_mut(idx)
+    }
+
+    /// Check if `index` refers to a value in this stash.
+    pub fn contains_key(&self, index: Ix) -> bool {
+        self.locate(index)
+            .is_some_and(|(shard, idx)| lock(&self.shards[shard]).contains_key(idx))
+    }
+
+    /// Take an item from a slot (if non empty).
+    pub fn take(&self, index: Ix) -> Option<V> {
+        let (shard, idx) = self.locate(index)?;
+        lock(&self.shards[shard]).take(idx)
+    }
+
+    /// Split a key into its shard number and the index within the shard.
+    fn locate(&self, index: Ix) -> Option<(usize, usize)> {
+        let key = index.into_usize();
+        let shard = key.checked_shr(self.local_bits).unwrap_or(0);
+        let idx = key & (usize::MAX >> (usize::BITS - self.local_bits));
+        if shard < self.shards.len() {
+            Some((shard, idx))
+        } else {
+            None
+        }
+    }
+}
+
+impl<V, Ix> Default for ShardedStash<V, Ix>
+where
+    Ix: Index,
+{
+    #[inline]
+    fn default() -> Self {
+        ShardedStash::new()
+    }
+}
+
+impl<V, Ix> fmt::Debug for ShardedStash<V, Ix>
+where
+    Ix: Index,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ShardedStash")
+            .field("shards", &self.shards())
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+impl<'a, V> Deref for Guard<'a, V> {
+    type Target = V;
+    #[inline]
+    fn deref(&self) -> &V {
+        &self.shard[self.idx]
+    }
+}
+
+impl<'a, V> DerefMut for Guard<'a, V> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut V {
+        &mut self.shard[self.idx]
+    }
+}
+
+impl<'a, V: fmt::Debug> fmt::Debug for Guard<'a, V> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
@@ -0,0 +1,177 @@
+use alloc::vec::Vec;
+use core::cmp;
+
+use super::{Entry, Stash};
+use crate::allocator::Allocator;
+use crate::diff::{Change, PatchError};
+use crate::index::Index;
+
+/// A change to a single slot.
+enum SlotChange<V> {
+    Add(V),
+    Remove,
+    Modify(V),
+}
+
+fn value<V>(entry: Option<&Entry<V>>) -> Option<&V> {
+    match entry {
+        Some(Entry::Full(value)) => Some(value),
+        _ => None,
+    }
+}
+
+impl<V, Ix, A> Stash<V, Ix, A>
+where
+    Ix: Index,
+    A: Allocator,
+{
+    /// Compute the changes needed to turn this stash into `other`.
+    ///
+    /// Changes are ordered by index and followed by a `Change::FreeList` if the number of slots
+    /// or the order in which vacant slots will be reused differ. Values are compared with
+    /// `PartialEq`.
+    pub fn diff(&self, other: &Self) -> Vec<Change<Ix, V>>
+    where
+        V: Clone + PartialEq,
+    {
+        let mut changes = Vec::new();
+        for idx in 0..cmp::max(self.data.len(), other.data.len()) {
+            let index = || Ix::from_usize(idx);
+            match (value(self.data.get(idx)), value(other.data.get(idx))) {
+                (None, Some(v)) => changes.push(Change::Added {
+                    index: index(),
+                    value: v.clone(),
+                }),
+                (Some(_), None) => changes.push(Change::Removed { index: index() }),
+                (Some(a), Some(b)) if a != b => changes.push(Change::Modified {
+                    index: index(),
+                    value: b.clone(),
+                }),
+                _ => (),
+            }
+        }
+        if self.data.len() != other.data.len() || !self.free_list().eq(other.free_list()) {
+            changes.push(Change::FreeList {
+                len: other.data.len(),
+                order: other.free_list().map(Ix::from_usize).collect(),
+            });
+        }
+        changes
+    }
+
+    /// Apply changes computed by `diff`.
+    ///
+    /// Changes must be ordered by index (as returned by `diff`). The patch is checked before
+    /// anything is modified: if it doesn't apply to this stash (e.g., it removes a value from a
+    /// vacant slot or leaves the free list inconsistent), the stash is left unmodified.
+    pub fn apply_patch<I>(&mut self, patch: I) -> Result<(), PatchError>
+    where
+        I: IntoIterator<Item = Change<Ix, V>>,
+    {
+        let mut changes: Vec<(usize, SlotChange<V>)> = Vec::new();
+        let mut free_list: Option<(usize, Vec<usize>)> = None;
+        for change in patch {
+            let (index, change) = match change {
+                Change::Added { index, value } => (index, SlotChange::Add(value)),
+                Change::Removed { index } => (index, SlotChange::Remove),
+                Change::Modified { index, value } => (index, SlotChange::Modify(value)),
+                Change::FreeList { len, order } => {
+                    if free_list.is_some() {
+                        return Err(PatchError::InvalidFreeList);
+                    }
+                    free_list = Some((len, order.into_iter().map(Ix::into_usize).collect()));
+                    continue;
+                }
+            };
+            let idx = index.into_usize();
+            if changes.last().is_some_and(|&(prev, _)| prev >= idx) {
+                return Err(PatchError::Conflict { index: idx });
+            }
+            changes.push((idx, change));
+        }
+
+        // Check the changes against the current contents.
+        let new_len = free_list.as_ref().map_or(self.data.len(), |&(len, _)| len);
+        // Every new slot must be added or listed as vacant. Check this before allocating.
+        if let Some((len, ref order)) = free_list {
+            let added = changes
+                .iter()
+                .filter(|(_, change)| matches!(change, SlotChange::Add(_)))
+                .count();
+            if len - cmp::min(len, self.data.len()) > added + order.len() {
+                return Err(PatchError::InvalidFreeList);
+            }
+        }
+        let mut full: Vec<bool> = (0..cmp::max(new_len, self.data.len()))
+            .map(|idx| value(self.data.get(idx)).is_some())
+            .collect();
+        let mut delta = 0isize;
+        for &(idx, ref change) in &changes {
+            let was_full = full.get(idx).copied().unwrap_or(false);
+            match *change {
+                SlotChange::Add(_) if !was_full && idx < new_len => {
+                    full[idx] = true;
+                    delta += 1;
+                }
+                SlotChange::Remove if was_full => {
+                    full[idx] = false;
+                    delta -= 1;
+                }
+                SlotChange::Modify(_) if was_full => (),
+                _ => return Err(PatchError::Conflict { index: idx }),
+            }
+        }
+
+        match free_list {
+            None => {
+                // Without a free list, the set of vacant slots can't change.
+                if changes
+                    .iter()
+                    .any(|&(idx, _)| full[idx] != value(self.data.get(idx)).is_some())
+                {
+                    return Err(PatchError::InvalidFreeList);
+                }
+                for (idx, change) in changes {
+                    if let SlotChange::Modify(value) = change {
+                        self.data[idx] = Entry::Full(value);
+                    }
+                }
+            }
+            Some((len, order)) => {
+                // Every remaining slot past the end must be vacant, and the free list must list
+                // every vacant slot exactly once.
+                if full[len..].iter().any(|&f| f) {
+                    return Err(PatchError::InvalidFreeList);
+                }
+                let vacant = full[..len].iter().filter(|&&f| !f).count();
+                if order.len() != vacant {
+                    return Err(PatchError::InvalidFreeList);
+                }
+                for &idx in &order {
+                    // Mark listed slots as full to catch duplicates.
+                    match full.get_mut(idx) {
+                        Some(f) if !*f && idx < len => *f = true,
+                        _ => return Err(PatchError::InvalidFreeList),
+                    }
+                }
+
+                // Everything checks out, apply.
+                self.data.truncate(len);
+                self.data.resize_with(len, || Entry::Empty(len));
+                // Changes past the end can only be removals, which truncating took care of.
+                for (idx, change) in changes.into_iter().filter(|&(idx, _)| idx < len) {
+                    self.data[idx] = match change {
+                        SlotChange::Add(value) | SlotChange::Modify(value) => Entry::Full(value),
+                        SlotChange::Remove => Entry::Empty(len),
+                    };
+                }
+                self.next_free = order.first().copied().unwrap_or(len);
+                for (i, &idx) in order.iter().enumerate() {
+                    self.data[idx] = Entry::Empty(order.get(i + 1).copied().unwrap_or(len));
+                }
+                self.size = (self.size as isize + delta) as usize;
+            }
+        }
+        Ok(())
+    }
+}
@@ -52,3 +52,17 @@ pub fn value_index<V, Ix: Index>((i, entry): (usize, Entry<V>)) -> Option<(Ix, V
         Empty(_) => None,
     }
 }
+
+pub fn index_ref<V, Ix: Index>((i, entry): (usize, &Entry<V>)) -> Option<Ix> {
+    match *entry {
+        Full(_) => Some(Ix::from_usize(i)),
+        Empty(_) => None,
+    }
+}
+
+pub fn index<V, Ix: Index>((i, entry): (usize, Entry<V>)) -> Option<Ix> {
+    match entry {
+        Full(_) => Some(Ix::from_usize(i)),
+        Empty(_) => None,
+    }
+}
@@ -0,0 +1,283 @@
+//! Record the operations applied to a stash so they can be replayed elsewhere.
+//!
+//! `Stash` assigns keys deterministically so two stashes subjected to the same sequence of
+//! operations assign the same keys. A `JournaledStash` records every key-changing operation
+//! (`put`, `take`, `clear`, and `insert_at`) as an `Op` into a `Sink`. The recorded operations can
+//! then be replayed on a replica with `Stash::apply`, which checks that each operation has the
+//! same effect on the replica as on the original and reports a `Divergence` otherwise.
+//!
+//! # Examples
+//!
+//! ```
+//! use stash::Stash;
+//! use stash::stash::journal::{JournaledStash, Op};
+//!
+//! let mut primary = JournaledStash::new(Stash::new(), Vec::new());
+//! let a = primary.put("a");
+//! let b = primary.put("b");
+//! primary.take(a);
+//!
+//! let mut replica = Stash::new();
+//! for op in primary.sink_mut().drain(..) {
+//!     replica.apply(op).unwrap();
+//! }
+//! assert_eq!(replica[b], "b");
+//!
+//! // A replica that has drifted is detected instead of silently assigning other keys.
+//! assert!(replica.apply(Op::Put { index: 5, value: "c" }).is_err());
+//! ```
+
+use alloc::vec::Vec;
+use core::error::Error;
+use core::fmt;
+use core::ops::Deref;
+
+use super::{Entry, Stash};
+use crate::allocator::Allocator;
+use crate::index::Index;
+
+/// An operation on a `Stash`.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum Op<V, Ix = usize> {
+    /// A value was put into the stash at `index`.
+    Put { index: Ix, value: V },
+    /// The value at `index` was taken from the stash.
+    Take { index: Ix },
+    /// The stash was cleared.
+    Clear,
+    /// A value was inserted at `index` with `insert_at`.
+    InsertAt { index: Ix, value: V },
+}
+
+impl<V: Clone, Ix> Op<&V, Ix> {
+    /// Clone the value out of an operation as recorded by a `JournaledStash`.
+    pub fn cloned(self) -> Op<V, Ix> {
+        match self {
+            Op::Put { index, value } => Op::Put {
+                index,
+                value: value.clone(),
+            },
+            Op::Take { index } => Op::Take { index },
+            Op::Clear => Op::Clear,
+            Op::InsertAt { index, value } => Op::InsertAt {
+                index,
+                value: value.clone(),
+            },
+        }
+    }
+}
+
+/// Receives the operations recorded by a `JournaledStash`.
+///
+/// Values are passed by reference so sinks that don't need to own them (e.g., sinks that
+/// serialize operations directly to a log) don't force `V: Clone`.
+pub trait Sink<V, Ix> {
+    /// Record an operation that has just been applied to the stash.
+    fn record(&mut self, op: Op<&V, Ix>);
+}
+
+impl<V: Clone, Ix> Sink<V, Ix> for Vec<Op<V, Ix>> {
+    fn record(&mut self, op: Op<&V, Ix>) {
+        self.push(op.cloned());
+    }
+}
+
+impl<V, Ix, S: Sink<V, Ix> + ?Sized> Sink<V, Ix> for &mut S {
+    fn record(&mut self, op: Op<&V, Ix>) {
+        (**self).record(op)
+    }
+}
+
+/// A `Stash` that records every operation that affects key assignment into a `Sink`.
+///
+/// Dereferences to the underlying `Stash` for read-only access. Values can be modified in place
+/// with `get_mut`, but such modifications are not recorded.
+pub struct JournaledStash<V, Ix = usize, S = Vec<Op<V, Ix>>> {
+    stash: Stash<V, Ix>,
+    sink: S,
+}
+
+impl<V, Ix, S> JournaledStash<V, Ix, S>
+where
+    Ix: Index,
+    S: Sink<V, Ix>,
+{
+    /// Wrap `stash`, recording future operations into `sink`.
+    ///
+    /// Replicas must start from a stash that's structurally identical to `stash` (e.g., a clone,
+    /// or an empty stash if `stash` is empty).
+    pub fn new(stash: Stash<V, Ix>, sink: S) -> Self {
+        JournaledStash { stash, sink }
+    }
+
+    /// Put a value into the stash, recording the operation.
+    pub fn put(&mut self, value: V) -> Ix {
+        let idx = self.stash.put(value).into_usize();
+        self.sink.record(Op::Put {
+            index: Ix::from_usize(idx),
+            value: full(&self.stash, idx),
+        });
+        Ix::from_usize(idx)
+    }
+
+    /// Take an item from a slot (if non empty), recording the operation.
+    ///
+    /// Nothing is recorded if the slot is empty.
+    pub fn take(&mut self, index: Ix) -> Option<V> {
+        let idx = index.into_usize();
+        let value = self.stash.take(Ix::from_usize(idx))?;
+        self.sink.record(Op::Take {
+            index: Ix::from_usize(idx),
+        });
+        Some(value)
+    }
+
+    /// Put a value into the stash at a specific index (see `Stash::insert_at`), recording the
+    /// operation.
+    pub fn insert_at(&mut self, index: Ix, value: V) -> Option<V> {
+        let idx = index.into_usize();
+        let old = self.stash.insert_at(Ix::from_usize(idx), value);
+        self.sink.record(Op::InsertAt {
+            index: Ix::from_usize(idx),
+            value: full(&self.stash, idx),
+        });
+        old
+    }
+
+    /// Clear the stash, recording the operation.
+    pub fn clear(&mut self) {
+        self.stash.clear();
+        self.sink.record(Op::Clear);
+    }
+
+    /// Get a mutable reference to the value at `index`.
+    ///
+    /// Modifications made through this reference are not recorded.
+    #[inline]
+    pub fn get_mut(&mut self, index: Ix) -> Option<&mut V> {
+        self.stash.get_mut(index)
+    }
+
+    /// Get a reference to the sink.
+    #[inline]
+    pub fn sink(&self) -> &S {
+        &self.sink
+    }
+
+    /// Get a mutable reference to the sink (e.g., to drain recorded operations).
+    #[inline]
+    pub fn sink_mut(&mut self) -> &mut S {
+        &mut self.sink
+    }
+
+    /// Unwrap the stash and the sink.
+    #[inline]
+    pub fn into_inner(self) -> (Stash<V, Ix>, S) {
+        (self.stash, self.sink)
+    }
+}
+
+impl<V, Ix, S> Deref for JournaledStash<V, Ix, S> {
+    type Target = Stash<V, Ix>;
+    #[inline]
+    fn deref(&self) -> &Stash<V, Ix> {
+        &self.stash
+    }
+}
+
+impl<V, Ix, S> fmt::Debug for JournaledStash<V, Ix, S>
+where
+    V: fmt::Debug,
+    Ix: fmt::Debug + Index,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&self.stash, f)
+    }
+}
+
+/// An operation that can't be applied to a stash without changing its meaning. Returned by
+/// `Stash::apply`.
+///
+/// This means the stash has diverged from the stash the operation was recorded on.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Divergence<V, Ix = usize> {
+    op: Op<V, Ix>,
+}
+
+impl<V, Ix> Divergence<V, Ix> {
+    /// The operation that couldn't be applied.
+    pub fn op(&self) -> &Op<V, Ix> {
+        &self.op
+    }
+
+    /// Recover the operation that couldn't be applied.
+    pub fn into_op(self) -> Op<V, Ix> {
+        self.op
+    }
+}
+
+impl<V, Ix> fmt::Display for Divergence<V, Ix> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self.op {
+            Op::Put { .. } => "stash diverged: put would have used a different index",
+            Op::Take { .. } => "stash diverged: nothing to take at the recorded index",
+            Op::Clear | Op::InsertAt { .. } => "stash diverged",
+        })
+    }
+}
+
+impl<V: fmt::Debug, Ix: fmt::Debug> Error for Divergence<V, Ix> {}
+
+impl<V, Ix, A> Stash<V, Ix, A>
+where
+    Ix: Index,
+    A: Allocator,
+{
+    /// Replay an operation recorded by a `JournaledStash`.
+    ///
+    /// Returns the value taken (for `Take`) or replaced (for `InsertAt`), if any. Fails without
+    /// modifying the stash if the operation wouldn't have the same effect as it had on the
+    /// original stash, i.e., if a `Put` would store the value at a different index or if there's
+    /// nothing to `Take`.
+    pub fn apply(&mut self, op: Op<V, Ix>) -> Result<Option<V>, Divergence<V, Ix>> {
+        match op {
+            Op::Put { index, value } => {
+                let idx = index.into_usize();
+                if idx != self.next_free {
+                    return Err(Divergence {
+                        op: Op::Put {
+                            index: Ix::from_usize(idx),
+                            value,
+                        },
+                    });
+                }
+                self.put(value);
+                Ok(None)
+            }
+            Op::Take { index } => {
+                let idx = index.into_usize();
+                match self.take(Ix::from_usize(idx)) {
+                    Some(value) => Ok(Some(value)),
+                    None => Err(Divergence {
+                        op: Op::Take {
+                            index: Ix::from_usize(idx),
+                        },
+                    }),
+                }
+            }
+            Op::Clear => {
+                self.clear();
+                Ok(None)
+            }
+            Op::InsertAt { index, value } => Ok(self.insert_at(index, value)),
+        }
+    }
+}
+
+/// Get the value in a slot that's known to be full.
+fn full<V, Ix>(stash: &Stash<V, Ix>, idx: usize) -> &V {
+    match stash.data[idx] {
+        Entry::Full(ref value) => value,
+        Entry::Empty(_) => unreachable!(),
+    }
+}
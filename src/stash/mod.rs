@@ -1,26 +1,44 @@
-use alloc::vec::{self, Vec};
+use crate::allocator::vec::{self, Vec};
+use crate::allocator::{Allocator, Global};
 use core::fmt;
+use core::hash::{Hash, Hasher};
 use core::iter;
 use core::marker;
 use core::mem;
 use core::ops;
 use core::slice;
 
-mod entry;
-use self::entry::Entry;
+mod diff;
+pub(crate) mod entry;
+pub mod journal;
+#[cfg(feature = "rayon")]
+mod par;
+#[cfg(feature = "serialization")]
+mod serialization;
+mod transaction;
+
+pub(crate) use self::entry::Entry;
+use crate::error::LookupError;
 use crate::index::Index;
 
-pub struct Extend<'a, I, Ix>
+#[cfg(feature = "rayon")]
+pub use self::par::{IntoParIter, ParIter, ParIterMut, ParValuesMut};
+#[cfg(feature = "serialization")]
+pub use self::serialization::{exact, sparse};
+pub use self::transaction::Transaction;
+
+pub struct Extend<'a, I, Ix, A = Global>
 where
     I: Iterator,
     I::Item: 'a,
     Ix: Index + 'a,
+    A: Allocator + 'a,
 {
     iter: I,
-    stash: &'a mut Stash<I::Item, Ix>,
+    stash: &'a mut Stash<I::Item, Ix, A>,
 }
 
-impl<'a, I, Ix: Index> Drop for Extend<'a, I, Ix>
+impl<'a, I, Ix: Index, A: Allocator> Drop for Extend<'a, I, Ix, A>
 where
     I: Iterator,
     I::Item: 'a,
@@ -30,7 +48,7 @@ where
     }
 }
 
-impl<'a, I, Ix: Index> Iterator for Extend<'a, I, Ix>
+impl<'a, I, Ix: Index, A: Allocator> Iterator for Extend<'a, I, Ix, A>
 where
     I: Iterator,
     I::Item: 'a,
@@ -45,14 +63,14 @@ where
     }
 }
 
-impl<'a, I, Ix: Index> ExactSizeIterator for Extend<'a, I, Ix>
+impl<'a, I, Ix: Index, A: Allocator> ExactSizeIterator for Extend<'a, I, Ix, A>
 where
     I: ExactSizeIterator,
     I::Item: 'a,
 {
 }
 
-impl<'a, I, Ix: Index> DoubleEndedIterator for Extend<'a, I, Ix>
+impl<'a, I, Ix: Index, A: Allocator> DoubleEndedIterator for Extend<'a, I, Ix, A>
 where
     I: DoubleEndedIterator,
     I::Item: 'a,
@@ -77,8 +95,22 @@ pub struct IterMut<'a, V: 'a, Ix: Index> {
 }
 
 /// Iterator over the `(index, value)` pairs.
-pub struct IntoIter<V, Ix: Index> {
-    inner: iter::Enumerate<vec::IntoIter<Entry<V>>>,
+pub struct IntoIter<V, Ix: Index, A: Allocator = Global> {
+    inner: iter::Enumerate<vec::IntoIter<Entry<V>, A>>,
+    len: usize,
+    _marker: marker::PhantomData<fn() -> Ix>,
+}
+
+/// Iterator over the indices of the values in the stash.
+pub struct Keys<'a, V: 'a, Ix: Index> {
+    inner: iter::Enumerate<slice::Iter<'a, Entry<V>>>,
+    len: usize,
+    _marker: marker::PhantomData<fn() -> Ix>,
+}
+
+/// Iterator over the indices of the values in the stash, consuming the stash.
+pub struct IntoKeys<V, Ix: Index, A: Allocator = Global> {
+    inner: iter::Enumerate<vec::IntoIter<Entry<V>, A>>,
     len: usize,
     _marker: marker::PhantomData<fn() -> Ix>,
 }
@@ -96,18 +128,49 @@ pub struct ValuesMut<'a, V: 'a> {
 }
 
 /// Iterator over values in the stash.
-pub struct IntoValues<V> {
-    inner: vec::IntoIter<Entry<V>>,
+pub struct IntoValues<V, A: Allocator = Global> {
+    inner: vec::IntoIter<Entry<V>, A>,
     len: usize,
 }
 
+/// Iterator over the vacant slots in the order in which `put` will reuse them.
+struct FreeList<'a, V: 'a> {
+    data: &'a [Entry<V>],
+    next: usize,
+    len: usize,
+}
+
+impl<'a, V> Iterator for FreeList<'a, V> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        match self.data.get(self.next) {
+            Some(Entry::Empty(next)) => {
+                let idx = mem::replace(&mut self.next, *next);
+                self.len -= 1;
+                Some(idx)
+            }
+            _ => None,
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<'a, V> ExactSizeIterator for FreeList<'a, V> {}
+
 impl_iter!(Values, (<'a, V>), &'a V, entry::value_ref, ());
 impl_iter!(ValuesMut, (<'a, V>), &'a mut V, entry::value_mut, ());
-impl_iter!(IntoValues, (<V>), V, entry::value, ());
+impl_iter!(IntoValues, (<V, A>), V, entry::value, (where A: Allocator));
 
 impl_iter!(Iter, (<'a, V, Ix>), (Ix, &'a V), entry::value_index_ref, (where Ix: Index));
 impl_iter!(IterMut, (<'a, V, Ix>), (Ix, &'a mut V), entry::value_index_mut, (where Ix: Index));
-impl_iter!(IntoIter, (<V, Ix>), (Ix, V), entry::value_index, (where Ix: Index));
+impl_iter!(IntoIter, (<V, Ix, A>), (Ix, V), entry::value_index, (where Ix: Index, A: Allocator));
+
+impl_iter!(Keys, (<'a, V, Ix>), Ix, entry::index_ref, (where Ix: Index));
+impl_iter!(IntoKeys, (<V, Ix, A>), Ix, entry::index, (where Ix: Index, A: Allocator));
 
 /// An `O(1)` amortized table that reuses keys.
 ///
@@ -125,9 +188,11 @@ impl_iter!(IntoIter, (<V, Ix>), (Ix, V), entry::value_index, (where Ix: Index));
 ///    assignment or iteration order. They can change at any time.
 ///
 /// An example use case is a file descriptor table.
+///
+/// The slots are allocated with `A` (the global allocator by default). See `Stash::new_in`.
 #[derive(Clone)]
-pub struct Stash<V, Ix = usize> {
-    data: Vec<Entry<V>>,
+pub struct Stash<V, Ix = usize, A: Allocator = Global> {
+    data: Vec<Entry<V>, A>,
     size: usize,
     next_free: usize,
     // add a phantom user of the Ix type to make sure an instance of Stash is bound to one
@@ -204,10 +269,61 @@ impl<V> Stash<V, usize> {
     }
 }
 
-impl<V, Ix> Stash<V, Ix>
+impl<V, Ix, A: Allocator> Stash<V, Ix, A> {
+    /// Constructs a new, empty `Stash<V, Ix, A>` that allocates its slots with `alloc`.
+    ///
+    /// The stash will not allocate until elements are put onto it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #![cfg_attr(feature = "nightly", feature(allocator_api))]
+    /// # #[cfg(feature = "allocator-api2")] {
+    /// extern crate allocator_api2;
+    /// # extern crate stash;
+    /// use allocator_api2::alloc::Global;
+    /// use stash::Stash;
+    ///
+    /// let mut stash: Stash<i32, usize, Global> = Stash::new_in(Global);
+    /// stash.put(1);
+    /// # }
+    /// ```
+    #[inline]
+    pub const fn new_in(alloc: A) -> Self {
+        Stash {
+            data: Vec::new_in(alloc),
+            next_free: 0,
+            size: 0,
+            _marker: marker::PhantomData,
+        }
+    }
+
+    /// Constructs a new, empty `Stash<V, Ix, A>` with the specified capacity that allocates its
+    /// slots with `alloc`.
+    ///
+    /// See `Stash::with_capacity`.
+    #[inline]
+    pub fn with_capacity_in(capacity: usize, alloc: A) -> Self {
+        Stash {
+            data: Vec::with_capacity_in(capacity, alloc),
+            next_free: 0,
+            size: 0,
+            _marker: marker::PhantomData,
+        }
+    }
+}
+
+impl<V, Ix, A> Stash<V, Ix, A>
 where
     Ix: Index,
+    A: Allocator,
 {
+    /// Returns a reference to the allocator backing the stash.
+    #[inline]
+    pub fn allocator(&self) -> &A {
+        self.data.allocator()
+    }
+
     /// Returns the number of elements the stash can hold without reallocating.
     ///
     /// # Examples
@@ -333,13 +449,80 @@ where
         idx
     }
 
+    /// Put a value into the stash at a specific index, returning the value previously stored
+    /// there (if any).
+    ///
+    /// If `index` is past the end of the stash, the stash grows to fit it and the new vacant
+    /// slots are reused (in ascending order) after all existing vacant slots. Like every other
+    /// operation, the effect on future key assignment is deterministic.
+    ///
+    /// Filling a vacant slot has to find it in the list of vacant slots so this is `O(n)` in the
+    /// number of vacant slots in the worst case.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the stash can't grow to `index + 1` slots (e.g., `index` is `usize::MAX`).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use stash::Stash;
+    ///
+    /// let mut stash = Stash::new();
+    /// assert_eq!(stash.insert_at(2, "c"), None);
+    /// assert_eq!(stash.insert_at(2, "C"), Some("c"));
+    /// assert_eq!(stash.put("a"), 0);
+    /// assert_eq!(stash.put("b"), 1);
+    /// assert_eq!(stash.put("d"), 3);
+    /// ```
+    pub fn insert_at(&mut self, index: Ix, value: V) -> Option<V> {
+        let idx = index.into_usize();
+        if let Some(Entry::Full(old)) = self.data.get_mut(idx) {
+            return Some(mem::replace(old, value));
+        }
+
+        // Grow the stash, chaining the new slots (including the target) onto the end of the
+        // free list. The last existing vacant slot (or `next_free`) already points at the
+        // current end of the stash.
+        if idx >= self.data.len() {
+            let len = self.data.len();
+            let new_len = idx.checked_add(1).expect("stash index overflowing!");
+            self.data.reserve(new_len - len);
+            self.data.extend((len..=idx).map(|i| Entry::Empty(i + 1)));
+        }
+
+        // Unlink the target slot from the free list.
+        let next = match self.data[idx] {
+            Entry::Empty(next) => next,
+            Entry::Full(_) => unreachable!(),
+        };
+        if self.next_free == idx {
+            self.next_free = next;
+        } else {
+            let mut cur = self.next_free;
+            loop {
+                match self.data[cur] {
+                    Entry::Empty(ref mut n) if *n == idx => {
+                        *n = next;
+                        break;
+                    }
+                    Entry::Empty(n) => cur = n,
+                    Entry::Full(_) => unreachable!("corrupt free list"),
+                }
+            }
+        }
+        self.data[idx] = Entry::Full(value);
+        self.size += 1;
+        None
+    }
+
     /// Put all items in the iterator into the stash.
     ///
     /// Returns an iterator over the indices where the items were inserted. The
     /// items are actually inserted as the Iterator is read. If the returned
     /// Iterator is dropped, the rest of the items will be inserted all at once.
     #[inline]
-    pub fn extend<I>(&mut self, iter: I) -> Extend<I, Ix>
+    pub fn extend<I>(&mut self, iter: I) -> Extend<'_, I, Ix, A>
     where
         I: Iterator<Item = V>,
     {
@@ -352,7 +535,7 @@ where
     ///
     /// Returns an iterator that yields `(index, &value)` pairs.
     #[inline]
-    pub fn iter(&self) -> Iter<V, Ix> {
+    pub fn iter(&self) -> Iter<'_, V, Ix> {
         Iter {
             len: self.len(),
             inner: self.data.iter().enumerate(),
@@ -364,7 +547,7 @@ where
     ///
     /// Returns an iterator that yields `(index, &mut value)` pairs.
     #[inline]
-    pub fn iter_mut(&mut self) -> IterMut<V, Ix> {
+    pub fn iter_mut(&mut self) -> IterMut<'_, V, Ix> {
         IterMut {
             len: self.len(),
             inner: self.data.iter_mut().enumerate(),
@@ -372,9 +555,51 @@ where
         }
     }
 
+    /// Iterate over the indices of the items in this `Stash<V>`.
+    #[inline]
+    pub fn keys(&self) -> Keys<'_, V, Ix> {
+        Keys {
+            len: self.len(),
+            inner: self.data.iter().enumerate(),
+            _marker: marker::PhantomData,
+        }
+    }
+
+    /// Iterate over the indices of the items in this `Stash<V>`, dropping the values.
+    #[inline]
+    pub fn into_keys(self) -> IntoKeys<V, Ix, A> {
+        IntoKeys {
+            len: self.len(),
+            inner: self.data.into_iter().enumerate(),
+            _marker: marker::PhantomData,
+        }
+    }
+
+    /// Collect the indices of the items in this `Stash<V>`.
+    ///
+    /// Unlike `keys`, the result doesn't borrow the stash so it can be used to, e.g., take items
+    /// while iterating.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use stash::Stash;
+    ///
+    /// let mut stash: Stash<i32> = (0..5).collect();
+    /// for index in stash.keys_snapshot() {
+    ///     if stash[index] % 2 == 0 {
+    ///         stash.take(index);
+    ///     }
+    /// }
+    /// assert_eq!(stash.keys().collect::<Vec<_>>(), [1, 3]);
+    /// ```
+    pub fn keys_snapshot(&self) -> alloc::vec::Vec<Ix> {
+        self.keys().collect()
+    }
+
     /// Iterate over the values in this `Stash<V>` by reference.
     #[inline]
-    pub fn values(&self) -> Values<V> {
+    pub fn values(&self) -> Values<'_, V> {
         Values {
             len: self.len(),
             inner: self.data.iter(),
@@ -383,7 +608,7 @@ where
 
     /// Mutably iterate over the values in this `Stash<V>` by reference.
     #[inline]
-    pub fn values_mut(&mut self) -> ValuesMut<V> {
+    pub fn values_mut(&mut self) -> ValuesMut<'_, V> {
         ValuesMut {
             len: self.len(),
             inner: self.data.iter_mut(),
@@ -392,7 +617,7 @@ where
 
     /// Iterate over the values in this `Stash<V>` by value.
     #[inline]
-    pub fn into_values(self) -> IntoValues<V> {
+    pub fn into_values(self) -> IntoValues<V, A> {
         IntoValues {
             len: self.len(),
             inner: self.data.into_iter(),
@@ -408,19 +633,25 @@ where
     }
 
     /// Take an item from a slot (if non empty).
+    #[inline]
     pub fn take(&mut self, index: Ix) -> Option<V> {
+        self.try_take(index).ok()
+    }
+
+    /// Take an item from a slot, reporting why it failed if the slot is empty or doesn't exist.
+    pub fn try_take(&mut self, index: Ix) -> Result<V, LookupError> {
         let take_index = index.into_usize();
         match self.data.get_mut(take_index) {
-            None => None,
+            None => Err(LookupError::OutOfBounds),
             Some(entry) => match mem::replace(entry, Entry::Empty(self.next_free)) {
                 Entry::Empty(free_slot) => {
                     *entry = Entry::Empty(free_slot);
-                    None
+                    Err(LookupError::Vacant)
                 }
                 Entry::Full(value) => {
                     self.next_free = take_index;
                     self.size -= 1;
-                    Some(value)
+                    Ok(value)
                 }
             },
         }
@@ -450,6 +681,12 @@ where
         }
     }
 
+    /// Check if there's a value at `index`.
+    #[inline]
+    pub fn contains_key(&self, index: Ix) -> bool {
+        self.get(index).is_some()
+    }
+
     /// Get a reference to the value at `index`.
     #[inline]
     pub fn get(&self, index: Ix) -> Option<&V> {
@@ -459,6 +696,16 @@ where
         }
     }
 
+    /// Get a reference to the value at `index`, reporting why it failed if the slot is empty or
+    /// doesn't exist.
+    pub fn try_get(&self, index: Ix) -> Result<&V, LookupError> {
+        match self.data.get(index.into_usize()) {
+            Some(Entry::Full(v)) => Ok(v),
+            Some(Entry::Empty(_)) => Err(LookupError::Vacant),
+            None => Err(LookupError::OutOfBounds),
+        }
+    }
+
     /// Get a reference to the value at `index` without bounds or empty checking.
     /// So use it very carefully!
     ///
@@ -484,6 +731,16 @@ where
         }
     }
 
+    /// Get a mutable reference to the value at `index`, reporting why it failed if the slot is
+    /// empty or doesn't exist.
+    pub fn try_get_mut(&mut self, index: Ix) -> Result<&mut V, LookupError> {
+        match self.data.get_mut(index.into_usize()) {
+            Some(Entry::Full(v)) => Ok(v),
+            Some(Entry::Empty(_)) => Err(LookupError::Vacant),
+            None => Err(LookupError::OutOfBounds),
+        }
+    }
+
     /// Get a mutable reference to the value at `index` without bounds or empty checking.
     /// So use it very carefully!
     ///
@@ -521,11 +778,74 @@ where
             self.next_free = 0;
         }
     }
+
+    /// Check if two stashes hold the same values at the same indices *and* will assign the same
+    /// keys to future values.
+    ///
+    /// Unlike `==` (which only compares the key/value mappings), this also compares the number of
+    /// slots and the order in which vacant slots will be reused. Two structurally equal stashes
+    /// are guaranteed to assign the same keys when subjected to the same operations.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use stash::Stash;
+    ///
+    /// let mut a = Stash::new();
+    /// let x = a.put("x");
+    /// let y = a.put("y");
+    /// let mut b = a.clone();
+    ///
+    /// a.take(x);
+    /// a.take(y);
+    /// b.take(y);
+    /// b.take(x);
+    ///
+    /// // Same (empty) mapping, but the vacant slots will be reused in a different order.
+    /// assert_eq!(a, b);
+    /// assert!(!a.structurally_eq(&b));
+    /// assert_ne!(a.put("z"), b.put("z"));
+    /// ```
+    pub fn structurally_eq(&self, other: &Self) -> bool
+    where
+        V: PartialEq,
+    {
+        self.data.len() == other.data.len()
+            && self == other
+            && self.free_list().eq(other.free_list())
+    }
+
+    /// Iterate over the vacant slots in the order in which they'll be reused.
+    fn free_list(&self) -> FreeList<'_, V> {
+        FreeList {
+            data: &self.data,
+            next: self.next_free,
+            len: self.data.len() - self.size,
+        }
+    }
+
+    /// Build a stash from its slots, its length, and the head of its free list.
+    ///
+    /// The caller must make sure the parts are consistent: `size` is the number of full slots and
+    /// the free list starting at `next_free` threads through every vacant slot.
+    pub(crate) fn from_raw_parts(data: Vec<Entry<V>, A>, size: usize, next_free: usize) -> Self {
+        Stash {
+            data,
+            size,
+            next_free,
+            _marker: marker::PhantomData,
+        }
+    }
+
+    /// Decompose the stash into its slots, its length, and the head of its free list.
+    pub(crate) fn into_raw_parts(self) -> (Vec<Entry<V>, A>, usize, usize) {
+        (self.data, self.size, self.next_free)
+    }
 }
 
-impl<V, Ix: Index> IntoIterator for Stash<V, Ix> {
+impl<V, Ix: Index, A: Allocator> IntoIterator for Stash<V, Ix, A> {
     type Item = (Ix, V);
-    type IntoIter = IntoIter<V, Ix>;
+    type IntoIter = IntoIter<V, Ix, A>;
 
     #[inline]
     fn into_iter(self) -> Self::IntoIter {
@@ -537,7 +857,7 @@ impl<V, Ix: Index> IntoIterator for Stash<V, Ix> {
     }
 }
 
-impl<'a, V, Ix: Index> IntoIterator for &'a Stash<V, Ix> {
+impl<'a, V, Ix: Index, A: Allocator> IntoIterator for &'a Stash<V, Ix, A> {
     type Item = (Ix, &'a V);
     type IntoIter = Iter<'a, V, Ix>;
 
@@ -547,7 +867,7 @@ impl<'a, V, Ix: Index> IntoIterator for &'a Stash<V, Ix> {
     }
 }
 
-impl<'a, V, Ix: Index> IntoIterator for &'a mut Stash<V, Ix> {
+impl<'a, V, Ix: Index, A: Allocator> IntoIterator for &'a mut Stash<V, Ix, A> {
     type Item = (Ix, &'a mut V);
     type IntoIter = IterMut<'a, V, Ix>;
 
@@ -557,17 +877,49 @@ impl<'a, V, Ix: Index> IntoIterator for &'a mut Stash<V, Ix> {
     }
 }
 
-impl<V, Ix> fmt::Debug for Stash<V, Ix>
+/// Two stashes are equal if they map the same keys to the same values.
+///
+/// This ignores vacant slots so equal stashes may assign different keys to future values. Use
+/// `Stash::structurally_eq` to also compare key assignment.
+impl<V: PartialEq, Ix, A: Allocator> PartialEq for Stash<V, Ix, A> {
+    fn eq(&self, other: &Self) -> bool {
+        // Compare raw indices so we don't need `Ix: PartialEq`.
+        fn pairs<V>(data: &[Entry<V>]) -> impl Iterator<Item = (usize, &V)> {
+            data.iter()
+                .enumerate()
+                .filter_map(entry::value_index_ref::<V, usize>)
+        }
+        self.size == other.size && pairs(&self.data).eq(pairs(&other.data))
+    }
+}
+
+impl<V: Eq, Ix, A: Allocator> Eq for Stash<V, Ix, A> {}
+
+/// Hashes the key/value mapping (consistent with `PartialEq`).
+impl<V: Hash, Ix, A: Allocator> Hash for Stash<V, Ix, A> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_usize(self.size);
+        for (i, entry) in self.data.iter().enumerate() {
+            if let Entry::Full(ref value) = *entry {
+                i.hash(state);
+                value.hash(state);
+            }
+        }
+    }
+}
+
+impl<V, Ix, A> fmt::Debug for Stash<V, Ix, A>
 where
     V: fmt::Debug,
     Ix: fmt::Debug + Index,
+    A: Allocator,
 {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_map().entries(self).finish()
     }
 }
 
-impl<V, Ix: Index> ops::Index<Ix> for Stash<V, Ix> {
+impl<V, Ix: Index, A: Allocator> ops::Index<Ix> for Stash<V, Ix, A> {
     type Output = V;
     #[inline]
     fn index(&self, index: Ix) -> &V {
@@ -575,122 +927,49 @@ impl<V, Ix: Index> ops::Index<Ix> for Stash<V, Ix> {
     }
 }
 
-impl<V, Ix: Index> ops::IndexMut<Ix> for Stash<V, Ix> {
+impl<V, Ix: Index, A: Allocator> ops::IndexMut<Ix> for Stash<V, Ix, A> {
     #[inline]
     fn index_mut(&mut self, index: Ix) -> &mut V {
         self.get_mut(index).expect("index out of bounds")
     }
 }
 
-impl<V, Ix: Index> Default for Stash<V, Ix> {
-    #[inline]
-    fn default() -> Self {
-        Stash {
-            data: Vec::new(),
-            next_free: 0,
-            size: 0,
-            _marker: marker::PhantomData,
-        }
+/// Puts every value into the stash (eagerly, unlike `Stash::extend`).
+impl<V, Ix: Index, A: Allocator> iter::Extend<V> for Stash<V, Ix, A> {
+    fn extend<I: IntoIterator<Item = V>>(&mut self, iter: I) {
+        Stash::extend(self, iter.into_iter()).for_each(drop);
     }
 }
 
-#[cfg(feature = "serialization")]
-mod serialization {
-    use super::*;
-    use serde::de::{Deserialize, Deserializer, SeqAccess, Visitor};
-    use serde::ser::{Serialize, SerializeSeq, Serializer};
-
-    impl<V, Ix> Serialize for Stash<V, Ix>
-    where
-        V: Serialize,
-        Ix: Index,
-    {
-        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
-            let mut seq = serializer.serialize_seq(Some(self.data.len()))?;
-            for e in &self.data {
-                let option = match e {
-                    Entry::Full(v) => Some(v),
-                    Entry::Empty(_) => None,
-                };
-                seq.serialize_element(&option)?;
-            }
-            seq.end()
-        }
-    }
-
-    impl<'de, V, Ix> Deserialize<'de> for Stash<V, Ix>
-    where
-        V: Deserialize<'de>,
-        Ix: Index,
-    {
-        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
-        where
-            D: Deserializer<'de>,
-        {
-            deserializer.deserialize_seq(StashVisitor::new())
-        }
-    }
-
-    struct StashVisitor<V, Ix> {
-        _marker: marker::PhantomData<fn(V) -> Ix>,
+impl<V, Ix: Index, A: Allocator + Default> iter::FromIterator<V> for Stash<V, Ix, A> {
+    fn from_iter<I: IntoIterator<Item = V>>(iter: I) -> Self {
+        let mut stash = Stash::new_in(A::default());
+        iter::Extend::extend(&mut stash, iter);
+        stash
     }
+}
 
-    impl<V, Ix> StashVisitor<V, Ix> {
-        fn new() -> StashVisitor<V, Ix> {
-            StashVisitor {
-                _marker: marker::PhantomData,
-            }
+/// Builds a stash by inserting every value at its index with `Stash::insert_at`.
+///
+/// If an index appears more than once, the last value wins.
+impl<V, Ix: Index, A: Allocator + Default> iter::FromIterator<(Ix, V)> for Stash<V, Ix, A> {
+    fn from_iter<I: IntoIterator<Item = (Ix, V)>>(iter: I) -> Self {
+        let mut stash = Stash::new_in(A::default());
+        for (index, value) in iter {
+            stash.insert_at(index, value);
         }
+        stash
     }
+}
 
-    impl<'de, V, Ix> Visitor<'de> for StashVisitor<V, Ix>
-    where
-        V: Deserialize<'de>,
-        Ix: Index,
-    {
-        type Value = Stash<V, Ix>;
-        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-            write!(formatter, "a sequence of optional values")
-        }
-
-        fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
-        where
-            A: SeqAccess<'de>,
-        {
-            let initial_size = seq.size_hint().unwrap_or(8);
-            let mut data = Vec::with_capacity(initial_size);
-            let mut i = 0;
-            let mut next_free = 0;
-            let mut size = 0;
-            let mut first_free = None;
-            while let Some(option) = seq.next_element()? {
-                match option {
-                    Some(v) => {
-                        data.push(Entry::Full(v));
-                        size += 1;
-                    }
-                    None => {
-                        if first_free.is_none() {
-                            first_free = Some(i);
-                        }
-                        data.push(Entry::Empty(next_free));
-                        next_free = i;
-                    }
-                }
-                i += 1;
-            }
-            // fix the last entry in linked list now that we know total length.
-            if let Some(Entry::Empty(next)) = first_free.and_then(|e| data.get_mut(e)) {
-                *next = i;
-            } else {
-                next_free = i;
-            }
-            Ok(Stash {
-                data,
-                next_free,
-                size,
-                _marker: marker::PhantomData,
-            })
+impl<V, Ix: Index> Default for Stash<V, Ix> {
+    #[inline]
+    fn default() -> Self {
+        Stash {
+            data: Vec::new(),
+            next_free: 0,
+            size: 0,
+            _marker: marker::PhantomData,
         }
     }
 }
@@ -0,0 +1,212 @@
+use alloc::vec::Vec;
+use core::marker;
+
+use rayon::iter::plumbing::UnindexedConsumer;
+use rayon::prelude::*;
+
+use super::{entry, Entry, Stash};
+use crate::allocator::{self, Allocator};
+use crate::index::Index;
+
+/// Parallel iterator over the `(index, &value)` pairs.
+pub struct ParIter<'a, V: 'a, Ix> {
+    data: &'a [Entry<V>],
+    _marker: marker::PhantomData<fn() -> Ix>,
+}
+
+/// Parallel iterator over the `(index, &mut value)` pairs.
+pub struct ParIterMut<'a, V: 'a, Ix> {
+    data: &'a mut [Entry<V>],
+    _marker: marker::PhantomData<fn() -> Ix>,
+}
+
+/// Parallel iterator over mutable references to the values in the stash.
+pub struct ParValuesMut<'a, V: 'a> {
+    data: &'a mut [Entry<V>],
+}
+
+/// Parallel iterator over the `(index, value)` pairs.
+pub struct IntoParIter<V, Ix> {
+    data: Vec<Entry<V>>,
+    _marker: marker::PhantomData<fn() -> Ix>,
+}
+
+impl<'a, V, Ix> ParallelIterator for ParIter<'a, V, Ix>
+where
+    V: Sync,
+    Ix: Index + Send,
+{
+    type Item = (Ix, &'a V);
+
+    fn drive_unindexed<C>(self, consumer: C) -> C::Result
+    where
+        C: UnindexedConsumer<Self::Item>,
+    {
+        self.data
+            .par_iter()
+            .enumerate()
+            .filter_map(entry::value_index_ref)
+            .drive_unindexed(consumer)
+    }
+}
+
+impl<'a, V, Ix> ParallelIterator for ParIterMut<'a, V, Ix>
+where
+    V: Send,
+    Ix: Index + Send,
+{
+    type Item = (Ix, &'a mut V);
+
+    fn drive_unindexed<C>(self, consumer: C) -> C::Result
+    where
+        C: UnindexedConsumer<Self::Item>,
+    {
+        self.data
+            .par_iter_mut()
+            .enumerate()
+            .filter_map(entry::value_index_mut)
+            .drive_unindexed(consumer)
+    }
+}
+
+impl<'a, V> ParallelIterator for ParValuesMut<'a, V>
+where
+    V: Send,
+{
+    type Item = &'a mut V;
+
+    fn drive_unindexed<C>(self, consumer: C) -> C::Result
+    where
+        C: UnindexedConsumer<Self::Item>,
+    {
+        self.data
+            .par_iter_mut()
+            .filter_map(entry::value_mut)
+            .drive_unindexed(consumer)
+    }
+}
+
+impl<V, Ix> ParallelIterator for IntoParIter<V, Ix>
+where
+    V: Send,
+    Ix: Index + Send,
+{
+    type Item = (Ix, V);
+
+    fn drive_unindexed<C>(self, consumer: C) -> C::Result
+    where
+        C: UnindexedConsumer<Self::Item>,
+    {
+        self.data
+            .into_par_iter()
+            .enumerate()
+            .filter_map(entry::value_index)
+            .drive_unindexed(consumer)
+    }
+}
+
+impl<V, Ix, A> Stash<V, Ix, A>
+where
+    Ix: Index,
+    A: Allocator,
+{
+    /// Iterate over the items in this `Stash<V>` in parallel.
+    ///
+    /// Returns a parallel iterator that yields `(index, &value)` pairs.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rayon::prelude::*;
+    /// use stash::Stash;
+    ///
+    /// let stash: Stash<u64> = (0..1000).collect();
+    /// let sum: u64 = stash.par_iter().map(|(i, v)| i as u64 + v).sum();
+    /// assert_eq!(sum, 999 * 1000);
+    /// ```
+    #[inline]
+    pub fn par_iter(&self) -> ParIter<'_, V, Ix> {
+        ParIter {
+            data: &self.data,
+            _marker: marker::PhantomData,
+        }
+    }
+
+    /// Mutably iterate over the items in this `Stash<V>` in parallel.
+    ///
+    /// Returns a parallel iterator that yields `(index, &mut value)` pairs.
+    #[inline]
+    pub fn par_iter_mut(&mut self) -> ParIterMut<'_, V, Ix> {
+        ParIterMut {
+            data: &mut self.data,
+            _marker: marker::PhantomData,
+        }
+    }
+
+    /// Mutably iterate over the values in this `Stash<V>` in parallel.
+    #[inline]
+    pub fn par_values_mut(&mut self) -> ParValuesMut<'_, V> {
+        ParValuesMut {
+            data: &mut self.data,
+        }
+    }
+
+    /// Iterate over the items in this `Stash<V>` by value, in parallel.
+    ///
+    /// Returns a parallel iterator that yields `(index, value)` pairs.
+    ///
+    /// Rayon can only split a standard `Vec`, so with the `allocator-api2` feature the slots are
+    /// first moved into a `Vec` in the global allocator.
+    #[inline]
+    pub fn into_par_iter(self) -> IntoParIter<V, Ix> {
+        IntoParIter {
+            data: allocator::into_std_vec(self.data),
+            _marker: marker::PhantomData,
+        }
+    }
+}
+
+impl<'a, V, Ix, A> IntoParallelIterator for &'a Stash<V, Ix, A>
+where
+    V: Sync,
+    Ix: Index + Send,
+    A: Allocator,
+{
+    type Item = (Ix, &'a V);
+    type Iter = ParIter<'a, V, Ix>;
+
+    #[inline]
+    fn into_par_iter(self) -> Self::Iter {
+        self.par_iter()
+    }
+}
+
+impl<'a, V, Ix, A> IntoParallelIterator for &'a mut Stash<V, Ix, A>
+where
+    V: Send,
+    Ix: Index + Send,
+    A: Allocator,
+{
+    type Item = (Ix, &'a mut V);
+    type Iter = ParIterMut<'a, V, Ix>;
+
+    #[inline]
+    fn into_par_iter(self) -> Self::Iter {
+        self.par_iter_mut()
+    }
+}
+
+impl<V, Ix, A> IntoParallelIterator for Stash<V, Ix, A>
+where
+    V: Send,
+    Ix: Index + Send,
+    A: Allocator,
+{
+    type Item = (Ix, V);
+    type Iter = IntoParIter<V, Ix>;
+
+    #[inline]
+    fn into_par_iter(self) -> Self::Iter {
+        Stash::into_par_iter(self)
+    }
+}
@@ -0,0 +1,750 @@
+use super::*;
+use crate::limits::Limits;
+use serde::de::{
+    self, Deserialize, DeserializeSeed, Deserializer, MapAccess, SeqAccess, Unexpected, Visitor,
+};
+use serde::ser::{Serialize, SerializeMap, SerializeSeq, SerializeStruct, Serializer};
+
+impl<V, Ix, A: Allocator> Serialize for Stash<V, Ix, A>
+where
+    V: Serialize,
+    Ix: Index,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.data.len()))?;
+        for e in &self.data {
+            let option = match e {
+                Entry::Full(v) => Some(v),
+                Entry::Empty(_) => None,
+            };
+            seq.serialize_element(&option)?;
+        }
+        seq.end()
+    }
+}
+
+impl<'de, V, Ix, A: Allocator + Default> Deserialize<'de> for Stash<V, Ix, A>
+where
+    V: Deserialize<'de>,
+    Ix: Index,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Stash::deserialize_with_limits(deserializer, Limits::implicit())
+    }
+}
+
+impl<V, Ix, A: Allocator> Stash<V, Ix, A>
+where
+    Ix: Index,
+{
+    /// Deserialize a stash, rejecting it if it exceeds the given limits.
+    ///
+    /// Deserialization always fails if the stash has more slots than `Ix` can address (see
+    /// `Index::try_from_usize`).
+    ///
+    /// To use this from a derived `Deserialize` implementation, wrap it in a function and pass
+    /// that to `#[serde(deserialize_with = "...")]`.
+    pub fn deserialize_with_limits<'de, D>(
+        deserializer: D,
+        limits: Limits,
+    ) -> Result<Self, D::Error>
+    where
+        V: Deserialize<'de>,
+        A: Default,
+        D: Deserializer<'de>,
+    {
+        StashSeed::new(limits).deserialize(deserializer)
+    }
+}
+
+/// Deserializes a stash in the default format.
+struct StashSeed<V, Ix, A: Allocator> {
+    limits: Limits,
+    _marker: marker::PhantomData<fn(V, A) -> Ix>,
+}
+
+impl<V, Ix, A: Allocator> StashSeed<V, Ix, A> {
+    fn new(limits: Limits) -> StashSeed<V, Ix, A> {
+        StashSeed {
+            limits,
+            _marker: marker::PhantomData,
+        }
+    }
+}
+
+impl<'de, V, Ix, A: Allocator + Default> DeserializeSeed<'de> for StashSeed<V, Ix, A>
+where
+    V: Deserialize<'de>,
+    Ix: Index,
+{
+    type Value = Stash<V, Ix, A>;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let visitor = StashVisitor::new(self.limits);
+        if deserializer.is_human_readable() {
+            // Accept both the sparse and the dense format.
+            deserializer.deserialize_any(visitor)
+        } else {
+            deserializer.deserialize_seq(visitor)
+        }
+    }
+}
+
+/// Builds a stash slot by slot, threading vacant slots onto the free list.
+struct Builder<V, Ix, A: Allocator> {
+    stash: Stash<V, Ix, A>,
+    first_free: Option<usize>,
+    limits: Limits,
+}
+
+impl<V, Ix, A: Allocator + Default> Builder<V, Ix, A>
+where
+    Ix: Index,
+{
+    fn new(limits: Limits, size_hint: Option<usize>) -> Builder<V, Ix, A> {
+        Builder {
+            stash: Stash {
+                data: Vec::with_capacity_in(
+                    limits.initial_capacity::<Entry<V>>(size_hint),
+                    A::default(),
+                ),
+                next_free: 0,
+                size: 0,
+                _marker: marker::PhantomData,
+            },
+            first_free: None,
+            limits,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.stash.data.len()
+    }
+
+    /// Check that the stash may have `slots` slots.
+    fn check_slots<E: de::Error>(&self, slots: usize) -> Result<(), E> {
+        if slots > self.limits.max_slots {
+            return Err(E::custom(format_args!(
+                "stash has more than {} slots",
+                self.limits.max_slots
+            )));
+        }
+        if slots > 0 && Ix::try_from_usize(slots - 1).is_none() {
+            return Err(E::custom(format_args!(
+                "stash slot {} can't be addressed by the index type",
+                slots - 1
+            )));
+        }
+        Ok(())
+    }
+
+    fn push<E: de::Error>(&mut self, option: Option<V>) -> Result<(), E> {
+        self.check_slots(self.len() + 1)?;
+        let stash = &mut self.stash;
+        match option {
+            Some(v) => {
+                if stash.size >= self.limits.max_len {
+                    return Err(E::custom(format_args!(
+                        "stash has more than {} entries",
+                        self.limits.max_len
+                    )));
+                }
+                stash.data.push(Entry::Full(v));
+                stash.size += 1;
+            }
+            None => {
+                let i = stash.data.len();
+                if self.first_free.is_none() {
+                    self.first_free = Some(i);
+                }
+                stash.data.push(Entry::Empty(stash.next_free));
+                stash.next_free = i;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> Stash<V, Ix, A> {
+        let len = self.stash.data.len();
+        // fix the last entry in linked list now that we know total length.
+        if let Some(Entry::Empty(next)) = self.first_free.and_then(|e| self.stash.data.get_mut(e)) {
+            *next = len;
+        } else {
+            self.stash.next_free = len;
+        }
+        self.stash
+    }
+}
+
+struct StashVisitor<V, Ix, A: Allocator> {
+    limits: Limits,
+    _marker: marker::PhantomData<fn(V, A) -> Ix>,
+}
+
+impl<V, Ix, A: Allocator> StashVisitor<V, Ix, A> {
+    fn new(limits: Limits) -> StashVisitor<V, Ix, A> {
+        StashVisitor {
+            limits,
+            _marker: marker::PhantomData,
+        }
+    }
+}
+
+impl<'de, V, Ix, A: Allocator + Default> Visitor<'de> for StashVisitor<V, Ix, A>
+where
+    V: Deserialize<'de>,
+    Ix: Index,
+{
+    type Value = Stash<V, Ix, A>;
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "a sequence of optional values or a map of keys to values"
+        )
+    }
+
+    fn visit_seq<M>(self, mut seq: M) -> Result<Self::Value, M::Error>
+    where
+        M: SeqAccess<'de>,
+    {
+        let mut builder = Builder::new(self.limits, seq.size_hint());
+        while let Some(option) = seq.next_element()? {
+            builder.push(option)?;
+        }
+        Ok(builder.finish())
+    }
+
+    fn visit_map<M>(self, map: M) -> Result<Self::Value, M::Error>
+    where
+        M: MapAccess<'de>,
+    {
+        sparse::visit_map(map, self.limits)
+    }
+}
+
+/// Serialize a `Stash` such that, once deserialized, it assigns exactly the same keys as the
+/// original.
+///
+/// The default serialization format only records the key/value mapping and rebuilds the list
+/// of vacant slots from scratch on deserialization. This format additionally records the
+/// order in which vacant slots will be reused. Use it with
+/// `#[serde(with = "stash::stash::exact")]`.
+///
+/// The format is versioned: it's a struct of the format version (`version`), the stash in its
+/// default format (`stash`), and the list of vacant slots (`free_list`).
+///
+/// Human readable deserializers (e.g., JSON) also accept a stash in the default format (e.g.,
+/// written before a field switched to this format), which gets the same free list as it would
+/// with the default format. The default format is never a map with a `version` field so the two
+/// can't be confused.
+pub mod exact {
+    use super::*;
+
+    const FORMAT_VERSION: u32 = 1;
+
+    const FIELDS: &[&str] = &["version", "stash", "free_list"];
+
+    #[derive(Deserialize)]
+    #[serde(field_identifier, rename_all = "snake_case")]
+    enum Field {
+        Version,
+        Stash,
+        FreeList,
+        // The fields of the sparse format.
+        Len,
+        Entries,
+    }
+
+    /// Serialize a stash in the exact format.
+    pub fn serialize<V, Ix, A, S>(stash: &Stash<V, Ix, A>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        V: Serialize,
+        Ix: Index,
+        A: Allocator,
+        S: Serializer,
+    {
+        let mut state = serializer.serialize_struct("Stash", 3)?;
+        state.serialize_field("version", &FORMAT_VERSION)?;
+        state.serialize_field("stash", stash)?;
+        state.serialize_field("free_list", &FreeListSer(stash))?;
+        state.end()
+    }
+
+    /// Deserialize a stash in the exact format.
+    pub fn deserialize<'de, V, Ix, A, D>(deserializer: D) -> Result<Stash<V, Ix, A>, D::Error>
+    where
+        V: Deserialize<'de>,
+        Ix: Index,
+        A: Allocator + Default,
+        D: Deserializer<'de>,
+    {
+        deserialize_with_limits(deserializer, Limits::implicit())
+    }
+
+    /// Deserialize a stash in the exact format, rejecting it if it exceeds the given limits.
+    pub fn deserialize_with_limits<'de, V, Ix, A, D>(
+        deserializer: D,
+        limits: Limits,
+    ) -> Result<Stash<V, Ix, A>, D::Error>
+    where
+        V: Deserialize<'de>,
+        Ix: Index,
+        A: Allocator + Default,
+        D: Deserializer<'de>,
+    {
+        if deserializer.is_human_readable() {
+            // Also accept the default format.
+            deserializer.deserialize_any(ExactVisitor::new(limits, true))
+        } else {
+            deserializer.deserialize_struct("Stash", FIELDS, ExactVisitor::new(limits, false))
+        }
+    }
+
+    struct FreeListSer<'a, V, Ix, A: Allocator>(&'a Stash<V, Ix, A>);
+
+    impl<'a, V, Ix, A: Allocator> Serialize for FreeListSer<'a, V, Ix, A>
+    where
+        Ix: Index,
+    {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_seq(self.0.free_list())
+        }
+    }
+
+    struct ExactVisitor<V, Ix, A: Allocator> {
+        limits: Limits,
+        /// Whether a sequence is a stash in the default format rather than this one.
+        human_readable: bool,
+        _marker: marker::PhantomData<fn(V, A) -> Ix>,
+    }
+
+    impl<V, Ix, A: Allocator> ExactVisitor<V, Ix, A> {
+        fn new(limits: Limits, human_readable: bool) -> ExactVisitor<V, Ix, A> {
+            ExactVisitor {
+                limits,
+                human_readable,
+                _marker: marker::PhantomData,
+            }
+        }
+    }
+
+    impl<'de, V, Ix, A: Allocator + Default> Visitor<'de> for ExactVisitor<V, Ix, A>
+    where
+        V: Deserialize<'de>,
+        Ix: Index,
+    {
+        type Value = Stash<V, Ix, A>;
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            write!(formatter, "a versioned stash")
+        }
+
+        fn visit_seq<M>(self, mut seq: M) -> Result<Self::Value, M::Error>
+        where
+            M: SeqAccess<'de>,
+        {
+            if self.human_readable {
+                // A stash in the default (dense) format.
+                return StashVisitor::new(self.limits).visit_seq(seq);
+            }
+            let version = seq
+                .next_element()?
+                .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+            let stash = seq
+                .next_element_seed(StashSeed::new(self.limits))?
+                .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+            let free_list = seq
+                .next_element()?
+                .ok_or_else(|| de::Error::invalid_length(2, &self))?;
+            finish(version, stash, free_list)
+        }
+
+        fn visit_map<M>(self, mut map: M) -> Result<Self::Value, M::Error>
+        where
+            M: MapAccess<'de>,
+        {
+            let mut version = None;
+            let mut stash = None;
+            let mut free_list = None;
+            let mut len = None;
+            let mut entries = None;
+            while let Some(key) = map.next_key()? {
+                match key {
+                    Field::Version => {
+                        if version.is_some() {
+                            return Err(de::Error::duplicate_field("version"));
+                        }
+                        version = Some(map.next_value()?);
+                    }
+                    Field::Stash => {
+                        if stash.is_some() {
+                            return Err(de::Error::duplicate_field("stash"));
+                        }
+                        stash = Some(map.next_value_seed(StashSeed::new(self.limits))?);
+                    }
+                    Field::FreeList => {
+                        if free_list.is_some() {
+                            return Err(de::Error::duplicate_field("free_list"));
+                        }
+                        free_list = Some(map.next_value()?);
+                    }
+                    Field::Len => {
+                        if len.is_some() {
+                            return Err(de::Error::duplicate_field("len"));
+                        }
+                        len = Some(map.next_value()?);
+                    }
+                    Field::Entries => {
+                        if entries.is_some() {
+                            return Err(de::Error::duplicate_field("entries"));
+                        }
+                        entries = Some(map.next_value_seed(sparse::EntriesSeed::new(self.limits))?);
+                    }
+                }
+            }
+            match version {
+                Some(version) => {
+                    if len.is_some() || entries.is_some() {
+                        return Err(de::Error::custom(
+                            "a stash in the exact format can't have sparse format fields",
+                        ));
+                    }
+                    let stash = stash.ok_or_else(|| de::Error::missing_field("stash"))?;
+                    let free_list =
+                        free_list.ok_or_else(|| de::Error::missing_field("free_list"))?;
+                    finish(version, stash, free_list)
+                }
+                // A stash in the default (sparse) format.
+                None => {
+                    if stash.is_some() || free_list.is_some() {
+                        return Err(de::Error::missing_field("version"));
+                    }
+                    let len = len.ok_or_else(|| de::Error::missing_field("len"))?;
+                    let entries = entries.ok_or_else(|| de::Error::missing_field("entries"))?;
+                    sparse::build(len, entries, self.limits)
+                }
+            }
+        }
+    }
+
+    /// Check the format version and relink the free list of the deserialized stash.
+    fn finish<V, Ix, A, E>(
+        version: u32,
+        mut stash: Stash<V, Ix, A>,
+        free_list: alloc::vec::Vec<usize>,
+    ) -> Result<Stash<V, Ix, A>, E>
+    where
+        A: Allocator,
+        E: de::Error,
+    {
+        if version != FORMAT_VERSION {
+            return Err(E::invalid_value(
+                Unexpected::Unsigned(version.into()),
+                &"format version 1",
+            ));
+        }
+
+        // Slots that haven't been linked yet. Can't collide with a real link as `data.len()`
+        // can't exceed `isize::MAX`.
+        const UNLINKED: usize = usize::MAX;
+
+        let len = stash.data.len();
+        for entry in &mut stash.data {
+            if let Entry::Empty(next) = entry {
+                *next = UNLINKED;
+            }
+        }
+
+        let mut prev: Option<usize> = None;
+        for &idx in &free_list {
+            // Terminate the list here for now, we'll fix the link up if we see another slot.
+            // This also marks the slot as linked.
+            match stash.data.get_mut(idx) {
+                Some(Entry::Empty(next)) if *next == UNLINKED => *next = len,
+                _ => {
+                    return Err(E::invalid_value(
+                        Unexpected::Unsigned(idx as u64),
+                        &"a vacant slot that isn't already in the free list",
+                    ))
+                }
+            }
+            match prev {
+                Some(prev) => stash.data[prev] = Entry::Empty(idx),
+                None => stash.next_free = idx,
+            }
+            prev = Some(idx);
+        }
+        if free_list.len() != len - stash.size {
+            return Err(E::invalid_length(free_list.len(), &"every vacant slot"));
+        }
+        if prev.is_none() {
+            stash.next_free = len;
+        }
+        Ok(stash)
+    }
+}
+
+/// Serialize a `Stash` as a map of keys to values.
+///
+/// The default format records every slot, vacant or not, so a stash with a single value at key
+/// 100000 serializes as 100000 empty slots followed by that value. This format instead records
+/// the number of slots and a map of keys to values. Use it with
+/// `#[serde(with = "stash::stash::sparse")]`. The default format's deserializer also accepts it
+/// from human readable deserializers (e.g., JSON).
+///
+/// Like the default format, this format does not preserve the order in which vacant slots will be
+/// reused.
+pub mod sparse {
+    use super::*;
+
+    const FIELDS: &[&str] = &["len", "entries"];
+
+    #[derive(Deserialize)]
+    #[serde(field_identifier, rename_all = "lowercase")]
+    enum Field {
+        Len,
+        Entries,
+    }
+
+    /// Serialize a stash in the sparse format.
+    pub fn serialize<V, Ix, A, S>(stash: &Stash<V, Ix, A>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        V: Serialize,
+        Ix: Index,
+        A: Allocator,
+        S: Serializer,
+    {
+        let mut state = serializer.serialize_struct("Stash", 2)?;
+        state.serialize_field("len", &stash.data.len())?;
+        state.serialize_field("entries", &EntriesSer(stash))?;
+        state.end()
+    }
+
+    /// Deserialize a stash in the sparse format.
+    pub fn deserialize<'de, V, Ix, A, D>(deserializer: D) -> Result<Stash<V, Ix, A>, D::Error>
+    where
+        V: Deserialize<'de>,
+        Ix: Index,
+        A: Allocator + Default,
+        D: Deserializer<'de>,
+    {
+        deserialize_with_limits(deserializer, Limits::implicit())
+    }
+
+    /// Deserialize a stash in the sparse format, rejecting it if it exceeds the given limits.
+    pub fn deserialize_with_limits<'de, V, Ix, A, D>(
+        deserializer: D,
+        limits: Limits,
+    ) -> Result<Stash<V, Ix, A>, D::Error>
+    where
+        V: Deserialize<'de>,
+        Ix: Index,
+        A: Allocator + Default,
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_struct("Stash", FIELDS, SparseVisitor::new(limits))
+    }
+
+    pub(super) fn visit_map<'de, V, Ix, A, M>(
+        mut map: M,
+        limits: Limits,
+    ) -> Result<Stash<V, Ix, A>, M::Error>
+    where
+        V: Deserialize<'de>,
+        Ix: Index,
+        A: Allocator + Default,
+        M: MapAccess<'de>,
+    {
+        let mut len = None;
+        let mut entries = None;
+        while let Some(key) = map.next_key()? {
+            match key {
+                Field::Len => {
+                    if len.is_some() {
+                        return Err(de::Error::duplicate_field("len"));
+                    }
+                    len = Some(map.next_value()?);
+                }
+                Field::Entries => {
+                    if entries.is_some() {
+                        return Err(de::Error::duplicate_field("entries"));
+                    }
+                    entries = Some(map.next_value_seed(EntriesSeed::new(limits))?);
+                }
+            }
+        }
+        let len = len.ok_or_else(|| de::Error::missing_field("len"))?;
+        let entries = entries.ok_or_else(|| de::Error::missing_field("entries"))?;
+        build(len, entries, limits)
+    }
+
+    pub(super) fn build<V, Ix, A, E>(
+        len: usize,
+        entries: Entries<V>,
+        limits: Limits,
+    ) -> Result<Stash<V, Ix, A>, E>
+    where
+        Ix: Index,
+        A: Allocator + Default,
+        E: de::Error,
+    {
+        let mut builder = Builder::new(limits, Some(len));
+        // Check this up-front as the length isn't bounded by the size of the input.
+        builder.check_slots(len)?;
+        if len > limits.max_sparse_slots {
+            return Err(E::custom(format_args!(
+                "sparse stash has more than {} slots (pass explicit limits to allow more)",
+                limits.max_sparse_slots
+            )));
+        }
+        let mut entries = entries.0;
+        entries.sort_unstable_by_key(|&(i, _)| i);
+        for (i, v) in entries {
+            if i >= len {
+                return Err(E::invalid_value(
+                    Unexpected::Unsigned(i as u64),
+                    &"a key less than the number of slots",
+                ));
+            }
+            if i < builder.len() {
+                return Err(E::custom(format_args!("duplicate key {}", i)));
+            }
+            while builder.len() < i {
+                builder.push(None)?;
+            }
+            builder.push(Some(v))?;
+        }
+        while builder.len() < len {
+            builder.push(None)?;
+        }
+        Ok(builder.finish())
+    }
+
+    struct EntriesSer<'a, V, Ix, A: Allocator>(&'a Stash<V, Ix, A>);
+
+    impl<'a, V, Ix, A: Allocator> Serialize for EntriesSer<'a, V, Ix, A>
+    where
+        V: Serialize,
+        Ix: Index,
+    {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let mut map = serializer.serialize_map(Some(self.0.len()))?;
+            for (i, e) in self.0.data.iter().enumerate() {
+                if let Entry::Full(v) = e {
+                    map.serialize_entry(&i, v)?;
+                }
+            }
+            map.end()
+        }
+    }
+
+    /// The occupied slots, in no particular order.
+    pub(super) struct Entries<V>(Vec<(usize, V)>);
+
+    /// Reads the occupied slots, enforcing `max_len` as they're read.
+    pub(super) struct EntriesSeed<V> {
+        limits: Limits,
+        _marker: marker::PhantomData<fn(V) -> V>,
+    }
+
+    impl<V> EntriesSeed<V> {
+        pub(super) fn new(limits: Limits) -> EntriesSeed<V> {
+            EntriesSeed {
+                limits,
+                _marker: marker::PhantomData,
+            }
+        }
+    }
+
+    impl<'de, V> DeserializeSeed<'de> for EntriesSeed<V>
+    where
+        V: Deserialize<'de>,
+    {
+        type Value = Entries<V>;
+
+        fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            deserializer.deserialize_map(self)
+        }
+    }
+
+    impl<'de, V> Visitor<'de> for EntriesSeed<V>
+    where
+        V: Deserialize<'de>,
+    {
+        type Value = Entries<V>;
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            write!(formatter, "a map of keys to values")
+        }
+
+        fn visit_map<M>(self, mut map: M) -> Result<Self::Value, M::Error>
+        where
+            M: MapAccess<'de>,
+        {
+            let mut entries =
+                Vec::with_capacity(self.limits.initial_capacity::<(usize, V)>(map.size_hint()));
+            while let Some(entry) = map.next_entry()? {
+                if entries.len() >= self.limits.max_len {
+                    return Err(de::Error::custom(format_args!(
+                        "stash has more than {} entries",
+                        self.limits.max_len
+                    )));
+                }
+                entries.push(entry);
+            }
+            Ok(Entries(entries))
+        }
+    }
+
+    struct SparseVisitor<V, Ix, A: Allocator> {
+        limits: Limits,
+        _marker: marker::PhantomData<fn(V, A) -> Ix>,
+    }
+
+    impl<V, Ix, A: Allocator> SparseVisitor<V, Ix, A> {
+        fn new(limits: Limits) -> SparseVisitor<V, Ix, A> {
+            SparseVisitor {
+                limits,
+                _marker: marker::PhantomData,
+            }
+        }
+    }
+
+    impl<'de, V, Ix, A: Allocator + Default> Visitor<'de> for SparseVisitor<V, Ix, A>
+    where
+        V: Deserialize<'de>,
+        Ix: Index,
+    {
+        type Value = Stash<V, Ix, A>;
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            write!(formatter, "a sparse stash")
+        }
+
+        fn visit_seq<M>(self, mut seq: M) -> Result<Self::Value, M::Error>
+        where
+            M: SeqAccess<'de>,
+        {
+            let len = seq
+                .next_element()?
+                .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+            let entries = seq
+                .next_element_seed(EntriesSeed::new(self.limits))?
+                .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+            build(len, entries, self.limits)
+        }
+
+        fn visit_map<M>(self, map: M) -> Result<Self::Value, M::Error>
+        where
+            M: MapAccess<'de>,
+        {
+            visit_map(map, self.limits)
+        }
+    }
+}
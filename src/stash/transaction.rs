@@ -0,0 +1,181 @@
+use alloc::collections::BTreeSet;
+use alloc::vec::Vec;
+use core::mem;
+
+use super::{Entry, Stash};
+use crate::allocator::{Allocator, Global};
+use crate::index::Index;
+
+/// How to undo a single operation.
+enum Undo<V> {
+    /// A value was put into the slot at this index. If `pushed`, the slot was created by the put.
+    Put { idx: usize, pushed: bool },
+    /// This value was taken from the slot at this index.
+    Take(usize, V),
+    /// The value in the slot at this index was (possibly) modified. This is the original.
+    Modify(usize, V),
+}
+
+/// A set of changes to a `Stash` that will be applied atomically. See `Stash::transaction`.
+///
+/// If the transaction is dropped without being committed (e.g., because the closure passed to
+/// `Stash::transaction` failed or panicked), every change is reverted.
+pub struct Transaction<'a, V: 'a, Ix: 'a = usize, A: Allocator + 'a = Global> {
+    stash: &'a mut Stash<V, Ix, A>,
+    undo: Vec<Undo<V>>,
+    /// Slots whose current value is already restored (or removed) by an entry in `undo`, so
+    /// modifying them doesn't need another copy.
+    saved: BTreeSet<usize>,
+}
+
+impl<'a, V, Ix, A> Transaction<'a, V, Ix, A>
+where
+    Ix: Index,
+    A: Allocator,
+{
+    /// Put a value into the stash.
+    ///
+    /// Returns the index at which this value was stored.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the size of the `Stash<V, Ix>` would overflow the `Ix` index type.
+    pub fn put(&mut self, value: V) -> Ix {
+        let len = self.stash.data.len();
+        let idx = self.stash.put(value).into_usize();
+        self.undo.push(Undo::Put {
+            idx,
+            pushed: self.stash.data.len() > len,
+        });
+        self.saved.insert(idx);
+        Ix::from_usize(idx)
+    }
+
+    /// Get a reference to the value at `index`.
+    #[inline]
+    pub fn get(&self, index: Ix) -> Option<&V> {
+        self.stash.get(index)
+    }
+
+    /// Get the number of items in the stash.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.stash.len()
+    }
+
+    /// Check if the stash is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.stash.is_empty()
+    }
+}
+
+impl<'a, V, Ix, A> Transaction<'a, V, Ix, A>
+where
+    V: Clone,
+    Ix: Index,
+    A: Allocator,
+{
+    /// Take an item from a slot (if non empty).
+    ///
+    /// Keeps a copy of the value in case the transaction is rolled back.
+    pub fn take(&mut self, index: Ix) -> Option<V> {
+        let idx = index.into_usize();
+        let value = self.stash.take(Ix::from_usize(idx))?;
+        self.undo.push(Undo::Take(idx, value.clone()));
+        Some(value)
+    }
+
+    /// Get a mutable reference to the value at `index`.
+    ///
+    /// The first time a value is accessed this way (unless it was put by this transaction), saves
+    /// a copy of it in case the transaction is rolled back.
+    pub fn get_mut(&mut self, index: Ix) -> Option<&mut V> {
+        let idx = index.into_usize();
+        let value = self.stash.get_mut(Ix::from_usize(idx))?;
+        if self.saved.insert(idx) {
+            self.undo.push(Undo::Modify(idx, value.clone()));
+        }
+        Some(value)
+    }
+}
+
+impl<'a, V, Ix, A: Allocator> Drop for Transaction<'a, V, Ix, A> {
+    fn drop(&mut self) {
+        rollback(self.stash, &mut self.undo);
+    }
+}
+
+impl<V, Ix, A> Stash<V, Ix, A>
+where
+    Ix: Index,
+    A: Allocator,
+{
+    /// Apply a set of changes atomically.
+    ///
+    /// Calls `f` with a `Transaction` through which it can put values into, take values from,
+    /// and modify values in the stash. If `f` returns an error (or panics), every change is
+    /// reverted. The stash is then exactly as it was before the transaction started, including
+    /// the order in which vacant slots will be reused, so later keys are assigned exactly as if
+    /// the transaction had never happened.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use stash::Stash;
+    ///
+    /// let mut stash = Stash::new();
+    /// let a = stash.put("a");
+    ///
+    /// let result: Result<(), &str> = stash.transaction(|tx| {
+    ///     tx.put("b");
+    ///     tx.take(a);
+    ///     Err("oops")
+    /// });
+    /// assert!(result.is_err());
+    /// assert_eq!(stash[a], "a");
+    /// assert_eq!(stash.len(), 1);
+    /// assert_eq!(stash.put("c"), 1);
+    /// ```
+    pub fn transaction<T, E, F>(&mut self, f: F) -> Result<T, E>
+    where
+        F: FnOnce(&mut Transaction<'_, V, Ix, A>) -> Result<T, E>,
+    {
+        let mut tx = Transaction {
+            stash: self,
+            undo: Vec::new(),
+            saved: BTreeSet::new(),
+        };
+        let result = f(&mut tx);
+        if result.is_ok() {
+            // Commit.
+            tx.undo.clear();
+        }
+        result
+    }
+}
+
+/// Revert every change in the undo log, in reverse order.
+fn rollback<V, Ix, A: Allocator>(stash: &mut Stash<V, Ix, A>, undo: &mut Vec<Undo<V>>) {
+    while let Some(op) = undo.pop() {
+        match op {
+            Undo::Put { idx, pushed } => {
+                if pushed {
+                    stash.data.pop();
+                } else {
+                    stash.data[idx] = Entry::Empty(stash.next_free);
+                }
+                stash.next_free = idx;
+                stash.size -= 1;
+            }
+            Undo::Take(idx, value) => {
+                match mem::replace(&mut stash.data[idx], Entry::Full(value)) {
+                    Entry::Empty(next) => stash.next_free = next,
+                    Entry::Full(_) => unreachable!(),
+                }
+                stash.size += 1;
+            }
+            Undo::Modify(idx, value) => stash.data[idx] = Entry::Full(value),
+        }
+    }
+}
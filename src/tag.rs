@@ -0,0 +1,276 @@
+//! Versioned keys for `UniqueStash`.
+
+use core::error::Error;
+use core::fmt;
+use core::str::FromStr;
+
+use crate::error::LookupError;
+
+/// The reason a tag failed to parse.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum TagParseErrorKind {
+    /// The string contains a character that isn't valid in the format.
+    InvalidChar,
+    /// The tag is too large to be represented.
+    Overflow,
+    /// The string represents a valid tag, but isn't that tag's canonical form (e.g., it has
+    /// leading zeros or uses lowercase letters).
+    NonCanonical,
+    /// The string is empty, has the wrong length, or is otherwise malformed.
+    InvalidFormat,
+    /// The token wasn't produced by this `TagCodec` (or was tampered with).
+    Unauthenticated,
+}
+
+/// An error returned when parsing a `Tag`.
+///
+/// Use `kind` to find out why parsing failed.
+///
+/// **Breaking change in 0.2:** this used to be a unit struct. Match on `kind()` instead of
+/// `Err(TagParseError)`, and construct errors with `TagParseError::new` (or `From`).
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct TagParseError {
+    kind: TagParseErrorKind,
+}
+
+impl TagParseError {
+    /// Create an error of the given kind (e.g., to report a malformed tag from a custom parser).
+    pub fn new(kind: TagParseErrorKind) -> Self {
+        TagParseError { kind }
+    }
+
+    /// The reason parsing failed.
+    pub fn kind(&self) -> TagParseErrorKind {
+        self.kind
+    }
+}
+
+impl From<TagParseErrorKind> for TagParseError {
+    fn from(kind: TagParseErrorKind) -> Self {
+        TagParseError::new(kind)
+    }
+}
+
+impl Error for TagParseError {}
+
+impl fmt::Display for TagParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self.kind {
+            TagParseErrorKind::InvalidChar => "failed to parse tag: invalid character",
+            TagParseErrorKind::Overflow => "failed to parse tag: tag out of range",
+            TagParseErrorKind::NonCanonical => "failed to parse tag: non-canonical form",
+            TagParseErrorKind::InvalidFormat => "failed to parse tag: invalid format",
+            TagParseErrorKind::Unauthenticated => "failed to parse tag: invalid token",
+        })
+    }
+}
+
+/// A versioned index into a `UniqueStash`.
+///
+/// Can be converted to and from strings of the form `###/###` (no leading
+/// zeros). Every tag has exactly one valid string representation. For a more
+/// compact, URL- and filename-safe form, see `Tag::base32`.
+///
+/// When serialized with a human readable serializer (e.g., JSON), a tag is
+/// represented by this string. Otherwise, it's represented by an `{idx, ver}`
+/// struct (as it always has been, rather than a packed integer, so binary data
+/// written by older versions can still be read).
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Tag {
+    pub(crate) idx: usize,
+    pub(crate) ver: u64,
+}
+
+impl Tag {
+    /// Get the raw index of the slot this tag refers to.
+    ///
+    /// Values are stored (and iterated) in slot order so this can be used to resume a scan
+    /// with `UniqueStash::iter_from` or `UniqueStash::cursor`.
+    #[inline]
+    pub fn index(self) -> usize {
+        self.idx
+    }
+
+    /// Pack this tag into a single integer, version first.
+    pub(crate) fn to_bits(self) -> u128 {
+        (u128::from(self.ver) << 64) | self.idx as u128
+    }
+
+    /// Unpack a tag packed with `to_bits`. Fails if the index doesn't fit in a
+    /// `usize`.
+    pub(crate) fn from_bits(bits: u128) -> Option<Tag> {
+        let idx = bits as u64;
+        if idx > usize::MAX as u64 {
+            return None;
+        }
+        Some(Tag {
+            idx: idx as usize,
+            ver: (bits >> 64) as u64,
+        })
+    }
+}
+
+impl fmt::Debug for Tag {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl fmt::Display for Tag {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.idx, self.ver)
+    }
+}
+
+impl FromStr for Tag {
+    type Err = TagParseError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut pieces = s.split('/');
+        match (pieces.next(), pieces.next(), pieces.next()) {
+            (Some(first), Some(second), None) => {
+                let idx = parse_decimal(first)?;
+                let ver = parse_decimal(second)?;
+                if idx > usize::MAX as u64 {
+                    return Err(TagParseError::new(TagParseErrorKind::Overflow));
+                }
+                Ok(Tag {
+                    idx: idx as usize,
+                    ver,
+                })
+            }
+            _ => Err(TagParseError::new(TagParseErrorKind::InvalidFormat)),
+        }
+    }
+}
+
+/// Parse a decimal number, accepting only its canonical form (digits only, no leading zeros).
+fn parse_decimal(s: &str) -> Result<u64, TagParseError> {
+    let bytes = s.as_bytes();
+    if bytes.is_empty() {
+        return Err(TagParseError::new(TagParseErrorKind::InvalidFormat));
+    }
+    let mut n: u64 = 0;
+    for &c in bytes {
+        if !c.is_ascii_digit() {
+            return Err(TagParseError::new(TagParseErrorKind::InvalidChar));
+        }
+        n = n
+            .checked_mul(10)
+            .and_then(|n| n.checked_add(u64::from(c - b'0')))
+            .ok_or_else(|| TagParseError::new(TagParseErrorKind::Overflow))?;
+    }
+    // Make sure we only accept one form of tag.
+    if bytes.len() > 1 && bytes[0] == b'0' {
+        return Err(TagParseError::new(TagParseErrorKind::NonCanonical));
+    }
+    Ok(n)
+}
+
+/// Crockford's base32 alphabet.
+const BASE32_ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+/// The maximum length of a base32-encoded tag.
+const BASE32_MAX_LEN: usize = 26;
+
+impl Tag {
+    /// Display this tag in its compact base32 form.
+    ///
+    /// The compact form is the Crockford base32 encoding of the tag's index and version packed
+    /// into a single 128-bit integer (version in the high 64 bits). It uses only digits and
+    /// uppercase letters, so it's safe to use in URLs and file names.
+    ///
+    /// Like the `###/###` form, every tag has exactly one valid compact form: digits are
+    /// uppercase, there are no leading zeros, and Crockford's aliases (`I`, `L`, `O`, and
+    /// lowercase letters) are rejected by `from_base32` rather than normalized.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use stash::{Tag, UniqueStash};
+    ///
+    /// let mut stash = UniqueStash::new();
+    /// let tag = stash.put("value");
+    /// let encoded = tag.base32().to_string();
+    /// assert_eq!(Tag::from_base32(&encoded).unwrap(), tag);
+    /// ```
+    pub fn base32(self) -> Base32 {
+        Base32(self)
+    }
+
+    /// Parse a tag from its compact base32 form (see `base32`).
+    pub fn from_base32(s: &str) -> Result<Tag, TagParseError> {
+        let bytes = s.as_bytes();
+        if bytes.is_empty() {
+            return Err(TagParseError::new(TagParseErrorKind::InvalidFormat));
+        }
+
+        let mut bits: u128 = 0;
+        let mut kind = None;
+        for &c in bytes {
+            let digit = match decode_base32(c) {
+                Some((digit, canonical)) => {
+                    if !canonical {
+                        kind = kind.or(Some(TagParseErrorKind::NonCanonical));
+                    }
+                    digit
+                }
+                None => return Err(TagParseError::new(TagParseErrorKind::InvalidChar)),
+            };
+            if bits >> 123 != 0 {
+                kind = kind.or(Some(TagParseErrorKind::Overflow));
+            }
+            bits = (bits << 5) | u128::from(digit);
+        }
+        if bytes.len() > 1 && bytes[0] == b'0' {
+            kind = kind.or(Some(TagParseErrorKind::NonCanonical));
+        }
+        if let Some(kind) = kind {
+            return Err(TagParseError::new(kind));
+        }
+        Tag::from_bits(bits).ok_or_else(|| TagParseError::new(TagParseErrorKind::Overflow))
+    }
+}
+
+/// Decode a Crockford base32 digit, returning the digit and whether it's in canonical form.
+fn decode_base32(c: u8) -> Option<(u8, bool)> {
+    let canonical = !c.is_ascii_lowercase();
+    let digit = match c.to_ascii_uppercase() {
+        c @ b'0'..=b'9' => c - b'0',
+        b'O' => 0,
+        b'I' | b'L' => return Some((1, false)),
+        c => BASE32_ALPHABET.iter().position(|&a| a == c)? as u8,
+    };
+    Some((digit, canonical && c != b'O'))
+}
+
+/// Displays a `Tag` in its compact base32 form. Returned by `Tag::base32`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Base32(Tag);
+
+impl fmt::Display for Base32 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut bits = self.0.to_bits();
+        let mut buf = [0u8; BASE32_MAX_LEN];
+        let mut start = buf.len();
+        loop {
+            start -= 1;
+            buf[start] = BASE32_ALPHABET[(bits & 0x1f) as usize];
+            bits >>= 5;
+            if bits == 0 {
+                break;
+            }
+        }
+        // Safe because the buffer only contains characters from `BASE32_ALPHABET`.
+        f.pad(unsafe { core::str::from_utf8_unchecked(&buf[start..]) })
+    }
+}
+
+/// Classify a lookup of `tag` in a slot with version `current_version` that didn't hit a value.
+pub(crate) fn version_mismatch(current_version: u64, tag: Tag) -> LookupError {
+    if tag.ver < current_version {
+        LookupError::Stale { current_version }
+    } else {
+        LookupError::FutureVersion
+    }
+}
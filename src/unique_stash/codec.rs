@@ -0,0 +1,247 @@
+use core::fmt;
+use core::ops::Deref;
+use core::str;
+
+use super::{Tag, TagParseError, TagParseErrorKind};
+
+/// The number of Feistel rounds.
+const ROUNDS: u64 = 8;
+
+/// Domain separation between the round function and the MAC.
+const ROUND_DOMAIN: u64 = 0x726f_756e_6400_0000;
+const MAC_DOMAIN: u64 = 0x6d61_6300_0000_0000;
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// The length of a token in bytes (before encoding).
+const RAW_LEN: usize = 24;
+
+/// The length of an encoded token.
+pub const TOKEN_LEN: usize = RAW_LEN / 3 * 4;
+
+/// Encodes `Tag`s as opaque, fixed-width, URL-safe tokens.
+///
+/// The `###/###` form of a tag is trivially enumerable: given one tag, it's easy to guess the
+/// tags of other entries. A `TagCodec` instead encrypts tags under a secret key and authenticates
+/// them so they can be handed to untrusted clients (e.g., as session IDs). Decoding fails for any
+/// token that wasn't produced by a codec with the same secret.
+///
+/// Tokens are always `TOKEN_LEN` characters from the URL-safe base64 alphabet (`A-Z`, `a-z`,
+/// `0-9`, `-`, and `_`). Encoding is deterministic: a tag always encodes to the same token.
+///
+/// Under the hood, tags are encrypted with a Feistel network and authenticated with a 64-bit MAC,
+/// both keyed with SipHash-2-4. The secret should be generated randomly and kept private.
+///
+/// # Examples
+///
+/// ```
+/// use stash::unique_stash::TagCodec;
+/// use stash::UniqueStash;
+///
+/// let codec = TagCodec::new(*b"very secret key!");
+/// let mut sessions = UniqueStash::new();
+///
+/// let token = codec.encode(sessions.put("alice"));
+/// assert_eq!(token.len(), stash::unique_stash::TOKEN_LEN);
+///
+/// let tag = codec.decode(&token).unwrap();
+/// assert_eq!(sessions[tag], "alice");
+///
+/// let other = TagCodec::new(*b"another secret!!");
+/// assert!(other.decode(&token).is_err());
+/// ```
+#[derive(Clone)]
+pub struct TagCodec {
+    k0: u64,
+    k1: u64,
+}
+
+impl TagCodec {
+    /// Create a codec with the given secret.
+    pub fn new(secret: [u8; 16]) -> Self {
+        let mut k0 = [0u8; 8];
+        let mut k1 = [0u8; 8];
+        k0.copy_from_slice(&secret[..8]);
+        k1.copy_from_slice(&secret[8..]);
+        TagCodec {
+            k0: u64::from_le_bytes(k0),
+            k1: u64::from_le_bytes(k1),
+        }
+    }
+
+    /// Encode a tag as an opaque token.
+    pub fn encode(&self, tag: Tag) -> TagToken {
+        let bits = tag.to_bits();
+        let mac = self.mac(bits);
+        let (mut left, mut right) = ((bits >> 64) as u64, bits as u64);
+        for round in 0..ROUNDS {
+            let next = left ^ self.round(round, right);
+            left = right;
+            right = next;
+        }
+
+        let mut raw = [0u8; RAW_LEN];
+        raw[..8].copy_from_slice(&left.to_le_bytes());
+        raw[8..16].copy_from_slice(&right.to_le_bytes());
+        raw[16..].copy_from_slice(&mac.to_le_bytes());
+
+        let mut token = [0u8; TOKEN_LEN];
+        for (chunk, out) in raw.chunks(3).zip(token.chunks_mut(4)) {
+            let n = (u32::from(chunk[0]) << 16) | (u32::from(chunk[1]) << 8) | u32::from(chunk[2]);
+            for (i, c) in out.iter_mut().enumerate() {
+                *c = ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize];
+            }
+        }
+        TagToken(token)
+    }
+
+    /// Decode a token produced by `encode`.
+    ///
+    /// Fails if the token is malformed, was tampered with, or was produced with a different
+    /// secret.
+    pub fn decode(&self, token: &str) -> Result<Tag, TagParseError> {
+        let token = token.as_bytes();
+        if token.len() != TOKEN_LEN {
+            return Err(TagParseError::new(TagParseErrorKind::InvalidFormat));
+        }
+
+        let mut raw = [0u8; RAW_LEN];
+        for (chunk, out) in token.chunks(4).zip(raw.chunks_mut(3)) {
+            let mut n = 0u32;
+            for &c in chunk {
+                n = (n << 6) | u32::from(decode_char(c).ok_or_else(invalid_char)?);
+            }
+            out[0] = (n >> 16) as u8;
+            out[1] = (n >> 8) as u8;
+            out[2] = n as u8;
+        }
+
+        let mut word = [0u8; 8];
+        let mut read = |i: usize| {
+            word.copy_from_slice(&raw[i * 8..(i + 1) * 8]);
+            u64::from_le_bytes(word)
+        };
+        let (mut left, mut right, mac) = (read(0), read(1), read(2));
+        for round in (0..ROUNDS).rev() {
+            let prev = right ^ self.round(round, left);
+            right = left;
+            left = prev;
+        }
+
+        let bits = (u128::from(left) << 64) | u128::from(right);
+        if self.mac(bits) != mac {
+            return Err(TagParseError::new(TagParseErrorKind::Unauthenticated));
+        }
+        Tag::from_bits(bits).ok_or_else(|| TagParseError::new(TagParseErrorKind::Overflow))
+    }
+
+    fn round(&self, round: u64, half: u64) -> u64 {
+        siphash(self.k0, self.k1, &[ROUND_DOMAIN | round, half])
+    }
+
+    fn mac(&self, bits: u128) -> u64 {
+        siphash(
+            self.k0,
+            self.k1,
+            &[MAC_DOMAIN, (bits >> 64) as u64, bits as u64],
+        )
+    }
+}
+
+impl fmt::Debug for TagCodec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Don't leak the secret.
+        f.write_str("TagCodec { .. }")
+    }
+}
+
+/// An opaque token produced by `TagCodec::encode`.
+///
+/// Dereferences to a `str` of `TOKEN_LEN` URL-safe characters.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct TagToken([u8; TOKEN_LEN]);
+
+impl TagToken {
+    /// The token as a string.
+    pub fn as_str(&self) -> &str {
+        // Safe because the token only contains characters from `ALPHABET`.
+        unsafe { str::from_utf8_unchecked(&self.0) }
+    }
+}
+
+impl Deref for TagToken {
+    type Target = str;
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for TagToken {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl fmt::Debug for TagToken {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for TagToken {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+fn invalid_char() -> TagParseError {
+    TagParseError::new(TagParseErrorKind::InvalidChar)
+}
+
+fn decode_char(c: u8) -> Option<u8> {
+    match c {
+        b'A'..=b'Z' => Some(c - b'A'),
+        b'a'..=b'z' => Some(c - b'a' + 26),
+        b'0'..=b'9' => Some(c - b'0' + 52),
+        b'-' => Some(62),
+        b'_' => Some(63),
+        _ => None,
+    }
+}
+
+/// SipHash-2-4 of a sequence of 64-bit (little-endian) words.
+fn siphash(k0: u64, k1: u64, msg: &[u64]) -> u64 {
+    let mut v = [
+        k0 ^ 0x736f_6d65_7073_6575,
+        k1 ^ 0x646f_7261_6e64_6f6d,
+        k0 ^ 0x6c79_6765_6e65_7261,
+        k1 ^ 0x7465_6462_7974_6573,
+    ];
+
+    fn sip_round(v: &mut [u64; 4]) {
+        v[0] = v[0].wrapping_add(v[1]);
+        v[1] = v[1].rotate_left(13) ^ v[0];
+        v[0] = v[0].rotate_left(32);
+        v[2] = v[2].wrapping_add(v[3]);
+        v[3] = v[3].rotate_left(16) ^ v[2];
+        v[0] = v[0].wrapping_add(v[3]);
+        v[3] = v[3].rotate_left(21) ^ v[0];
+        v[2] = v[2].wrapping_add(v[1]);
+        v[1] = v[1].rotate_left(17) ^ v[2];
+        v[2] = v[2].rotate_left(32);
+    }
+
+    // The final block holds the message length in bytes (there's never a partial block).
+    let last = (msg.len() as u64 * 8) << 56;
+    for &m in msg.iter().chain(Some(&last)) {
+        v[3] ^= m;
+        sip_round(&mut v);
+        sip_round(&mut v);
+        v[0] ^= m;
+    }
+    v[2] ^= 0xff;
+    for _ in 0..4 {
+        sip_round(&mut v);
+    }
+    v[0] ^ v[1] ^ v[2] ^ v[3]
+}
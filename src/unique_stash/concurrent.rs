@@ -0,0 +1,494 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::marker;
+use core::mem::MaybeUninit;
+use core::ops::Deref;
+use core::ptr;
+use core::sync::atomic::{AtomicPtr, AtomicU64, AtomicUsize, Ordering};
+
+use super::Tag;
+
+// Each slot has a single state word:
+//
+// | version (42 bits) | guards (20 bits) | lifecycle (2 bits) |
+//
+// The version is bumped every time a value is removed so tags are never reused. A slot whose
+// version would overflow is retired instead of being reused.
+const LIFECYCLE_MASK: u64 = 0b11;
+const GUARD_SHIFT: u32 = 2;
+const GUARD_ONE: u64 = 1 << GUARD_SHIFT;
+const MAX_GUARDS: u64 = (1 << 20) - 1;
+const VERSION_SHIFT: u32 = 22;
+const MAX_VERSION: u64 = (1 << (64 - VERSION_SHIFT)) - 1;
+
+/// The slot is vacant (and either free or retired).
+const VACANT: u64 = 0;
+/// The slot holds a value.
+const FULL: u64 = 1;
+/// The value is being taken: no new guards may be created and the taker is waiting for the
+/// existing ones to be dropped.
+const TAKING: u64 = 2;
+/// The value has been removed: no new guards may be created and the last guard will drop the
+/// value.
+const REMOVED: u64 = 3;
+
+fn lifecycle(state: u64) -> u64 {
+    state & LIFECYCLE_MASK
+}
+
+fn guards(state: u64) -> u64 {
+    (state >> GUARD_SHIFT) & MAX_GUARDS
+}
+
+fn version(state: u64) -> u64 {
+    state >> VERSION_SHIFT
+}
+
+fn vacant(version: u64) -> u64 {
+    version << VERSION_SHIFT
+}
+
+// Slots are allocated in segments that double in size so the stash can grow without moving
+// existing slots.
+const BASE_SHIFT: u32 = 5;
+const SEGMENTS: usize = if usize::BITS > 32 { 27 } else { 26 };
+const MAX_SLOTS: usize = ((1 << SEGMENTS) - 1) << BASE_SHIFT;
+
+/// Marks the end of the free list. Indices always fit in 32 bits (see `MAX_SLOTS`).
+const NIL: u64 = u32::MAX as u64;
+
+/// Find the segment and offset of the slot at `idx`.
+fn locate(idx: usize) -> (usize, usize) {
+    let seg = (usize::BITS - 1 - ((idx >> BASE_SHIFT) + 1).leading_zeros()) as usize;
+    (seg, idx - (((1 << seg) - 1) << BASE_SHIFT))
+}
+
+fn segment_len(seg: usize) -> usize {
+    1 << (seg + BASE_SHIFT as usize)
+}
+
+/// Pack a free list link: the slot's index in the low 32 bits and the version the slot will be
+/// reused with in the high 32 bits. Every push uses a new version so a stale head never compares
+/// equal to the current one (no ABA).
+///
+/// Versions are up to 42 bits wide but only their low 32 bits make it into the link, so the ABA
+/// tag wraps around after 2^32 reuses of the same slot. A stale head could only match if a
+/// `pop_free` were preempted between loading the head and swapping it while the same slot was
+/// freed and reused exactly a multiple of 2^32 times.
+fn link(idx: usize, version: u64) -> u64 {
+    (version << 32) | idx as u64
+}
+
+struct Slot<V> {
+    state: AtomicU64,
+    /// The next link in the free list (only meaningful while the slot is free).
+    next: AtomicU64,
+    value: UnsafeCell<MaybeUninit<V>>,
+}
+
+// The value is shared between threads through guards and moved between threads by `take`.
+unsafe impl<V: Send + Sync> Sync for Slot<V> {}
+
+impl<V> Slot<V> {
+    /// Drop a removed value, then free the slot.
+    ///
+    /// The caller must have exclusive access to the value (i.e., it must have removed it and seen
+    /// the last guard dropped).
+    unsafe fn drop_value(&self, stash: &ConcurrentUniqueStash<V>, idx: usize, version: u64) {
+        ptr::drop_in_place((*self.value.get()).as_mut_ptr());
+        stash.free(self, idx, version);
+    }
+}
+
+/// A `UniqueStash` that can be modified through a shared reference.
+///
+/// `put`, `get`, `take`, and `remove` take `&self`, so the stash can be shared between threads
+/// (e.g., in an `Arc`) without a `Mutex`. Each slot's state, including its version, lives in a
+/// single atomic word and vacant slots are kept on a lock-free free list: `put`, `get`, and
+/// `remove` are lock-free. `take` is not: it has to wait for guards to the value being taken to
+/// be dropped (see its documentation).
+///
+/// Like `UniqueStash`, a `Tag` is never reused: once a value is removed, its tag stays invalid
+/// forever. Unlike `UniqueStash`, keys are not assigned deterministically.
+///
+/// # Examples
+///
+/// ```
+/// use stash::ConcurrentUniqueStash;
+/// use std::thread;
+///
+/// let stash = ConcurrentUniqueStash::new();
+/// let tags: Vec<_> = thread::scope(|s| {
+///     let stash = &stash;
+///     let workers: Vec<_> = (0..4).map(|i| s.spawn(move || stash.put(i))).collect();
+///     workers.into_iter().map(|w| w.join().unwrap()).collect()
+/// });
+/// assert_eq!(stash.len(), 4);
+///
+/// let guard = stash.get(tags[0]).unwrap();
+/// assert_eq!(*guard, 0);
+/// drop(guard);
+///
+/// assert_eq!(stash.take(tags[0]), Some(0));
+/// assert!(stash.get(tags[0]).is_none());
+/// ```
+pub struct ConcurrentUniqueStash<V> {
+    segments: [AtomicPtr<Slot<V>>; SEGMENTS],
+    /// The number of slots ever handed out.
+    slots: AtomicUsize,
+    size: AtomicUsize,
+    /// The head of the free list (see `link`).
+    free: AtomicU64,
+    // Send and Sync are implemented manually.
+    _marker: marker::PhantomData<*const V>,
+}
+
+unsafe impl<V: Send> Send for ConcurrentUniqueStash<V> {}
+unsafe impl<V: Send + Sync> Sync for ConcurrentUniqueStash<V> {}
+
+/// A reference to a value in a `ConcurrentUniqueStash`. See `ConcurrentUniqueStash::get`.
+///
+/// The value can't be dropped while a guard to it exists.
+pub struct Guard<'a, V: 'a> {
+    stash: &'a ConcurrentUniqueStash<V>,
+    slot: &'a Slot<V>,
+    tag: Tag,
+}
+
+impl<V> ConcurrentUniqueStash<V> {
+    /// Constructs a new, empty `ConcurrentUniqueStash<V>`.
+    ///
+    /// The stash will not allocate until elements are put onto it.
+    pub const fn new() -> Self {
+        ConcurrentUniqueStash {
+            segments: [const { AtomicPtr::new(ptr::null_mut()) }; SEGMENTS],
+            slots: AtomicUsize::new(0),
+            size: AtomicUsize::new(0),
+            free: AtomicU64::new(NIL),
+            _marker: marker::PhantomData,
+        }
+    }
+
+    /// Get the number of items in the stash.
+    ///
+    /// Other threads may be modifying the stash so this is only a snapshot.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.size.load(Ordering::Relaxed)
+    }
+
+    /// Check if the stash is empty. See `len`.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Put a value into the stash.
+    ///
+    /// Returns the tag at which this value was stored.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the stash would hold more than about 2^32 slots.
+    pub fn put(&self, value: V) -> Tag {
+        let (idx, slot) = match self.pop_free() {
+            Some(free) => free,
+            None => self.alloc(),
+        };
+        // The slot is ours: it's vacant, and nobody can create a guard to a vacant slot.
+        let version = version(slot.state.load(Ordering::Relaxed));
+        unsafe { (*slot.value.get()).as_mut_ptr().write(value) };
+        slot.state.store(vacant(version) | FULL, Ordering::Release);
+        self.size.fetch_add(1, Ordering::Relaxed);
+        Tag { idx, ver: version }
+    }
+
+    /// Get a guard to the value at `index`.
+    ///
+    /// The value won't be dropped while the guard exists, even if it's removed from the stash.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are already about 2^20 guards to the value.
+    pub fn get(&self, index: Tag) -> Option<Guard<'_, V>> {
+        let slot = self.slot(index.idx)?;
+        let mut state = slot.state.load(Ordering::Acquire);
+        loop {
+            if lifecycle(state) != FULL || version(state) != index.ver {
+                return None;
+            }
+            assert!(guards(state) < MAX_GUARDS, "too many guards");
+            match slot.state.compare_exchange_weak(
+                state,
+                state + GUARD_ONE,
+                Ordering::Acquire,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => {
+                    return Some(Guard {
+                        stash: self,
+                        slot,
+                        tag: index,
+                    })
+                }
+                Err(actual) => state = actual,
+            }
+        }
+    }
+
+    /// Check if `index` refers to a value in this stash.
+    #[inline]
+    pub fn contains(&self, index: Tag) -> bool {
+        self.slot(index.idx).is_some_and(|slot| {
+            let state = slot.state.load(Ordering::Acquire);
+            lifecycle(state) == FULL && version(state) == index.ver
+        })
+    }
+
+    /// Get a mutable reference to the value at `index`.
+    pub fn get_mut(&mut self, index: Tag) -> Option<&mut V> {
+        let slot = self.slot(index.idx)?;
+        let state = slot.state.load(Ordering::Relaxed);
+        if lifecycle(state) != FULL || version(state) != index.ver {
+            return None;
+        }
+        // We have exclusive access to the stash so there can't be any guards.
+        Some(unsafe { &mut *(*slot.value.get()).as_mut_ptr() })
+    }
+
+    /// Take an item from a slot (if non empty).
+    ///
+    /// The tag stops being valid immediately. If guards to the value exist, this blocks until
+    /// they're all dropped, so unlike the other methods, `take` isn't lock-free.
+    ///
+    /// # Deadlocks
+    ///
+    /// Calling `take` while the current thread holds a guard to the value never returns, as does
+    /// calling it while another thread holds a guard it won't drop until this `take` returns.
+    /// Use `remove` if guards might outlive the call: it drops the value when the last guard is
+    /// dropped instead of waiting for it.
+    pub fn take(&self, index: Tag) -> Option<V> {
+        let (slot, state) = self.mark(index, TAKING)?;
+        let mut state = state;
+        while guards(state) != 0 {
+            spin();
+            state = slot.state.load(Ordering::Acquire);
+        }
+        unsafe {
+            let value = (*slot.value.get()).as_ptr().read();
+            self.free(slot, index.idx, index.ver);
+            Some(value)
+        }
+    }
+
+    /// Remove an item from a slot (if non empty), dropping it.
+    ///
+    /// The tag stops being valid immediately. If guards to the value exist, the value is dropped
+    /// when the last one is dropped. Returns `false` if there was nothing to remove.
+    pub fn remove(&self, index: Tag) -> bool {
+        match self.mark(index, REMOVED) {
+            Some((slot, state)) => {
+                if guards(state) == 0 {
+                    unsafe { slot.drop_value(self, index.idx, index.ver) };
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Move the full slot at `index` into the given lifecycle state, returning the new state.
+    fn mark(&self, index: Tag, lifecycle_state: u64) -> Option<(&Slot<V>, u64)> {
+        let slot = self.slot(index.idx)?;
+        let mut state = slot.state.load(Ordering::Acquire);
+        loop {
+            if lifecycle(state) != FULL || version(state) != index.ver {
+                return None;
+            }
+            let marked = (state & !LIFECYCLE_MASK) | lifecycle_state;
+            match slot.state.compare_exchange_weak(
+                state,
+                marked,
+                Ordering::Acquire,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => {
+                    self.size.fetch_sub(1, Ordering::Relaxed);
+                    return Some((slot, marked));
+                }
+                Err(actual) => state = actual,
+            }
+        }
+    }
+
+    /// Mark a slot whose value has been moved out or dropped as vacant and put it on the free
+    /// list (or retire it if its version is exhausted).
+    fn free(&self, slot: &Slot<V>, idx: usize, version: u64) {
+        if version == MAX_VERSION {
+            slot.state.store(vacant(version), Ordering::Release);
+            return;
+        }
+        let version = version + 1;
+        slot.state.store(vacant(version), Ordering::Release);
+        let new = link(idx, version);
+        let mut head = self.free.load(Ordering::Relaxed);
+        loop {
+            slot.next.store(head, Ordering::Relaxed);
+            match self
+                .free
+                .compare_exchange_weak(head, new, Ordering::Release, Ordering::Relaxed)
+            {
+                Ok(_) => return,
+                Err(actual) => head = actual,
+            }
+        }
+    }
+
+    /// Pop a slot off the free list.
+    fn pop_free(&self) -> Option<(usize, &Slot<V>)> {
+        let mut head = self.free.load(Ordering::Acquire);
+        loop {
+            let idx = (head & NIL) as usize;
+            if idx as u64 == NIL {
+                return None;
+            }
+            let slot = self.slot(idx).expect("free slots are allocated");
+            let next = slot.next.load(Ordering::Relaxed);
+            match self
+                .free
+                .compare_exchange_weak(head, next, Ordering::Acquire, Ordering::Acquire)
+            {
+                Ok(_) => return Some((idx, slot)),
+                Err(actual) => head = actual,
+            }
+        }
+    }
+
+    /// Allocate a new slot.
+    fn alloc(&self) -> (usize, &Slot<V>) {
+        let idx = self.slots.fetch_add(1, Ordering::Relaxed);
+        assert!(idx < MAX_SLOTS, "ConcurrentUniqueStash is full");
+        let (seg, offset) = locate(idx);
+        let mut segment = self.segments[seg].load(Ordering::Acquire);
+        if segment.is_null() {
+            let new: Box<[Slot<V>]> = (0..segment_len(seg))
+                .map(|_| Slot {
+                    state: AtomicU64::new(vacant(0)),
+                    next: AtomicU64::new(NIL),
+                    value: UnsafeCell::new(MaybeUninit::uninit()),
+                })
+                .collect::<Vec<_>>()
+                .into_boxed_slice();
+            let new = Box::into_raw(new) as *mut Slot<V>;
+            segment = match self.segments[seg].compare_exchange(
+                ptr::null_mut(),
+                new,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => new,
+                Err(existing) => {
+                    // Another thread allocated the segment first.
+                    drop(unsafe { free_segment(new, seg) });
+                    existing
+                }
+            };
+        }
+        (idx, unsafe { &*segment.add(offset) })
+    }
+
+    /// Get the slot at `idx` (if allocated).
+    fn slot(&self, idx: usize) -> Option<&Slot<V>> {
+        if idx >= MAX_SLOTS {
+            return None;
+        }
+        let (seg, offset) = locate(idx);
+        let segment = self.segments[seg].load(Ordering::Acquire);
+        if segment.is_null() {
+            None
+        } else {
+            Some(unsafe { &*segment.add(offset) })
+        }
+    }
+}
+
+/// Reconstruct a segment allocated by `alloc`.
+unsafe fn free_segment<V>(segment: *mut Slot<V>, seg: usize) -> Box<[Slot<V>]> {
+    Box::from_raw(ptr::slice_from_raw_parts_mut(segment, segment_len(seg)))
+}
+
+/// Wait for another thread to make progress.
+fn spin() {
+    #[cfg(feature = "std")]
+    std::thread::yield_now();
+    #[cfg(not(feature = "std"))]
+    core::hint::spin_loop();
+}
+
+impl<V> Drop for ConcurrentUniqueStash<V> {
+    fn drop(&mut self) {
+        for (seg, segment) in self.segments.iter_mut().enumerate() {
+            let segment = *segment.get_mut();
+            if segment.is_null() {
+                continue;
+            }
+            let mut slots = unsafe { free_segment(segment, seg) };
+            for slot in slots.iter_mut() {
+                // Nothing can be borrowing the stash so every non-vacant slot is full.
+                if lifecycle(*slot.state.get_mut()) != VACANT {
+                    unsafe { ptr::drop_in_place(slot.value.get_mut().as_mut_ptr()) };
+                }
+            }
+        }
+    }
+}
+
+impl<V> Default for ConcurrentUniqueStash<V> {
+    #[inline]
+    fn default() -> Self {
+        ConcurrentUniqueStash::new()
+    }
+}
+
+impl<V> fmt::Debug for ConcurrentUniqueStash<V> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ConcurrentUniqueStash")
+            .field("len", &self.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<'a, V> Guard<'a, V> {
+    /// Get the tag of the guarded value.
+    #[inline]
+    pub fn tag(&self) -> Tag {
+        self.tag
+    }
+}
+
+impl<'a, V> Deref for Guard<'a, V> {
+    type Target = V;
+    #[inline]
+    fn deref(&self) -> &V {
+        // The value can't be dropped or moved while we hold a guard.
+        unsafe { &*(*self.slot.value.get()).as_ptr() }
+    }
+}
+
+impl<'a, V: fmt::Debug> fmt::Debug for Guard<'a, V> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<'a, V> Drop for Guard<'a, V> {
+    fn drop(&mut self) {
+        let state = self.slot.state.fetch_sub(GUARD_ONE, Ordering::AcqRel) - GUARD_ONE;
+        if lifecycle(state) == REMOVED && guards(state) == 0 {
+            // The value was removed while we held the last guard.
+            unsafe { self.slot.drop_value(self.stash, self.tag.idx, self.tag.ver) };
+        }
+    }
+}
@@ -0,0 +1,173 @@
+use crate::allocator::{Allocator, Global};
+
+use super::entry::{self, Entry, VerEntry};
+use super::{Tag, UniqueStash};
+
+/// Find the first full slot at or after `from` (or `data.len()` if there is none).
+fn seek<V>(data: &[VerEntry<V>], from: usize) -> usize {
+    data.get(from..)
+        .and_then(|rest| rest.iter().position(|e| matches!(e.entry, Entry::Full(_))))
+        .map_or(data.len(), |offset| from + offset)
+}
+
+/// A cursor over the values in a `UniqueStash`, in slot order. See `UniqueStash::cursor`.
+///
+/// The cursor either points at a value or is past the end of the stash.
+pub struct Cursor<'a, V: 'a, A: Allocator + 'a = Global> {
+    stash: &'a UniqueStash<V, A>,
+    idx: usize,
+}
+
+// Manual impl: a derive would require `V: Clone`.
+impl<'a, V, A: Allocator> Clone for Cursor<'a, V, A> {
+    fn clone(&self) -> Self {
+        Cursor {
+            stash: self.stash,
+            idx: self.idx,
+        }
+    }
+}
+
+impl<'a, V, A: Allocator> Cursor<'a, V, A> {
+    /// Get the tag of and a reference to the current value, or `None` if the cursor is past the
+    /// end of the stash.
+    #[inline]
+    pub fn current(&self) -> Option<(Tag, &'a V)> {
+        let entry = self.stash.data.get(self.idx)?;
+        entry::value_index_ref((self.idx, entry))
+    }
+
+    /// Move the cursor to the next value (if any).
+    pub fn move_next(&mut self) {
+        if self.idx < self.stash.data.len() {
+            self.idx = seek(&self.stash.data, self.idx + 1);
+        }
+    }
+
+    /// Get the raw index of the slot the cursor points at.
+    ///
+    /// Pass this to `UniqueStash::cursor` (or `UniqueStash::iter_from`) to resume a scan from
+    /// this position later.
+    #[inline]
+    pub fn position(&self) -> usize {
+        self.idx
+    }
+}
+
+/// A cursor over the values in a `UniqueStash`, in slot order, that can modify and remove values.
+/// See `UniqueStash::cursor_mut`.
+///
+/// The cursor either points at a value or is past the end of the stash.
+pub struct CursorMut<'a, V: 'a, A: Allocator + 'a = Global> {
+    stash: &'a mut UniqueStash<V, A>,
+    idx: usize,
+}
+
+impl<'a, V, A: Allocator> CursorMut<'a, V, A> {
+    /// Get the tag of and a reference to the current value, or `None` if the cursor is past the
+    /// end of the stash.
+    #[inline]
+    pub fn current(&self) -> Option<(Tag, &V)> {
+        let entry = self.stash.data.get(self.idx)?;
+        entry::value_index_ref((self.idx, entry))
+    }
+
+    /// Get the tag of and a mutable reference to the current value, or `None` if the cursor is
+    /// past the end of the stash.
+    #[inline]
+    pub fn current_mut(&mut self) -> Option<(Tag, &mut V)> {
+        let entry = self.stash.data.get_mut(self.idx)?;
+        entry::value_index_mut((self.idx, entry))
+    }
+
+    /// Move the cursor to the next value (if any).
+    pub fn move_next(&mut self) {
+        if self.idx < self.stash.data.len() {
+            self.idx = seek(&self.stash.data, self.idx + 1);
+        }
+    }
+
+    /// Remove the current value from the stash and move the cursor to the next value.
+    ///
+    /// Returns `None` (and doesn't move) if the cursor is past the end of the stash.
+    pub fn remove_current(&mut self) -> Option<V> {
+        let (tag, _) = self.current()?;
+        let value = self.stash.take(tag);
+        self.move_next();
+        value
+    }
+
+    /// Get the raw index of the slot the cursor points at.
+    ///
+    /// Pass this to `UniqueStash::cursor_mut` (or `UniqueStash::iter_from`) to resume a scan
+    /// from this position later.
+    #[inline]
+    pub fn position(&self) -> usize {
+        self.idx
+    }
+}
+
+impl<V, A: Allocator> UniqueStash<V, A> {
+    /// Get a cursor pointing at the first value stored at or after the raw index `idx` (see
+    /// `Tag::index`).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use stash::UniqueStash;
+    ///
+    /// let mut stash = UniqueStash::new();
+    /// let tags: Vec<_> = stash.extend(0..4).collect();
+    /// stash.take(tags[1]);
+    ///
+    /// let mut cursor = stash.cursor(1);
+    /// assert_eq!(cursor.current(), Some((tags[2], &2)));
+    /// cursor.move_next();
+    /// assert_eq!(cursor.current(), Some((tags[3], &3)));
+    /// cursor.move_next();
+    /// assert_eq!(cursor.current(), None);
+    /// ```
+    pub fn cursor(&self, idx: usize) -> Cursor<'_, V, A> {
+        Cursor {
+            idx: seek(&self.data, idx),
+            stash: self,
+        }
+    }
+
+    /// Get a mutable cursor pointing at the first value stored at or after the raw index `idx`
+    /// (see `Tag::index`).
+    ///
+    /// # Examples
+    ///
+    /// Expire every odd value, in pages of two:
+    ///
+    /// ```
+    /// use stash::UniqueStash;
+    ///
+    /// let mut stash = UniqueStash::new();
+    /// stash.extend(0..10).count();
+    ///
+    /// let mut position = 0;
+    /// loop {
+    ///     let mut cursor = stash.cursor_mut(position);
+    ///     for _ in 0..2 {
+    ///         match cursor.current() {
+    ///             Some((_, v)) if v % 2 == 1 => drop(cursor.remove_current()),
+    ///             Some(_) => cursor.move_next(),
+    ///             None => break,
+    ///         }
+    ///     }
+    ///     if cursor.current().is_none() {
+    ///         break;
+    ///     }
+    ///     position = cursor.position();
+    /// }
+    /// assert!(stash.values().eq(&[0, 2, 4, 6, 8]));
+    /// ```
+    pub fn cursor_mut(&mut self, idx: usize) -> CursorMut<'_, V, A> {
+        CursorMut {
+            idx: seek(&self.data, idx),
+            stash: self,
+        }
+    }
+}
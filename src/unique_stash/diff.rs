@@ -0,0 +1,251 @@
+use alloc::vec::Vec;
+use core::cmp;
+
+use super::entry::{Entry, VerEntry};
+use super::{Tag, UniqueStash};
+use crate::allocator::Allocator;
+use crate::diff::{Change, PatchError};
+
+/// A change to a single slot.
+enum SlotChange<V> {
+    Add(V),
+    Remove,
+    Modify(V),
+}
+
+fn value<V>(data: &[VerEntry<V>], idx: usize) -> Option<(Tag, &V)> {
+    match data.get(idx) {
+        Some(VerEntry {
+            version,
+            entry: Entry::Full(value),
+        }) => Some((Tag { idx, ver: *version }, value)),
+        _ => None,
+    }
+}
+
+/// The lowest version the slot at `idx` may have after a patch: a slot must never go back to a
+/// version that it has already handed out (or will hand out) a tag for.
+fn min_version<V>(data: &[VerEntry<V>], idx: usize) -> u64 {
+    match data.get(idx) {
+        Some(VerEntry {
+            version,
+            entry: Entry::Full(_),
+        }) => version + 1,
+        Some(VerEntry { version, .. }) => *version,
+        None => 0,
+    }
+}
+
+impl<V, A: Allocator> UniqueStash<V, A> {
+    /// Iterate over the tags vacant slots will be reused with, in the order in which they'll be
+    /// reused.
+    fn vacant_tags(&self) -> impl Iterator<Item = Tag> + '_ {
+        self.free_list().map(move |idx| Tag {
+            idx,
+            ver: self.data[idx].version,
+        })
+    }
+
+    /// Compute the changes needed to turn this stash into `other`.
+    ///
+    /// Changes are ordered by index and followed by a `Change::FreeList` if the number of slots,
+    /// the order in which vacant slots will be reused, or the versions they'll be reused with
+    /// differ. Values are compared with `PartialEq`.
+    ///
+    /// A slot whose value was replaced by a value with a new tag is reported as a `Removed`
+    /// change (with the old tag) followed by an `Added` change (with the new tag).
+    pub fn diff(&self, other: &Self) -> Vec<Change<Tag, V>>
+    where
+        V: Clone + PartialEq,
+    {
+        let mut changes = Vec::new();
+        for idx in 0..cmp::max(self.data.len(), other.data.len()) {
+            match (value(&self.data, idx), value(&other.data, idx)) {
+                (None, Some((tag, v))) => changes.push(Change::Added {
+                    index: tag,
+                    value: v.clone(),
+                }),
+                (Some((tag, _)), None) => changes.push(Change::Removed { index: tag }),
+                (Some((a, _)), Some((b, v))) if a != b => {
+                    changes.push(Change::Removed { index: a });
+                    changes.push(Change::Added {
+                        index: b,
+                        value: v.clone(),
+                    });
+                }
+                (Some((_, a)), Some((tag, b))) if a != b => changes.push(Change::Modified {
+                    index: tag,
+                    value: b.clone(),
+                }),
+                _ => (),
+            }
+        }
+        if self.data.len() != other.data.len() || !self.vacant_tags().eq(other.vacant_tags()) {
+            changes.push(Change::FreeList {
+                len: other.data.len(),
+                order: other.vacant_tags().collect(),
+            });
+        }
+        changes
+    }
+
+    /// Apply changes computed by `diff`.
+    ///
+    /// Changes must be ordered by index (as returned by `diff`). The patch is checked before
+    /// anything is modified: if it doesn't apply to this stash (e.g., it removes a value with a
+    /// tag that isn't in the stash, rolls back the version of a slot, removes slots, or leaves
+    /// the free list inconsistent), the stash is left unmodified.
+    ///
+    /// A `UniqueStash` never drops slots (that would forget their versions), so patches that
+    /// shrink it (e.g., a diff against a fresh stash) are rejected.
+    pub fn apply_patch<I>(&mut self, patch: I) -> Result<(), PatchError>
+    where
+        I: IntoIterator<Item = Change<Tag, V>>,
+    {
+        let mut changes: Vec<(Tag, SlotChange<V>)> = Vec::new();
+        let mut free_list: Option<(usize, Vec<Tag>)> = None;
+        for change in patch {
+            let (tag, change) = match change {
+                Change::Added { index, value } => (index, SlotChange::Add(value)),
+                Change::Removed { index } => (index, SlotChange::Remove),
+                Change::Modified { index, value } => (index, SlotChange::Modify(value)),
+                Change::FreeList { len, order } => {
+                    if free_list.is_some() {
+                        return Err(PatchError::InvalidFreeList);
+                    }
+                    free_list = Some((len, order));
+                    continue;
+                }
+            };
+            // A slot may only be changed once, except for a removal followed by an addition.
+            let ordered = match changes.last() {
+                Some((prev, SlotChange::Remove)) if prev.idx == tag.idx => {
+                    matches!(change, SlotChange::Add(_))
+                }
+                Some((prev, _)) => prev.idx < tag.idx,
+                None => true,
+            };
+            if !ordered {
+                return Err(PatchError::Conflict { index: tag.idx });
+            }
+            changes.push((tag, change));
+        }
+
+        // Check the changes against the current contents, tracking the version of each full slot.
+        let new_len = free_list.as_ref().map_or(self.data.len(), |&(len, _)| len);
+        // Every new slot must be added or listed as vacant. Check this before allocating.
+        if let Some((len, ref order)) = free_list {
+            // Dropping slots would forget their versions and let their tags be handed out again.
+            if len < self.data.len() {
+                return Err(PatchError::InvalidFreeList);
+            }
+            let added = changes
+                .iter()
+                .filter(|(_, change)| matches!(change, SlotChange::Add(_)))
+                .count();
+            if len - cmp::min(len, self.data.len()) > added + order.len() {
+                return Err(PatchError::InvalidFreeList);
+            }
+        }
+        let mut full: Vec<Option<u64>> = (0..cmp::max(new_len, self.data.len()))
+            .map(|idx| value(&self.data, idx).map(|(tag, _)| tag.ver))
+            .collect();
+        let mut delta = 0isize;
+        for &(tag, ref change) in &changes {
+            let current = full.get(tag.idx).copied().flatten();
+            match *change {
+                SlotChange::Add(_)
+                    if current.is_none()
+                        && tag.idx < new_len
+                        && tag.ver >= min_version(&self.data, tag.idx) =>
+                {
+                    full[tag.idx] = Some(tag.ver);
+                    delta += 1;
+                }
+                SlotChange::Remove if current == Some(tag.ver) => {
+                    full[tag.idx] = None;
+                    delta -= 1;
+                }
+                SlotChange::Modify(_) if current == Some(tag.ver) => (),
+                _ => return Err(PatchError::Conflict { index: tag.idx }),
+            }
+        }
+
+        match free_list {
+            None => {
+                // Without a free list, the set of vacant slots can't change.
+                if changes
+                    .iter()
+                    .any(|(tag, _)| full[tag.idx].is_some() != value(&self.data, tag.idx).is_some())
+                {
+                    return Err(PatchError::InvalidFreeList);
+                }
+                for (tag, change) in changes {
+                    let slot = &mut self.data[tag.idx];
+                    match change {
+                        SlotChange::Add(value) => {
+                            slot.version = tag.ver;
+                            slot.entry = Entry::Full(value);
+                        }
+                        SlotChange::Modify(value) => slot.entry = Entry::Full(value),
+                        // Always followed by an addition.
+                        SlotChange::Remove => (),
+                    }
+                }
+            }
+            Some((len, order)) => {
+                // Every remaining slot past the end must be vacant, and the free list must list
+                // every vacant slot exactly once.
+                if full[len..].iter().any(Option::is_some) {
+                    return Err(PatchError::InvalidFreeList);
+                }
+                let vacant = full[..len].iter().filter(|f| f.is_none()).count();
+                if order.len() != vacant {
+                    return Err(PatchError::InvalidFreeList);
+                }
+                for tag in &order {
+                    // Mark listed slots as full to catch duplicates.
+                    match full.get_mut(tag.idx) {
+                        Some(f)
+                            if f.is_none()
+                                && tag.idx < len
+                                && tag.ver >= min_version(&self.data, tag.idx) =>
+                        {
+                            *f = Some(tag.ver)
+                        }
+                        _ => return Err(PatchError::InvalidFreeList),
+                    }
+                }
+
+                // Everything checks out, apply.
+                self.data.truncate(len);
+                self.data.resize_with(len, || VerEntry {
+                    version: 0,
+                    entry: Entry::Empty(len),
+                });
+                // Changes past the end can only be removals, which truncating took care of.
+                for (tag, change) in changes.into_iter().filter(|(tag, _)| tag.idx < len) {
+                    let slot = &mut self.data[tag.idx];
+                    match change {
+                        SlotChange::Add(value) => {
+                            slot.version = tag.ver;
+                            slot.entry = Entry::Full(value);
+                        }
+                        SlotChange::Modify(value) => slot.entry = Entry::Full(value),
+                        SlotChange::Remove => slot.entry = Entry::Empty(len),
+                    }
+                }
+                self.next_free = order.first().map_or(len, |tag| tag.idx);
+                for (i, tag) in order.iter().enumerate() {
+                    let next = order.get(i + 1).map_or(len, |tag| tag.idx);
+                    self.data[tag.idx] = VerEntry {
+                        version: tag.ver,
+                        entry: Entry::Empty(next),
+                    };
+                }
+                self.size = (self.size as isize + delta) as usize;
+            }
+        }
+        Ok(())
+    }
+}
@@ -91,3 +91,23 @@ pub fn value<V>(entry: VerEntry<V>) -> Option<V> {
         Empty(_) => None,
     }
 }
+
+pub fn index_ref<V>((i, entry): (usize, &VerEntry<V>)) -> Option<Tag> {
+    match entry.entry {
+        Full(_) => Some(Tag {
+            idx: i,
+            ver: entry.version,
+        }),
+        Empty(_) => None,
+    }
+}
+
+pub fn index<V>((i, entry): (usize, VerEntry<V>)) -> Option<Tag> {
+    match entry.entry {
+        Full(_) => Some(Tag {
+            idx: i,
+            ver: entry.version,
+        }),
+        Empty(_) => None,
+    }
+}
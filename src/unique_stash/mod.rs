@@ -1,83 +1,51 @@
-use alloc::vec::{self, Vec};
-use core::error::Error;
+use crate::allocator::vec::{self, Vec};
+use crate::allocator::{Allocator, Global};
 use core::fmt;
+use core::hash::{Hash, Hasher};
 use core::iter;
 use core::mem;
-use core::ops::{Index, IndexMut};
+use core::ops::{self, Bound, Index, IndexMut, RangeBounds};
 use core::slice;
-use core::str::FromStr;
 
 use self::entry::{Entry, VerEntry};
+use crate::error::LookupError;
+use crate::index::Index as StashIndex;
+use crate::stash::{Entry as StashEntry, Stash};
+use crate::tag::version_mismatch;
 
-mod entry;
-
-#[derive(Debug, Clone, Eq, PartialEq)]
-pub struct TagParseError;
-
-impl Error for TagParseError {}
-
-impl fmt::Display for TagParseError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        f.write_str("failed to parse tag")
-    }
-}
-
-/// A versioned index into a `UniqueStash`.
-///
-/// Can be converted to and from strings of the form `###/###` (no leading
-/// zeros). Every tag has exactly one valid string representation.
-#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
-#[cfg_attr(feature = "serialization", derive(Serialize, Deserialize))]
-pub struct Tag {
-    idx: usize,
-    ver: u64,
-}
-
-impl fmt::Debug for Tag {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        fmt::Display::fmt(self, f)
-    }
-}
-
-impl fmt::Display for Tag {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}/{}", self.idx, self.ver)
-    }
-}
-impl FromStr for Tag {
-    type Err = TagParseError;
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut pieces = s.split('/').fuse();
-        if let (Some(first), Some(second), None) = (pieces.next(), pieces.next(), pieces.next()) {
-            // Make sure we only accept one form of tag.
-            if (first.len() > 1 && first.as_bytes()[0] == b'0')
-                || (second.len() > 1 && second.as_bytes()[0] == b'0')
-            {
-                return Err(TagParseError);
-            }
+mod codec;
+#[cfg(all(target_has_atomic = "64", target_has_atomic = "ptr"))]
+mod concurrent;
+mod cursor;
+mod diff;
+pub(crate) mod entry;
+#[cfg(feature = "rayon")]
+mod par;
+#[cfg(feature = "serialization")]
+mod serialization;
 
-            if let (Ok(index), Ok(version)) = (first.parse(), second.parse()) {
-                return Ok(Tag {
-                    idx: index,
-                    ver: version,
-                });
-            }
-        }
-        Err(TagParseError)
-    }
-}
+pub use self::codec::{TagCodec, TagToken, TOKEN_LEN};
+#[cfg(all(target_has_atomic = "64", target_has_atomic = "ptr"))]
+pub use self::concurrent::{ConcurrentUniqueStash, Guard};
+pub use self::cursor::{Cursor, CursorMut};
+#[cfg(feature = "rayon")]
+pub use self::par::{IntoParIter, ParIter, ParIterMut, ParValuesMut};
+#[cfg(feature = "serialization")]
+pub use self::serialization::{exact, sparse};
+pub use crate::tag::{Base32, Tag, TagParseError, TagParseErrorKind};
 
 /// The iterator produced by `Unique::extend`.
-pub struct Extend<'a, I>
+pub struct Extend<'a, I, A = Global>
 where
     I: Iterator,
     I::Item: 'a,
+    A: Allocator + 'a,
 {
     iter: I,
-    stash: &'a mut UniqueStash<I::Item>,
+    stash: &'a mut UniqueStash<I::Item, A>,
 }
 
-impl<'a, I> Drop for Extend<'a, I>
+impl<'a, I, A: Allocator> Drop for Extend<'a, I, A>
 where
     I: Iterator,
     I::Item: 'a,
@@ -87,7 +55,7 @@ where
     }
 }
 
-impl<'a, I> Iterator for Extend<'a, I>
+impl<'a, I, A: Allocator> Iterator for Extend<'a, I, A>
 where
     I: Iterator,
     I::Item: 'a,
@@ -102,14 +70,14 @@ where
     }
 }
 
-impl<'a, I> ExactSizeIterator for Extend<'a, I>
+impl<'a, I, A: Allocator> ExactSizeIterator for Extend<'a, I, A>
 where
     I: ExactSizeIterator,
     I::Item: 'a,
 {
 }
 
-impl<'a, I> DoubleEndedIterator for Extend<'a, I>
+impl<'a, I, A: Allocator> DoubleEndedIterator for Extend<'a, I, A>
 where
     I: DoubleEndedIterator,
     I::Item: 'a,
@@ -132,8 +100,20 @@ pub struct IterMut<'a, V: 'a> {
 }
 
 /// Iterator over the `(index, value)` pairs.
-pub struct IntoIter<V> {
-    inner: iter::Enumerate<vec::IntoIter<VerEntry<V>>>,
+pub struct IntoIter<V, A: Allocator = Global> {
+    inner: iter::Enumerate<vec::IntoIter<VerEntry<V>, A>>,
+    len: usize,
+}
+
+/// Iterator over the tags of the values in the stash.
+pub struct Keys<'a, V: 'a> {
+    inner: iter::Enumerate<slice::Iter<'a, VerEntry<V>>>,
+    len: usize,
+}
+
+/// Iterator over the tags of the values in the stash, consuming the stash.
+pub struct IntoKeys<V, A: Allocator = Global> {
+    inner: iter::Enumerate<vec::IntoIter<VerEntry<V>, A>>,
     len: usize,
 }
 
@@ -150,18 +130,75 @@ pub struct ValuesMut<'a, V: 'a> {
 }
 
 /// Iterator over values in the stash.
-pub struct IntoValues<V> {
-    inner: vec::IntoIter<VerEntry<V>>,
+pub struct IntoValues<V, A: Allocator = Global> {
+    inner: vec::IntoIter<VerEntry<V>, A>,
+    len: usize,
+}
+
+/// Iterator over the `(index, &value)` pairs in a range of slots. See `UniqueStash::range`.
+pub struct Range<'a, V: 'a> {
+    inner: iter::Zip<ops::Range<usize>, slice::Iter<'a, VerEntry<V>>>,
+}
+
+impl<'a, V> Iterator for Range<'a, V> {
+    type Item = (Tag, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.find_map(entry::value_index_ref)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.inner.size_hint().1)
+    }
+}
+
+impl<'a, V> DoubleEndedIterator for Range<'a, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        (&mut self.inner).rev().find_map(entry::value_index_ref)
+    }
+}
+
+/// Iterator over the vacant slots in the order in which `put` will reuse them.
+struct FreeList<'a, V: 'a> {
+    data: &'a [VerEntry<V>],
+    next: usize,
     len: usize,
 }
 
+impl<'a, V> Iterator for FreeList<'a, V> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        match self.data.get(self.next) {
+            Some(VerEntry {
+                entry: Entry::Empty(next),
+                ..
+            }) => {
+                let idx = mem::replace(&mut self.next, *next);
+                self.len -= 1;
+                Some(idx)
+            }
+            _ => None,
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<'a, V> ExactSizeIterator for FreeList<'a, V> {}
+
 impl_iter!(Values, (<'a, V>), &'a V, entry::value_ref, ());
 impl_iter!(ValuesMut, (<'a, V>), &'a mut V, entry::value_mut, ());
-impl_iter!(IntoValues, (<V>), V, entry::value, ());
+impl_iter!(IntoValues, (<V, A>), V, entry::value, (where A: Allocator));
 
 impl_iter!(Iter, (<'a, V>), (Tag, &'a V), entry::value_index_ref, ());
 impl_iter!(IterMut, (<'a, V>), (Tag, &'a mut V), entry::value_index_mut, ());
-impl_iter!(IntoIter, (<V>), (Tag, V), entry::value_index, ());
+impl_iter!(IntoIter, (<V, A>), (Tag, V), entry::value_index, (where A: Allocator));
+
+impl_iter!(Keys, (<'a, V>), Tag, entry::index_ref, ());
+impl_iter!(IntoKeys, (<V, A>), Tag, entry::index, (where A: Allocator));
 
 /// An `O(1)` amortized table that does not reuse keys.
 ///
@@ -171,9 +208,11 @@ impl_iter!(IntoIter, (<V>), (Tag, V), entry::value_index, ());
 ///
 /// An example use case is a session table where expired session IDs should
 /// never be re-used.
+///
+/// The slots are allocated with `A` (the global allocator by default). See `UniqueStash::new_in`.
 #[derive(Clone)]
-pub struct UniqueStash<V> {
-    data: Vec<VerEntry<V>>,
+pub struct UniqueStash<V, A: Allocator = Global> {
+    data: Vec<VerEntry<V>, A>,
     size: usize,
     next_free: usize,
 }
@@ -235,6 +274,54 @@ impl<V> UniqueStash<V> {
             size: 0,
         }
     }
+}
+
+impl<V, A: Allocator> UniqueStash<V, A> {
+    /// Constructs a new, empty `UniqueStash<T, A>` that allocates its slots with `alloc`.
+    ///
+    /// The stash will not allocate until elements are put onto it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #![cfg_attr(feature = "nightly", feature(allocator_api))]
+    /// # #[cfg(feature = "allocator-api2")] {
+    /// extern crate allocator_api2;
+    /// # extern crate stash;
+    /// use allocator_api2::alloc::Global;
+    /// use stash::UniqueStash;
+    ///
+    /// let mut stash: UniqueStash<i32, Global> = UniqueStash::new_in(Global);
+    /// stash.put(1);
+    /// # }
+    /// ```
+    #[inline]
+    pub const fn new_in(alloc: A) -> Self {
+        UniqueStash {
+            data: Vec::new_in(alloc),
+            next_free: 0,
+            size: 0,
+        }
+    }
+
+    /// Constructs a new, empty `UniqueStash<T, A>` with the specified capacity that allocates
+    /// its slots with `alloc`.
+    ///
+    /// See `UniqueStash::with_capacity`.
+    #[inline]
+    pub fn with_capacity_in(capacity: usize, alloc: A) -> Self {
+        UniqueStash {
+            data: Vec::with_capacity_in(capacity, alloc),
+            next_free: 0,
+            size: 0,
+        }
+    }
+
+    /// Returns a reference to the allocator backing the stash.
+    #[inline]
+    pub fn allocator(&self) -> &A {
+        self.data.allocator()
+    }
 
     /// Returns the number of elements the stash can hold without reallocating.
     ///
@@ -322,6 +409,17 @@ impl<V> UniqueStash<V> {
         }
     }
 
+    /// Get the tag that would be returned from next call to `put`.
+    pub fn next_tag(&self) -> Tag {
+        Tag {
+            idx: self.next_free,
+            ver: self
+                .data
+                .get(self.next_free)
+                .map_or(0, |entry| entry.version),
+        }
+    }
+
     /// Put a value into the stash.
     ///
     /// Returns the index at which this value was stored.
@@ -356,7 +454,7 @@ impl<V> UniqueStash<V> {
     /// items are actually inserted as the Iterator is read. If the returned
     /// Iterator is dropped, the rest of the items will be inserted all at once.
     #[inline]
-    pub fn extend<I>(&mut self, iter: I) -> Extend<I>
+    pub fn extend<I>(&mut self, iter: I) -> Extend<'_, I, A>
     where
         I: Iterator<Item = V>,
     {
@@ -369,7 +467,7 @@ impl<V> UniqueStash<V> {
     ///
     /// Returns an iterator that yields `(index, &value)` pairs.
     #[inline]
-    pub fn iter(&self) -> Iter<V> {
+    pub fn iter(&self) -> Iter<'_, V> {
         Iter {
             len: self.len(),
             inner: self.data.iter().enumerate(),
@@ -380,16 +478,86 @@ impl<V> UniqueStash<V> {
     ///
     /// Returns an iterator that yields `(index, &mut value)` pairs.
     #[inline]
-    pub fn iter_mut(&mut self) -> IterMut<V> {
+    pub fn iter_mut(&mut self) -> IterMut<'_, V> {
         IterMut {
             len: self.len(),
             inner: self.data.iter_mut().enumerate(),
         }
     }
 
+    /// Iterate over the items stored in a range of slots (by raw index, see `Tag::index`).
+    ///
+    /// Returns an iterator that yields `(index, &value)` pairs in slot order, the same order as
+    /// `iter`. Bounds past the end of the stash are clamped, so this never panics.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use stash::UniqueStash;
+    ///
+    /// let mut stash = UniqueStash::new();
+    /// stash.extend(0..10).count();
+    ///
+    /// let page: Vec<_> = stash.range(3..6).map(|(_, v)| *v).collect();
+    /// assert_eq!(page, [3, 4, 5]);
+    /// ```
+    pub fn range<R: RangeBounds<usize>>(&self, range: R) -> Range<'_, V> {
+        let len = self.data.len();
+        let end = match range.end_bound() {
+            Bound::Included(&end) => end.saturating_add(1),
+            Bound::Excluded(&end) => end,
+            Bound::Unbounded => len,
+        }
+        .min(len);
+        let start = match range.start_bound() {
+            Bound::Included(&start) => start,
+            Bound::Excluded(&start) => start.saturating_add(1),
+            Bound::Unbounded => 0,
+        }
+        .min(end);
+        Range {
+            inner: (start..end).zip(self.data[start..end].iter()),
+        }
+    }
+
+    /// Iterate over the items stored at or after the raw index `idx` (see `Tag::index`).
+    ///
+    /// Equivalent to `range(idx..)`. To resume a scan after the item tagged `tag`, use
+    /// `iter_from(tag.index() + 1)`.
+    #[inline]
+    pub fn iter_from(&self, idx: usize) -> Range<'_, V> {
+        self.range(idx..)
+    }
+
+    /// Iterate over the tags of the items in this `UniqueStash<V>`.
+    #[inline]
+    pub fn keys(&self) -> Keys<'_, V> {
+        Keys {
+            len: self.len(),
+            inner: self.data.iter().enumerate(),
+        }
+    }
+
+    /// Iterate over the tags of the items in this `UniqueStash<V>`, dropping the values.
+    #[inline]
+    pub fn into_keys(self) -> IntoKeys<V, A> {
+        IntoKeys {
+            len: self.len(),
+            inner: self.data.into_iter().enumerate(),
+        }
+    }
+
+    /// Collect the tags of the items in this `UniqueStash<V>`.
+    ///
+    /// Unlike `keys`, the result doesn't borrow the stash so it can be used to, e.g., take items
+    /// while iterating.
+    pub fn tags_snapshot(&self) -> alloc::vec::Vec<Tag> {
+        self.keys().collect()
+    }
+
     /// Iterate over the values in this `UniqueStash<V>` by reference.
     #[inline]
-    pub fn values(&self) -> Values<V> {
+    pub fn values(&self) -> Values<'_, V> {
         Values {
             len: self.len(),
             inner: self.data.iter(),
@@ -398,7 +566,7 @@ impl<V> UniqueStash<V> {
 
     /// Mutably iterate over the values in this `UniqueStash<V>` by reference.
     #[inline]
-    pub fn values_mut(&mut self) -> ValuesMut<V> {
+    pub fn values_mut(&mut self) -> ValuesMut<'_, V> {
         ValuesMut {
             len: self.len(),
             inner: self.data.iter_mut(),
@@ -407,7 +575,7 @@ impl<V> UniqueStash<V> {
 
     /// Iterate over the values in this `UniqueStash<V>` by value.
     #[inline]
-    pub fn into_values(self) -> IntoValues<V> {
+    pub fn into_values(self) -> IntoValues<V, A> {
         IntoValues {
             len: self.len(),
             inner: self.data.into_iter(),
@@ -423,7 +591,15 @@ impl<V> UniqueStash<V> {
     }
 
     /// Take an item from a slot (if non empty).
+    #[inline]
     pub fn take(&mut self, index: Tag) -> Option<V> {
+        self.try_take(index).ok()
+    }
+
+    /// Take an item from a slot, reporting why it failed if the tag is invalid.
+    ///
+    /// See `try_get` for the possible errors.
+    pub fn try_take(&mut self, index: Tag) -> Result<V, LookupError> {
         match self.data.get_mut(index.idx) {
             Some(VerEntry { version, entry }) if *version == index.ver => {
                 match mem::replace(entry, Entry::Empty(self.next_free)) {
@@ -433,38 +609,168 @@ impl<V> UniqueStash<V> {
                         *version += 1;
                         self.next_free = index.idx;
                         self.size -= 1;
-                        Some(value)
+                        Ok(value)
                     }
                     empty => {
                         // Just put it back.
                         *entry = empty;
-                        None
+                        Err(LookupError::FutureVersion)
                     }
                 }
             }
+            Some(VerEntry { version, .. }) => Err(version_mismatch(*version, index)),
+            None => Err(LookupError::OutOfBounds),
+        }
+    }
+
+    /// Take an item from a slot without bounds, version, or empty checking.
+    /// So use it very carefully!
+    ///
+    /// # Safety
+    ///
+    /// This can be safely used as long as `index` was returned by `put` on
+    /// this stash and the user is sure not to have taken the value
+    /// associated with it.
+    #[inline]
+    pub unsafe fn take_unchecked(&mut self, index: Tag) -> V {
+        let VerEntry { version, entry } = self.data.get_unchecked_mut(index.idx);
+        debug_assert_eq!(*version, index.ver);
+        match mem::replace(entry, Entry::Empty(self.next_free)) {
+            Entry::Empty(_) => ::unreachable::unreachable(),
+            Entry::Full(value) => {
+                *version += 1;
+                self.next_free = index.idx;
+                self.size -= 1;
+                value
+            }
+        }
+    }
+
+    /// Check if `index` refers to a value in this stash.
+    #[inline]
+    pub fn contains(&self, index: Tag) -> bool {
+        self.get(index).is_some()
+    }
+
+    /// Get the tag of the value currently stored at the raw index `idx` (the index half of a
+    /// `Tag`), if any.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use stash::UniqueStash;
+    ///
+    /// let mut stash = UniqueStash::new();
+    /// let tag = stash.put("value");
+    /// assert_eq!(stash.current_tag(0), Some(tag));
+    /// stash.take(tag);
+    /// assert_eq!(stash.current_tag(0), None);
+    /// ```
+    pub fn current_tag(&self, idx: usize) -> Option<Tag> {
+        match self.data.get(idx) {
+            Some(VerEntry {
+                version,
+                entry: Entry::Full(_),
+            }) => Some(Tag { idx, ver: *version }),
             _ => None,
         }
     }
 
     /// Get a reference to the value at `index`.
+    #[inline]
     pub fn get(&self, index: Tag) -> Option<&V> {
+        self.try_get(index).ok()
+    }
+
+    /// Get a reference to the value at `index`, reporting why it failed if the tag is invalid.
+    ///
+    /// Fails with:
+    ///
+    ///  * `OutOfBounds` if the tag's index is past the end of the stash.
+    ///  * `Stale` if the value the tag referred to has been taken.
+    ///  * `FutureVersion` if the tag can't have been issued by this stash (e.g., it was forged,
+    ///    or it belongs to another stash). This includes tags for a vacant slot's next version.
+    ///
+    /// As every take bumps the slot's version, a `UniqueStash` never fails with `Vacant`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use stash::{LookupError, UniqueStash};
+    ///
+    /// let mut stash = UniqueStash::new();
+    /// let tag = stash.put("session");
+    /// stash.take(tag);
+    /// assert_eq!(
+    ///     stash.try_get(tag),
+    ///     Err(LookupError::Stale { current_version: 1 })
+    /// );
+    /// ```
+    pub fn try_get(&self, index: Tag) -> Result<&V, LookupError> {
         match self.data.get(index.idx) {
             Some(VerEntry {
                 version,
                 entry: Entry::Full(value),
-            }) if *version == index.ver => Some(value),
-            _ => None,
+            }) if *version == index.ver => Ok(value),
+            Some(VerEntry { version, .. }) => Err(version_mismatch(*version, index)),
+            None => Err(LookupError::OutOfBounds),
+        }
+    }
+
+    /// Get a reference to the value at `index` without bounds, version, or empty checking.
+    /// So use it very carefully!
+    ///
+    /// # Safety
+    ///
+    /// This can be safely used as long as `index` was returned by `put` on
+    /// this stash and the user is sure not to have taken the value
+    /// associated with it.
+    #[inline]
+    pub unsafe fn get_unchecked(&self, index: Tag) -> &V {
+        let entry = self.data.get_unchecked(index.idx);
+        debug_assert_eq!(entry.version, index.ver);
+        match entry.entry {
+            Entry::Full(ref value) => value,
+            Entry::Empty(_) => ::unreachable::unreachable(),
         }
     }
 
     /// Get a mutable reference to the value at `index`.
+    #[inline]
     pub fn get_mut(&mut self, index: Tag) -> Option<&mut V> {
+        self.try_get_mut(index).ok()
+    }
+
+    /// Get a mutable reference to the value at `index`, reporting why it failed if the tag is
+    /// invalid.
+    ///
+    /// See `try_get` for the possible errors.
+    pub fn try_get_mut(&mut self, index: Tag) -> Result<&mut V, LookupError> {
         match self.data.get_mut(index.idx) {
             Some(VerEntry {
                 version,
                 entry: Entry::Full(value),
-            }) if *version == index.ver => Some(value),
-            _ => None,
+            }) if *version == index.ver => Ok(value),
+            Some(VerEntry { version, .. }) => Err(version_mismatch(*version, index)),
+            None => Err(LookupError::OutOfBounds),
+        }
+    }
+
+    /// Get a mutable reference to the value at `index` without bounds, version, or empty
+    /// checking. So use it very carefully!
+    ///
+    /// # Safety
+    ///
+    /// This can be safely used as long as `index` was returned by `put` on
+    /// this stash and the user is sure not to have taken the value
+    /// associated with it.
+    #[inline]
+    pub unsafe fn get_unchecked_mut(&mut self, index: Tag) -> &mut V {
+        let entry = self.data.get_unchecked_mut(index.idx);
+        debug_assert_eq!(entry.version, index.ver);
+        match entry.entry {
+            Entry::Full(ref mut value) => value,
+            Entry::Empty(_) => ::unreachable::unreachable(),
         }
     }
 
@@ -479,16 +785,48 @@ impl<V> UniqueStash<V> {
                 continue;
             }
             item.version += 1;
+            let value = mem::replace(&mut item.entry, Entry::Empty(self.next_free));
             self.next_free = i;
             self.size -= 1;
-            item.entry = Entry::Empty(self.next_free);
+            // Drop last, that way a panic just stops this half way through.
+            drop(value);
+        }
+    }
+
+    /// Check if two stashes hold the same values with the same tags *and* will assign the same
+    /// tags to future values.
+    ///
+    /// Unlike `==` (which only compares the tag/value mappings), this also compares the number
+    /// of slots, the order in which vacant slots will be reused, and the versions they'll be
+    /// reused with. Two structurally equal stashes are guaranteed to assign the same tags when
+    /// subjected to the same operations.
+    pub fn structurally_eq(&self, other: &Self) -> bool
+    where
+        V: PartialEq,
+    {
+        self.data.len() == other.data.len()
+            && self == other
+            && self.free_list().eq(other.free_list())
+            && self
+                .data
+                .iter()
+                .zip(&other.data)
+                .all(|(a, b)| a.version == b.version)
+    }
+
+    /// Iterate over the vacant slots in the order in which they'll be reused.
+    fn free_list(&self) -> FreeList<'_, V> {
+        FreeList {
+            data: &self.data,
+            next: self.next_free,
+            len: self.data.len() - self.size,
         }
     }
 }
 
-impl<V> IntoIterator for UniqueStash<V> {
+impl<V, A: Allocator> IntoIterator for UniqueStash<V, A> {
     type Item = (Tag, V);
-    type IntoIter = IntoIter<V>;
+    type IntoIter = IntoIter<V, A>;
 
     #[inline]
     fn into_iter(self) -> Self::IntoIter {
@@ -499,7 +837,7 @@ impl<V> IntoIterator for UniqueStash<V> {
     }
 }
 
-impl<'a, V> IntoIterator for &'a UniqueStash<V> {
+impl<'a, V, A: Allocator> IntoIterator for &'a UniqueStash<V, A> {
     type Item = (Tag, &'a V);
     type IntoIter = Iter<'a, V>;
 
@@ -509,7 +847,7 @@ impl<'a, V> IntoIterator for &'a UniqueStash<V> {
     }
 }
 
-impl<'a, V> IntoIterator for &'a mut UniqueStash<V> {
+impl<'a, V, A: Allocator> IntoIterator for &'a mut UniqueStash<V, A> {
     type Item = (Tag, &'a mut V);
     type IntoIter = IterMut<'a, V>;
 
@@ -519,16 +857,39 @@ impl<'a, V> IntoIterator for &'a mut UniqueStash<V> {
     }
 }
 
-impl<V> fmt::Debug for UniqueStash<V>
+/// Two stashes are equal if they map the same tags to the same values.
+///
+/// This ignores vacant slots so equal stashes may assign different tags to future values. Use
+/// `UniqueStash::structurally_eq` to also compare tag assignment.
+impl<V: PartialEq, A: Allocator> PartialEq for UniqueStash<V, A> {
+    fn eq(&self, other: &Self) -> bool {
+        self.size == other.size && self.iter().eq(other.iter())
+    }
+}
+
+impl<V: Eq, A: Allocator> Eq for UniqueStash<V, A> {}
+
+/// Hashes the tag/value mapping (consistent with `PartialEq`).
+impl<V: Hash, A: Allocator> Hash for UniqueStash<V, A> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_usize(self.size);
+        for pair in self {
+            pair.hash(state);
+        }
+    }
+}
+
+impl<V, A> fmt::Debug for UniqueStash<V, A>
 where
     V: fmt::Debug,
+    A: Allocator,
 {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_map().entries(self).finish()
     }
 }
 
-impl<V> Index<Tag> for UniqueStash<V> {
+impl<V, A: Allocator> Index<Tag> for UniqueStash<V, A> {
     type Output = V;
     #[inline]
     fn index(&self, index: Tag) -> &V {
@@ -536,13 +897,28 @@ impl<V> Index<Tag> for UniqueStash<V> {
     }
 }
 
-impl<V> IndexMut<Tag> for UniqueStash<V> {
+impl<V, A: Allocator> IndexMut<Tag> for UniqueStash<V, A> {
     #[inline]
     fn index_mut(&mut self, index: Tag) -> &mut V {
         self.get_mut(index).expect("index out of bounds")
     }
 }
 
+/// Puts every value into the stash (eagerly, unlike `UniqueStash::extend`).
+impl<V, A: Allocator> iter::Extend<V> for UniqueStash<V, A> {
+    fn extend<I: IntoIterator<Item = V>>(&mut self, iter: I) {
+        UniqueStash::extend(self, iter.into_iter()).for_each(drop);
+    }
+}
+
+impl<V, A: Allocator + Default> iter::FromIterator<V> for UniqueStash<V, A> {
+    fn from_iter<I: IntoIterator<Item = V>>(iter: I) -> Self {
+        let mut stash = UniqueStash::new_in(A::default());
+        iter::Extend::extend(&mut stash, iter);
+        stash
+    }
+}
+
 impl<V> Default for UniqueStash<V> {
     #[inline]
     fn default() -> Self {
@@ -550,111 +926,114 @@ impl<V> Default for UniqueStash<V> {
     }
 }
 
-#[cfg(feature = "serialization")]
-mod serialization {
-    use super::*;
-    use core::marker;
-    use serde::de::{Deserialize, Deserializer, SeqAccess, Visitor};
-    use serde::ser::{Serialize, SerializeSeq, Serializer};
-
-    impl<V> Serialize for UniqueStash<V>
-    where
-        V: Serialize,
-    {
-        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
-            let mut seq = serializer.serialize_seq(Some(self.data.len()))?;
-            for ve in &self.data {
-                let option = match &ve.entry {
-                    Entry::Full(v) => Some(v),
-                    Entry::Empty(_) => None,
-                };
-                seq.serialize_element(&(ve.version, option))?;
-            }
-            seq.end()
-        }
+impl<V, A: Allocator + Clone> UniqueStash<V, A> {
+    /// Convert a `Stash` into a `UniqueStash`, assigning the given version to each slot.
+    ///
+    /// Every value keeps its index and vacant slots are reused in the same order. Combined with
+    /// `into_stash`, this allows converting a `UniqueStash` into a `Stash` and back without
+    /// resurrecting stale tags.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `versions` doesn't have exactly one entry per slot in the stash.
+    pub fn from_stash<Ix: StashIndex>(
+        stash: Stash<V, Ix, A>,
+        versions: alloc::vec::Vec<u64>,
+    ) -> Self {
+        let (data, size, next_free) = stash.into_raw_parts();
+        assert_eq!(
+            data.len(),
+            versions.len(),
+            "expected one version per stash slot"
+        );
+        UniqueStash::from_stash_parts(data, size, next_free, versions)
     }
 
-    impl<'de, V> Deserialize<'de> for UniqueStash<V>
+    fn from_stash_parts<I>(
+        data: Vec<StashEntry<V>, A>,
+        size: usize,
+        next_free: usize,
+        versions: I,
+    ) -> Self
     where
-        V: Deserialize<'de>,
+        I: IntoIterator<Item = u64>,
     {
-        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
-        where
-            D: Deserializer<'de>,
-        {
-            deserializer.deserialize_seq(StashVisitor::new())
+        let mut entries = Vec::with_capacity_in(data.len(), data.allocator().clone());
+        entries.extend(
+            data.into_iter()
+                .zip(versions)
+                .map(|(entry, version)| VerEntry {
+                    version,
+                    entry: match entry {
+                        StashEntry::Full(value) => Entry::Full(value),
+                        StashEntry::Empty(next) => Entry::Empty(next),
+                    },
+                }),
+        );
+        UniqueStash {
+            data: entries,
+            size,
+            next_free,
         }
     }
 
-    struct StashVisitor<V> {
-        _marker: marker::PhantomData<fn(V) -> V>,
-    }
-
-    impl<V> StashVisitor<V> {
-        fn new() -> StashVisitor<V> {
-            StashVisitor {
-                _marker: marker::PhantomData,
-            }
+    /// Convert this `UniqueStash` into a `Stash` and the version of each slot.
+    ///
+    /// Every value keeps its index (the index half of its tag) and vacant slots are reused in the
+    /// same order, so both stashes will assign the same indices to future values. The returned
+    /// versions can be passed to `UniqueStash::from_stash` to convert back.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the stash's slots can't be addressed with `Ix`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use stash::{Stash, UniqueStash};
+    ///
+    /// let mut unique = UniqueStash::new();
+    /// let a = unique.put("a");
+    /// let b = unique.put("b");
+    /// unique.take(a);
+    ///
+    /// let (mut stash, versions): (Stash<_>, _) = unique.into_stash();
+    /// assert_eq!(stash[1], "b");
+    /// assert_eq!(versions, vec![1, 0]);
+    /// assert_eq!(stash.next_index(), 0);
+    ///
+    /// let unique = UniqueStash::from_stash(stash, versions);
+    /// assert_eq!(unique[b], "b");
+    /// assert_eq!(unique.get(a), None);
+    /// ```
+    pub fn into_stash<Ix: StashIndex>(self) -> (Stash<V, Ix, A>, alloc::vec::Vec<u64>) {
+        if let Some(last) = self.data.len().checked_sub(1) {
+            assert!(
+                Ix::try_from_usize(last).is_some(),
+                "stash index type overflowing!"
+            );
         }
+        let mut versions = alloc::vec::Vec::with_capacity(self.data.len());
+        let mut data = Vec::with_capacity_in(self.data.len(), self.data.allocator().clone());
+        data.extend(self.data.into_iter().map(|VerEntry { version, entry }| {
+            versions.push(version);
+            match entry {
+                Entry::Full(value) => StashEntry::Full(value),
+                Entry::Empty(next) => StashEntry::Empty(next),
+            }
+        }));
+        (
+            Stash::from_raw_parts(data, self.size, self.next_free),
+            versions,
+        )
     }
+}
 
-    impl<'de, V> Visitor<'de> for StashVisitor<V>
-    where
-        V: Deserialize<'de>,
-    {
-        type Value = UniqueStash<V>;
-        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-            write!(formatter, "a sequence of optional values and versions")
-        }
-
-        fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
-        where
-            A: SeqAccess<'de>,
-        {
-            let initial_size = seq.size_hint().unwrap_or(8);
-            let mut data = Vec::with_capacity(initial_size);
-            let mut i = 0;
-            let mut next_free = 0;
-            let mut size = 0;
-            let mut first_free = None;
-            while let Some((version, option)) = seq.next_element()? {
-                match option {
-                    Some(v) => {
-                        data.push(VerEntry {
-                            entry: Entry::Full(v),
-                            version,
-                        });
-                        size += 1;
-                    }
-                    None => {
-                        if first_free.is_none() {
-                            first_free = Some(i);
-                        }
-                        data.push(VerEntry {
-                            entry: Entry::Empty(next_free),
-                            version,
-                        });
-                        next_free = i;
-                    }
-                }
-                i += 1;
-            }
-            // fix the last entry in linked list now that we know total length
-            let opt = first_free.and_then(|e| data.get_mut(e));
-            if let Some(VerEntry {
-                entry: Entry::Empty(next),
-                ..
-            }) = opt
-            {
-                *next = i;
-            } else {
-                next_free = i;
-            }
-            Ok(UniqueStash {
-                data,
-                next_free,
-                size,
-            })
-        }
+impl<V, Ix: StashIndex, A: Allocator + Clone> From<Stash<V, Ix, A>> for UniqueStash<V, A> {
+    /// Convert a `Stash` into a `UniqueStash`, preserving indices and the order in which vacant
+    /// slots will be reused. Every slot starts at version 0.
+    fn from(stash: Stash<V, Ix, A>) -> Self {
+        let (data, size, next_free) = stash.into_raw_parts();
+        UniqueStash::from_stash_parts(data, size, next_free, iter::repeat(0))
     }
 }
@@ -0,0 +1,193 @@
+use alloc::vec::Vec;
+
+use rayon::iter::plumbing::UnindexedConsumer;
+use rayon::prelude::*;
+
+use super::entry::{self, VerEntry};
+use super::{Tag, UniqueStash};
+use crate::allocator::{self, Allocator};
+
+/// Parallel iterator over the `(index, &value)` pairs.
+pub struct ParIter<'a, V: 'a> {
+    data: &'a [VerEntry<V>],
+}
+
+/// Parallel iterator over the `(index, &mut value)` pairs.
+pub struct ParIterMut<'a, V: 'a> {
+    data: &'a mut [VerEntry<V>],
+}
+
+/// Parallel iterator over mutable references to the values in the stash.
+pub struct ParValuesMut<'a, V: 'a> {
+    data: &'a mut [VerEntry<V>],
+}
+
+/// Parallel iterator over the `(index, value)` pairs.
+pub struct IntoParIter<V> {
+    data: Vec<VerEntry<V>>,
+}
+
+impl<'a, V> ParallelIterator for ParIter<'a, V>
+where
+    V: Sync,
+{
+    type Item = (Tag, &'a V);
+
+    fn drive_unindexed<C>(self, consumer: C) -> C::Result
+    where
+        C: UnindexedConsumer<Self::Item>,
+    {
+        self.data
+            .par_iter()
+            .enumerate()
+            .filter_map(entry::value_index_ref)
+            .drive_unindexed(consumer)
+    }
+}
+
+impl<'a, V> ParallelIterator for ParIterMut<'a, V>
+where
+    V: Send,
+{
+    type Item = (Tag, &'a mut V);
+
+    fn drive_unindexed<C>(self, consumer: C) -> C::Result
+    where
+        C: UnindexedConsumer<Self::Item>,
+    {
+        self.data
+            .par_iter_mut()
+            .enumerate()
+            .filter_map(entry::value_index_mut)
+            .drive_unindexed(consumer)
+    }
+}
+
+impl<'a, V> ParallelIterator for ParValuesMut<'a, V>
+where
+    V: Send,
+{
+    type Item = &'a mut V;
+
+    fn drive_unindexed<C>(self, consumer: C) -> C::Result
+    where
+        C: UnindexedConsumer<Self::Item>,
+    {
+        self.data
+            .par_iter_mut()
+            .filter_map(entry::value_mut)
+            .drive_unindexed(consumer)
+    }
+}
+
+impl<V> ParallelIterator for IntoParIter<V>
+where
+    V: Send,
+{
+    type Item = (Tag, V);
+
+    fn drive_unindexed<C>(self, consumer: C) -> C::Result
+    where
+        C: UnindexedConsumer<Self::Item>,
+    {
+        self.data
+            .into_par_iter()
+            .enumerate()
+            .filter_map(entry::value_index)
+            .drive_unindexed(consumer)
+    }
+}
+
+impl<V, A: Allocator> UniqueStash<V, A> {
+    /// Iterate over the items in this `UniqueStash<V>` in parallel.
+    ///
+    /// Returns a parallel iterator that yields `(index, &value)` pairs.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rayon::prelude::*;
+    /// use stash::UniqueStash;
+    ///
+    /// let stash: UniqueStash<u64> = (0..1000).collect();
+    /// let sum: u64 = stash.par_iter().map(|(tag, v)| tag.index() as u64 + v).sum();
+    /// assert_eq!(sum, 999 * 1000);
+    /// ```
+    #[inline]
+    pub fn par_iter(&self) -> ParIter<'_, V> {
+        ParIter { data: &self.data }
+    }
+
+    /// Mutably iterate over the items in this `UniqueStash<V>` in parallel.
+    ///
+    /// Returns a parallel iterator that yields `(index, &mut value)` pairs.
+    #[inline]
+    pub fn par_iter_mut(&mut self) -> ParIterMut<'_, V> {
+        ParIterMut {
+            data: &mut self.data,
+        }
+    }
+
+    /// Mutably iterate over the values in this `UniqueStash<V>` in parallel.
+    #[inline]
+    pub fn par_values_mut(&mut self) -> ParValuesMut<'_, V> {
+        ParValuesMut {
+            data: &mut self.data,
+        }
+    }
+
+    /// Iterate over the items in this `UniqueStash<V>` by value, in parallel.
+    ///
+    /// Returns a parallel iterator that yields `(index, value)` pairs.
+    ///
+    /// Rayon can only split a standard `Vec`, so with the `allocator-api2` feature the slots are
+    /// first moved into a `Vec` in the global allocator.
+    #[inline]
+    pub fn into_par_iter(self) -> IntoParIter<V> {
+        IntoParIter {
+            data: allocator::into_std_vec(self.data),
+        }
+    }
+}
+
+impl<'a, V, A> IntoParallelIterator for &'a UniqueStash<V, A>
+where
+    V: Sync,
+    A: Allocator,
+{
+    type Item = (Tag, &'a V);
+    type Iter = ParIter<'a, V>;
+
+    #[inline]
+    fn into_par_iter(self) -> Self::Iter {
+        self.par_iter()
+    }
+}
+
+impl<'a, V, A> IntoParallelIterator for &'a mut UniqueStash<V, A>
+where
+    V: Send,
+    A: Allocator,
+{
+    type Item = (Tag, &'a mut V);
+    type Iter = ParIterMut<'a, V>;
+
+    #[inline]
+    fn into_par_iter(self) -> Self::Iter {
+        self.par_iter_mut()
+    }
+}
+
+impl<V, A> IntoParallelIterator for UniqueStash<V, A>
+where
+    V: Send,
+    A: Allocator,
+{
+    type Item = (Tag, V);
+    type Iter = IntoParIter<V>;
+
+    #[inline]
+    fn into_par_iter(self) -> Self::Iter {
+        UniqueStash::into_par_iter(self)
+    }
+}
@@ -0,0 +1,950 @@
+use super::*;
+use crate::limits::Limits;
+use alloc::vec::Vec;
+use core::marker;
+use serde::de::{
+    self, Deserialize, DeserializeSeed, Deserializer, MapAccess, SeqAccess, Unexpected, Visitor,
+};
+use serde::ser::{Serialize, SerializeMap, SerializeSeq, SerializeStruct, Serializer};
+
+impl Serialize for Tag {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.collect_str(self)
+        } else {
+            // Keep the (already compact) derived struct form so binary data written by older
+            // versions can still be read with non-self-describing formats.
+            let mut state = serializer.serialize_struct("Tag", 2)?;
+            state.serialize_field("idx", &self.idx)?;
+            state.serialize_field("ver", &self.ver)?;
+            state.end()
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Tag {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_any(TagVisitor)
+        } else {
+            deserializer.deserialize_struct("Tag", &["idx", "ver"], TagVisitor)
+        }
+    }
+}
+
+/// Accepts the string and (legacy) struct forms of a tag.
+struct TagVisitor;
+
+impl<'de> Visitor<'de> for TagVisitor {
+    type Value = Tag;
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "a tag")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Tag, E> {
+        v.parse()
+            .map_err(|_| E::invalid_value(Unexpected::Str(v), &"a tag of the form ###/###"))
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Tag, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let idx = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        let ver = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+        Ok(Tag { idx, ver })
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Tag, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(field_identifier, rename_all = "lowercase")]
+        enum Field {
+            Idx,
+            Ver,
+        }
+
+        let mut idx = None;
+        let mut ver = None;
+        while let Some(key) = map.next_key()? {
+            match key {
+                Field::Idx => {
+                    if idx.is_some() {
+                        return Err(de::Error::duplicate_field("idx"));
+                    }
+                    idx = Some(map.next_value()?);
+                }
+                Field::Ver => {
+                    if ver.is_some() {
+                        return Err(de::Error::duplicate_field("ver"));
+                    }
+                    ver = Some(map.next_value()?);
+                }
+            }
+        }
+        let idx = idx.ok_or_else(|| de::Error::missing_field("idx"))?;
+        let ver = ver.ok_or_else(|| de::Error::missing_field("ver"))?;
+        Ok(Tag { idx, ver })
+    }
+}
+
+impl<V, A: Allocator> Serialize for UniqueStash<V, A>
+where
+    V: Serialize,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.data.len()))?;
+        for ve in &self.data {
+            let option = match &ve.entry {
+                Entry::Full(v) => Some(v),
+                Entry::Empty(_) => None,
+            };
+            seq.serialize_element(&(ve.version, option))?;
+        }
+        seq.end()
+    }
+}
+
+impl<'de, V, A: Allocator + Default> Deserialize<'de> for UniqueStash<V, A>
+where
+    V: Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        UniqueStash::deserialize_with_limits(deserializer, Limits::implicit())
+    }
+}
+
+impl<V, A: Allocator> UniqueStash<V, A> {
+    /// Deserialize a stash, rejecting it if it exceeds the given limits.
+    ///
+    /// To use this from a derived `Deserialize` implementation, wrap it in a function and pass
+    /// that to `#[serde(deserialize_with = "...")]`.
+    pub fn deserialize_with_limits<'de, D>(
+        deserializer: D,
+        limits: Limits,
+    ) -> Result<Self, D::Error>
+    where
+        V: Deserialize<'de>,
+        A: Default,
+        D: Deserializer<'de>,
+    {
+        StashSeed::new(limits).deserialize(deserializer)
+    }
+}
+
+/// Deserializes a stash in the default format.
+struct StashSeed<V, A: Allocator> {
+    limits: Limits,
+    _marker: marker::PhantomData<fn(V, A) -> V>,
+}
+
+impl<V, A: Allocator> StashSeed<V, A> {
+    fn new(limits: Limits) -> StashSeed<V, A> {
+        StashSeed {
+            limits,
+            _marker: marker::PhantomData,
+        }
+    }
+}
+
+impl<'de, V, A: Allocator + Default> DeserializeSeed<'de> for StashSeed<V, A>
+where
+    V: Deserialize<'de>,
+{
+    type Value = UniqueStash<V, A>;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let visitor = StashVisitor::new(self.limits);
+        if deserializer.is_human_readable() {
+            // Accept both the sparse and the dense format.
+            deserializer.deserialize_any(visitor)
+        } else {
+            deserializer.deserialize_seq(visitor)
+        }
+    }
+}
+
+/// Builds a stash slot by slot, threading vacant slots onto the free list.
+struct Builder<V, A: Allocator> {
+    stash: UniqueStash<V, A>,
+    first_free: Option<usize>,
+    limits: Limits,
+}
+
+impl<V, A: Allocator + Default> Builder<V, A> {
+    fn new(limits: Limits, size_hint: Option<usize>) -> Builder<V, A> {
+        Builder {
+            stash: UniqueStash::with_capacity_in(
+                limits.initial_capacity::<VerEntry<V>>(size_hint),
+                A::default(),
+            ),
+            first_free: None,
+            limits,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.stash.data.len()
+    }
+
+    /// Check that the stash may have `slots` slots.
+    fn check_slots<E: de::Error>(&self, slots: usize) -> Result<(), E> {
+        if slots > self.limits.max_slots {
+            return Err(E::custom(format_args!(
+                "stash has more than {} slots",
+                self.limits.max_slots
+            )));
+        }
+        Ok(())
+    }
+
+    fn push<E: de::Error>(&mut self, version: u64, option: Option<V>) -> Result<(), E> {
+        self.check_slots(self.len() + 1)?;
+        let stash = &mut self.stash;
+        match option {
+            Some(v) => {
+                if stash.size >= self.limits.max_len {
+                    return Err(E::custom(format_args!(
+                        "stash has more than {} entries",
+                        self.limits.max_len
+                    )));
+                }
+                stash.data.push(VerEntry {
+                    entry: Entry::Full(v),
+                    version,
+                });
+                stash.size += 1;
+            }
+            None => {
+                let i = stash.data.len();
+                if self.first_free.is_none() {
+                    self.first_free = Some(i);
+                }
+                stash.data.push(VerEntry {
+                    entry: Entry::Empty(stash.next_free),
+                    version,
+                });
+                stash.next_free = i;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> UniqueStash<V, A> {
+        let len = self.stash.data.len();
+        // fix the last entry in linked list now that we know total length
+        let opt = self.first_free.and_then(|e| self.stash.data.get_mut(e));
+        if let Some(VerEntry {
+            entry: Entry::Empty(next),
+            ..
+        }) = opt
+        {
+            *next = len;
+        } else {
+            self.stash.next_free = len;
+        }
+        self.stash
+    }
+}
+
+struct StashVisitor<V, A: Allocator> {
+    limits: Limits,
+    _marker: marker::PhantomData<fn(V, A) -> V>,
+}
+
+impl<V, A: Allocator> StashVisitor<V, A> {
+    fn new(limits: Limits) -> StashVisitor<V, A> {
+        StashVisitor {
+            limits,
+            _marker: marker::PhantomData,
+        }
+    }
+}
+
+impl<'de, V, A: Allocator + Default> Visitor<'de> for StashVisitor<V, A>
+where
+    V: Deserialize<'de>,
+{
+    type Value = UniqueStash<V, A>;
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "a sequence of optional values and versions or a map of tags to values"
+        )
+    }
+
+    fn visit_seq<M>(self, mut seq: M) -> Result<Self::Value, M::Error>
+    where
+        M: SeqAccess<'de>,
+    {
+        let mut builder = Builder::new(self.limits, seq.size_hint());
+        while let Some((version, option)) = seq.next_element()? {
+            builder.push(version, option)?;
+        }
+        Ok(builder.finish())
+    }
+
+    fn visit_map<M>(self, map: M) -> Result<Self::Value, M::Error>
+    where
+        M: MapAccess<'de>,
+    {
+        sparse::visit_map(map, self.limits)
+    }
+}
+
+/// Serialize a `UniqueStash` such that, once deserialized, it assigns exactly the same tags
+/// as the original.
+///
+/// The default serialization format only records the tag/value mapping and rebuilds the list
+/// of vacant slots from scratch on deserialization. This format additionally records the
+/// order in which vacant slots will be reused. Use it with
+/// `#[serde(with = "stash::unique_stash::exact")]`.
+///
+/// The format is versioned: it's a struct of the format version (`version`), the stash in its
+/// default format (`stash`), and the list of vacant slots (`free_list`).
+///
+/// Human readable deserializers (e.g., JSON) also accept a stash in the default format (e.g.,
+/// written before a field switched to this format), which gets the same free list as it would
+/// with the default format. The default format is never a map with a `version` field so the two
+/// can't be confused.
+pub mod exact {
+    use super::*;
+
+    const FORMAT_VERSION: u32 = 1;
+
+    const FIELDS: &[&str] = &["version", "stash", "free_list"];
+
+    #[derive(Deserialize)]
+    #[serde(field_identifier, rename_all = "snake_case")]
+    enum Field {
+        Version,
+        Stash,
+        FreeList,
+        // The fields of the sparse format.
+        Len,
+        Entries,
+        Vacant,
+    }
+
+    /// Serialize a stash in the exact format.
+    pub fn serialize<V, A, S>(stash: &UniqueStash<V, A>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        A: Allocator,
+        V: Serialize,
+        S: Serializer,
+    {
+        let mut state = serializer.serialize_struct("UniqueStash", 3)?;
+        state.serialize_field("version", &FORMAT_VERSION)?;
+        state.serialize_field("stash", stash)?;
+        state.serialize_field("free_list", &FreeListSer(stash))?;
+        state.end()
+    }
+
+    /// Deserialize a stash in the exact format.
+    pub fn deserialize<'de, V, A, D>(deserializer: D) -> Result<UniqueStash<V, A>, D::Error>
+    where
+        A: Allocator + Default,
+        V: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        deserialize_with_limits(deserializer, Limits::implicit())
+    }
+
+    /// Deserialize a stash in the exact format, rejecting it if it exceeds the given limits.
+    pub fn deserialize_with_limits<'de, V, A, D>(
+        deserializer: D,
+        limits: Limits,
+    ) -> Result<UniqueStash<V, A>, D::Error>
+    where
+        A: Allocator + Default,
+        V: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        if deserializer.is_human_readable() {
+            // Also accept the default format.
+            deserializer.deserialize_any(ExactVisitor::new(limits, true))
+        } else {
+            deserializer.deserialize_struct("UniqueStash", FIELDS, ExactVisitor::new(limits, false))
+        }
+    }
+
+    struct FreeListSer<'a, V, A: Allocator>(&'a UniqueStash<V, A>);
+
+    impl<'a, V, A: Allocator> Serialize for FreeListSer<'a, V, A> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_seq(self.0.free_list())
+        }
+    }
+
+    struct ExactVisitor<V, A: Allocator> {
+        limits: Limits,
+        /// Whether a sequence is a stash in the default format rather than this one.
+        human_readable: bool,
+        _marker: marker::PhantomData<fn(V, A) -> V>,
+    }
+
+    impl<V, A: Allocator> ExactVisitor<V, A> {
+        fn new(limits: Limits, human_readable: bool) -> ExactVisitor<V, A> {
+            ExactVisitor {
+                limits,
+                human_readable,
+                _marker: marker::PhantomData,
+            }
+        }
+    }
+
+    impl<'de, V, A: Allocator + Default> Visitor<'de> for ExactVisitor<V, A>
+    where
+        V: Deserialize<'de>,
+    {
+        type Value = UniqueStash<V, A>;
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            write!(formatter, "a versioned unique stash")
+        }
+
+        fn visit_seq<M>(self, mut seq: M) -> Result<Self::Value, M::Error>
+        where
+            M: SeqAccess<'de>,
+        {
+            if self.human_readable {
+                // A stash in the default (dense) format.
+                return StashVisitor::new(self.limits).visit_seq(seq);
+            }
+            let version = seq
+                .next_element()?
+                .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+            let stash = seq
+                .next_element_seed(StashSeed::new(self.limits))?
+                .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+            let free_list = seq
+                .next_element()?
+                .ok_or_else(|| de::Error::invalid_length(2, &self))?;
+            finish(version, stash, free_list)
+        }
+
+        fn visit_map<M>(self, mut map: M) -> Result<Self::Value, M::Error>
+        where
+            M: MapAccess<'de>,
+        {
+            let mut version = None;
+            let mut stash = None;
+            let mut free_list = None;
+            let mut len = None;
+            let mut entries = None;
+            let mut vacant = None;
+            while let Some(key) = map.next_key()? {
+                match key {
+                    Field::Version => {
+                        if version.is_some() {
+                            return Err(de::Error::duplicate_field("version"));
+                        }
+                        version = Some(map.next_value()?);
+                    }
+                    Field::Stash => {
+                        if stash.is_some() {
+                            return Err(de::Error::duplicate_field("stash"));
+                        }
+                        stash = Some(map.next_value_seed(StashSeed::new(self.limits))?);
+                    }
+                    Field::FreeList => {
+                        if free_list.is_some() {
+                            return Err(de::Error::duplicate_field("free_list"));
+                        }
+                        free_list = Some(map.next_value()?);
+                    }
+                    Field::Len => {
+                        if len.is_some() {
+                            return Err(de::Error::duplicate_field("len"));
+                        }
+                        len = Some(map.next_value()?);
+                    }
+                    Field::Entries => {
+                        if entries.is_some() {
+                            return Err(de::Error::duplicate_field("entries"));
+                        }
+                        entries = Some(map.next_value_seed(sparse::EntriesSeed::new(self.limits))?);
+                    }
+                    Field::Vacant => {
+                        if vacant.is_some() {
+                            return Err(de::Error::duplicate_field("vacant"));
+                        }
+                        vacant = Some(map.next_value_seed(sparse::VacantSeed {
+                            limits: self.limits,
+                        })?);
+                    }
+                }
+            }
+            match version {
+                Some(version) => {
+                    if len.is_some() || entries.is_some() || vacant.is_some() {
+                        return Err(de::Error::custom(
+                            "a stash in the exact format can't have sparse format fields",
+                        ));
+                    }
+                    let stash = stash.ok_or_else(|| de::Error::missing_field("stash"))?;
+                    let free_list =
+                        free_list.ok_or_else(|| de::Error::missing_field("free_list"))?;
+                    finish(version, stash, free_list)
+                }
+                // A stash in the default (sparse) format.
+                None => {
+                    if stash.is_some() || free_list.is_some() {
+                        return Err(de::Error::missing_field("version"));
+                    }
+                    let len = len.ok_or_else(|| de::Error::missing_field("len"))?;
+                    let entries = entries.ok_or_else(|| de::Error::missing_field("entries"))?;
+                    let vacant = vacant.ok_or_else(|| de::Error::missing_field("vacant"))?;
+                    sparse::build(len, entries, vacant, self.limits)
+                }
+            }
+        }
+    }
+
+    /// Check the format version and relink the free list of the deserialized stash.
+    fn finish<V, A, E>(
+        version: u32,
+        mut stash: UniqueStash<V, A>,
+        free_list: Vec<usize>,
+    ) -> Result<UniqueStash<V, A>, E>
+    where
+        A: Allocator,
+        E: de::Error,
+    {
+        if version != FORMAT_VERSION {
+            return Err(E::invalid_value(
+                Unexpected::Unsigned(version.into()),
+                &"format version 1",
+            ));
+        }
+
+        // Slots that haven't been linked yet. Can't collide with a real link as `data.len()`
+        // can't exceed `isize::MAX`.
+        const UNLINKED: usize = usize::MAX;
+
+        let len = stash.data.len();
+        for item in &mut stash.data {
+            if let Entry::Empty(next) = &mut item.entry {
+                *next = UNLINKED;
+            }
+        }
+
+        let mut prev: Option<usize> = None;
+        for &idx in &free_list {
+            // Terminate the list here for now, we'll fix the link up if we see another slot.
+            // This also marks the slot as linked.
+            match stash.data.get_mut(idx).map(|item| &mut item.entry) {
+                Some(Entry::Empty(next)) if *next == UNLINKED => *next = len,
+                _ => {
+                    return Err(E::invalid_value(
+                        Unexpected::Unsigned(idx as u64),
+                        &"a vacant slot that isn't already in the free list",
+                    ))
+                }
+            }
+            match prev {
+                Some(prev) => stash.data[prev].entry = Entry::Empty(idx),
+                None => stash.next_free = idx,
+            }
+            prev = Some(idx);
+        }
+        if free_list.len() != len - stash.size {
+            return Err(E::invalid_length(free_list.len(), &"every vacant slot"));
+        }
+        if prev.is_none() {
+            stash.next_free = len;
+        }
+        Ok(stash)
+    }
+}
+
+/// Serialize a `UniqueStash` as a map of tags to values.
+///
+/// The default format records every slot, vacant or not, as a sequence. This format instead
+/// records the number of slots, a map of tags (in their `###/###` string form) to values, and the
+/// tags that will be assigned to the vacant slots that have been used before when they're next
+/// reused. Use it with `#[serde(with = "stash::unique_stash::sparse")]`. The default format's
+/// deserializer also accepts it from human readable deserializers (e.g., JSON).
+///
+/// Like the default format, this format does not preserve the order in which vacant slots will be
+/// reused. Slots that appear in neither the entries nor the vacant tags are assumed to be vacant
+/// and unused.
+pub mod sparse {
+    use super::*;
+
+    const FIELDS: &[&str] = &["len", "entries", "vacant"];
+
+    #[derive(Deserialize)]
+    #[serde(field_identifier, rename_all = "lowercase")]
+    enum Field {
+        Len,
+        Entries,
+        Vacant,
+    }
+
+    /// Serialize a stash in the sparse format.
+    pub fn serialize<V, A, S>(stash: &UniqueStash<V, A>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        A: Allocator,
+        V: Serialize,
+        S: Serializer,
+    {
+        let mut state = serializer.serialize_struct("UniqueStash", 3)?;
+        state.serialize_field("len", &stash.data.len())?;
+        state.serialize_field("entries", &EntriesSer(stash))?;
+        state.serialize_field("vacant", &VacantSer(stash))?;
+        state.end()
+    }
+
+    /// Deserialize a stash in the sparse format.
+    pub fn deserialize<'de, V, A, D>(deserializer: D) -> Result<UniqueStash<V, A>, D::Error>
+    where
+        A: Allocator + Default,
+        V: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        deserialize_with_limits(deserializer, Limits::implicit())
+    }
+
+    /// Deserialize a stash in the sparse format, rejecting it if it exceeds the given limits.
+    pub fn deserialize_with_limits<'de, V, A, D>(
+        deserializer: D,
+        limits: Limits,
+    ) -> Result<UniqueStash<V, A>, D::Error>
+    where
+        A: Allocator + Default,
+        V: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_struct("UniqueStash", FIELDS, SparseVisitor::new(limits))
+    }
+
+    pub(super) fn visit_map<'de, V, A, M>(
+        mut map: M,
+        limits: Limits,
+    ) -> Result<UniqueStash<V, A>, M::Error>
+    where
+        A: Allocator + Default,
+        V: Deserialize<'de>,
+        M: MapAccess<'de>,
+    {
+        let mut len = None;
+        let mut entries = None;
+        let mut vacant = None;
+        while let Some(key) = map.next_key()? {
+            match key {
+                Field::Len => {
+                    if len.is_some() {
+                        return Err(de::Error::duplicate_field("len"));
+                    }
+                    len = Some(map.next_value()?);
+                }
+                Field::Entries => {
+                    if entries.is_some() {
+                        return Err(de::Error::duplicate_field("entries"));
+                    }
+                    entries = Some(map.next_value_seed(EntriesSeed::new(limits))?);
+                }
+                Field::Vacant => {
+                    if vacant.is_some() {
+                        return Err(de::Error::duplicate_field("vacant"));
+                    }
+                    vacant = Some(map.next_value_seed(VacantSeed { limits })?);
+                }
+            }
+        }
+        let len = len.ok_or_else(|| de::Error::missing_field("len"))?;
+        let entries = entries.ok_or_else(|| de::Error::missing_field("entries"))?;
+        let vacant = vacant.ok_or_else(|| de::Error::missing_field("vacant"))?;
+        build(len, entries, vacant, limits)
+    }
+
+    pub(super) fn build<V, A, E>(
+        len: usize,
+        entries: Entries<V>,
+        vacant: Vec<Tag>,
+        limits: Limits,
+    ) -> Result<UniqueStash<V, A>, E>
+    where
+        A: Allocator + Default,
+        E: de::Error,
+    {
+        let mut builder = Builder::new(limits, Some(len));
+        // Check this up-front as the length isn't bounded by the size of the input.
+        builder.check_slots(len)?;
+        if len > limits.max_sparse_slots {
+            return Err(E::custom(format_args!(
+                "sparse stash has more than {} slots (pass explicit limits to allow more)",
+                limits.max_sparse_slots
+            )));
+        }
+        let mut slots: Vec<(Tag, Option<V>)> = entries
+            .0
+            .into_iter()
+            .map(|(tag, v)| (tag, Some(v)))
+            .chain(vacant.into_iter().map(|tag| (tag, None)))
+            .collect();
+        slots.sort_unstable_by_key(|&(tag, _)| tag.idx);
+        for (tag, option) in slots {
+            if tag.idx >= len {
+                return Err(E::invalid_value(
+                    Unexpected::Unsigned(tag.idx as u64),
+                    &"an index less than the number of slots",
+                ));
+            }
+            if tag.idx < builder.len() {
+                return Err(E::custom(format_args!("duplicate index {}", tag.idx)));
+            }
+            while builder.len() < tag.idx {
+                builder.push(0, None)?;
+            }
+            builder.push(tag.ver, option)?;
+        }
+        while builder.len() < len {
+            builder.push(0, None)?;
+        }
+        Ok(builder.finish())
+    }
+
+    /// A tag in its string form.
+    struct TagString(Tag);
+
+    impl Serialize for TagString {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_str(&self.0)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for TagString {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            deserializer.deserialize_str(TagStringVisitor)
+        }
+    }
+
+    struct TagStringVisitor;
+
+    impl<'de> Visitor<'de> for TagStringVisitor {
+        type Value = TagString;
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            write!(formatter, "a tag of the form ###/###")
+        }
+
+        fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+            v.parse()
+                .map(TagString)
+                .map_err(|_| E::invalid_value(Unexpected::Str(v), &self))
+        }
+    }
+
+    struct EntriesSer<'a, V, A: Allocator>(&'a UniqueStash<V, A>);
+
+    impl<'a, V, A: Allocator> Serialize for EntriesSer<'a, V, A>
+    where
+        V: Serialize,
+    {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let mut map = serializer.serialize_map(Some(self.0.len()))?;
+            for (tag, v) in self.0 {
+                map.serialize_entry(&TagString(tag), v)?;
+            }
+            map.end()
+        }
+    }
+
+    /// The tags of the vacant slots that have been used before. Unused slots (with version 0) are
+    /// left out.
+    struct VacantSer<'a, V, A: Allocator>(&'a UniqueStash<V, A>);
+
+    impl<'a, V, A: Allocator> VacantSer<'a, V, A> {
+        fn tags(&self) -> impl Iterator<Item = Tag> + 'a {
+            self.0
+                .data
+                .iter()
+                .enumerate()
+                .filter_map(|(idx, ve)| match ve.entry {
+                    Entry::Empty(_) if ve.version != 0 => Some(Tag {
+                        idx,
+                        ver: ve.version,
+                    }),
+                    _ => None,
+                })
+        }
+    }
+
+    impl<'a, V, A: Allocator> Serialize for VacantSer<'a, V, A> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let mut seq = serializer.serialize_seq(Some(self.tags().count()))?;
+            for tag in self.tags() {
+                seq.serialize_element(&TagString(tag))?;
+            }
+            seq.end()
+        }
+    }
+
+    /// Reads the tags of the vacant slots, enforcing `max_slots` as they're read.
+    pub(super) struct VacantSeed {
+        pub(super) limits: Limits,
+    }
+
+    impl<'de> DeserializeSeed<'de> for VacantSeed {
+        type Value = Vec<Tag>;
+
+        fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            deserializer.deserialize_seq(self)
+        }
+    }
+
+    impl<'de> Visitor<'de> for VacantSeed {
+        type Value = Vec<Tag>;
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            write!(formatter, "a sequence of tags")
+        }
+
+        fn visit_seq<M>(self, mut seq: M) -> Result<Self::Value, M::Error>
+        where
+            M: SeqAccess<'de>,
+        {
+            let mut vacant =
+                Vec::with_capacity(self.limits.initial_capacity::<Tag>(seq.size_hint()));
+            while let Some(TagString(tag)) = seq.next_element()? {
+                if vacant.len() >= self.limits.max_slots {
+                    return Err(de::Error::custom(format_args!(
+                        "stash has more than {} slots",
+                        self.limits.max_slots
+                    )));
+                }
+                vacant.push(tag);
+            }
+            Ok(vacant)
+        }
+    }
+
+    /// The occupied slots, in no particular order.
+    pub(super) struct Entries<V>(Vec<(Tag, V)>);
+
+    /// Reads the occupied slots, enforcing `max_len` as they're read.
+    pub(super) struct EntriesSeed<V> {
+        limits: Limits,
+        _marker: marker::PhantomData<fn(V) -> V>,
+    }
+
+    impl<V> EntriesSeed<V> {
+        pub(super) fn new(limits: Limits) -> EntriesSeed<V> {
+            EntriesSeed {
+                limits,
+                _marker: marker::PhantomData,
+            }
+        }
+    }
+
+    impl<'de, V> DeserializeSeed<'de> for EntriesSeed<V>
+    where
+        V: Deserialize<'de>,
+    {
+        type Value = Entries<V>;
+
+        fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            deserializer.deserialize_map(self)
+        }
+    }
+
+    impl<'de, V> Visitor<'de> for EntriesSeed<V>
+    where
+        V: Deserialize<'de>,
+    {
+        type Value = Entries<V>;
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            write!(formatter, "a map of tags to values")
+        }
+
+        fn visit_map<M>(self, mut map: M) -> Result<Self::Value, M::Error>
+        where
+            M: MapAccess<'de>,
+        {
+            let mut entries =
+                Vec::with_capacity(self.limits.initial_capacity::<(Tag, V)>(map.size_hint()));
+            while let Some((TagString(tag), v)) = map.next_entry()? {
+                if entries.len() >= self.limits.max_len {
+                    return Err(de::Error::custom(format_args!(
+                        "stash has more than {} entries",
+                        self.limits.max_len
+                    )));
+                }
+                entries.push((tag, v));
+            }
+            Ok(Entries(entries))
+        }
+    }
+
+    struct SparseVisitor<V, A: Allocator> {
+        limits: Limits,
+        _marker: marker::PhantomData<fn(V, A) -> V>,
+    }
+
+    impl<V, A: Allocator> SparseVisitor<V, A> {
+        fn new(limits: Limits) -> SparseVisitor<V, A> {
+            SparseVisitor {
+                limits,
+                _marker: marker::PhantomData,
+            }
+        }
+    }
+
+    impl<'de, V, A: Allocator + Default> Visitor<'de> for SparseVisitor<V, A>
+    where
+        V: Deserialize<'de>,
+    {
+        type Value = UniqueStash<V, A>;
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            write!(formatter, "a sparse unique stash")
+        }
+
+        fn visit_seq<M>(self, mut seq: M) -> Result<Self::Value, M::Error>
+        where
+            M: SeqAccess<'de>,
+        {
+            let len = seq
+                .next_element()?
+                .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+            let entries = seq
+                .next_element_seed(EntriesSeed::new(self.limits))?
+                .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+            let vacant = seq
+                .next_element_seed(VacantSeed {
+                    limits: self.limits,
+                })?
+                .ok_or_else(|| de::Error::invalid_length(2, &self))?;
+            build(len, entries, vacant, self.limits)
+        }
+
+        fn visit_map<M>(self, map: M) -> Result<Self::Value, M::Error>
+        where
+            M: MapAccess<'de>,
+        {
+            visit_map(map, self.limits)
+        }
+    }
+}
@@ -0,0 +1,102 @@
+#![cfg(feature = "allocator-api2")]
+#![cfg_attr(feature = "nightly", feature(allocator_api))]
+extern crate allocator_api2;
+extern crate stash;
+use allocator_api2::alloc::{AllocError, Allocator, Global, Layout};
+use stash::{Stash, UniqueStash};
+
+use std::cell::Cell;
+use std::ptr::NonNull;
+
+/// An allocator that keeps track of how many bytes it has handed out.
+#[derive(Default)]
+struct Counting {
+    live: Cell<usize>,
+}
+
+unsafe impl Allocator for Counting {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        let ptr = Global.allocate(layout)?;
+        self.live.set(self.live.get() + layout.size());
+        Ok(ptr)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        self.live.set(self.live.get() - layout.size());
+        Global.deallocate(ptr, layout)
+    }
+}
+
+#[test]
+fn stash_new_in() {
+    let alloc = Counting::default();
+    {
+        let mut stash = Stash::new_in(&alloc);
+        assert_eq!(alloc.live.get(), 0);
+        let keys: Vec<usize> = stash.extend(0..100).collect();
+        assert!(alloc.live.get() > 0);
+        assert_eq!(stash.take(keys[3]), Some(3));
+        assert_eq!(stash.put(300), keys[3]);
+        assert_eq!(stash[keys[3]], 300);
+        assert_eq!(stash.iter().count(), 100);
+
+        let copy = stash.clone();
+        assert_eq!(copy, stash);
+        assert!(copy.into_values().eq(stash.values().copied()));
+    }
+    assert_eq!(alloc.live.get(), 0);
+
+    let stash: Stash<&str, usize, _> = Stash::with_capacity_in(10, &alloc);
+    assert!(stash.capacity() >= 10);
+    assert!(alloc.live.get() > 0);
+    assert!(std::ptr::eq(*stash.allocator(), &alloc));
+}
+
+#[test]
+fn unique_stash_new_in() {
+    let alloc = Counting::default();
+    {
+        let mut stash = UniqueStash::new_in(&alloc);
+        let tags: Vec<_> = stash.extend(0..100).collect();
+        assert!(alloc.live.get() > 0);
+        assert_eq!(stash.take(tags[3]), Some(3));
+        let tag = stash.put(300);
+        assert_ne!(tag, tags[3]);
+        assert_eq!(stash.get(tags[3]), None);
+
+        let mut cursor = stash.cursor_mut(tag.index());
+        assert_eq!(cursor.remove_current(), Some(300));
+        assert!(stash
+            .into_iter()
+            .map(|(_, v)| v)
+            .eq((0..100).filter(|&v| v != 3)));
+    }
+    assert_eq!(alloc.live.get(), 0);
+
+    let stash: UniqueStash<&str, _> = UniqueStash::with_capacity_in(10, &alloc);
+    assert!(stash.capacity() >= 10);
+    assert!(alloc.live.get() > 0);
+}
+
+#[test]
+fn keeps_allocator() {
+    let alloc = Counting::default();
+    let mut stash = Stash::new_in(&alloc);
+    let a: usize = stash.put(1);
+    stash.put(2);
+    let before = stash.clone();
+    let result: Result<(), ()> = stash.transaction(|tx| {
+        tx.take(a);
+        Ok(())
+    });
+    assert!(result.is_ok());
+    let mut replica = before.clone();
+    replica.apply_patch(before.diff(&stash)).unwrap();
+    assert_eq!(replica, stash);
+
+    let unique = UniqueStash::from(stash);
+    assert!(std::ptr::eq(*unique.allocator(), &alloc));
+    let (stash, versions): (Stash<_, usize, _>, _) = unique.into_stash();
+    assert_eq!(versions, [0, 0]);
+    assert!(std::ptr::eq(*stash.allocator(), &alloc));
+}
@@ -0,0 +1,115 @@
+extern crate stash;
+use stash::{ArrayStash, ArrayUniqueStash, LookupError, Stash, UniqueStash};
+
+use std::sync::Mutex;
+
+#[test]
+fn same_keys_as_stash() {
+    let mut array: ArrayStash<i32, 8> = ArrayStash::new();
+    let mut stash = Stash::new();
+    let mut keys = Vec::new();
+    for i in 0..6 {
+        let key = array.put(i).unwrap();
+        assert_eq!(key, stash.put(i));
+        keys.push(key);
+    }
+    for &key in &[keys[4], keys[1], keys[2]] {
+        assert_eq!(array.take(key), stash.take(key));
+    }
+    for i in 6..10 {
+        assert_eq!(array.next_index(), Some(stash.next_index()));
+        assert_eq!(array.put(i).unwrap(), stash.put(i));
+    }
+    assert_eq!(array.len(), stash.len());
+    assert!(array.iter().eq(stash.iter()));
+
+    array.clear();
+    stash.clear();
+    assert!(array.is_empty());
+    assert_eq!(array.put(0).unwrap(), stash.put(0));
+}
+
+#[test]
+fn full() {
+    let mut stash: ArrayStash<&str, 2> = ArrayStash::new();
+    assert_eq!(stash.capacity(), 2);
+    let a = stash.put("a").unwrap();
+    let b = stash.put("b").unwrap();
+    assert!(stash.is_full());
+    assert_eq!(stash.next_index(), None);
+    assert_eq!(stash.put("c"), Err("c"));
+    assert_eq!(stash.len(), 2);
+
+    assert_eq!(stash.take(a), Some("a"));
+    assert_eq!(stash.put("c"), Ok(a));
+    assert_eq!(stash[a], "c");
+    assert_eq!(stash[b], "b");
+    assert_eq!(stash.try_get(2), Err(LookupError::OutOfBounds));
+
+    let mut zero: ArrayStash<&str, 0> = ArrayStash::new();
+    assert_eq!(zero.put("a"), Err("a"));
+}
+
+#[test]
+fn iter() {
+    let mut stash: ArrayStash<i32, 4> = ArrayStash::new();
+    let a = stash.put(1).unwrap();
+    let b = stash.put(2).unwrap();
+    let c = stash.put(3).unwrap();
+    stash.take(b);
+    for value in stash.values_mut() {
+        *value *= 10;
+    }
+    assert_eq!(stash.values().copied().collect::<Vec<_>>(), [10, 30]);
+    assert_eq!(stash.iter().len(), 2);
+    let mut iter = stash.clone().into_iter();
+    assert_eq!(iter.next_back(), Some((c, 30)));
+    assert_eq!(iter.next(), Some((a, 10)));
+    assert_eq!(iter.next(), None);
+    assert_eq!(format!("{:?}", stash), "{0: 10, 2: 30}");
+}
+
+static HANDLES: Mutex<ArrayStash<&str, 4>> = Mutex::new(ArrayStash::new());
+static TAGS: Mutex<ArrayUniqueStash<&str, 4>> = Mutex::new(ArrayUniqueStash::new());
+
+#[test]
+fn statics() {
+    let key = HANDLES.lock().unwrap().put("a").unwrap();
+    assert_eq!(HANDLES.lock().unwrap().take(key), Some("a"));
+
+    let tag = TAGS.lock().unwrap().put("a").unwrap();
+    assert_eq!(TAGS.lock().unwrap().take(tag), Some("a"));
+}
+
+#[test]
+fn unique() {
+    let mut array: ArrayUniqueStash<i32, 2> = ArrayUniqueStash::new();
+    let mut stash = UniqueStash::new();
+    let a = array.put(1).unwrap();
+    assert_eq!(a, stash.put(1));
+    let b = array.put(2).unwrap();
+    assert_eq!(b, stash.put(2));
+    assert_eq!(array.put(3), Err(3));
+
+    assert_eq!(array.take(a), Some(1));
+    assert_eq!(
+        array.try_take(a),
+        Err(LookupError::Stale { current_version: 1 })
+    );
+    stash.take(a);
+    let c = array.put(3).unwrap();
+    assert_eq!(c, stash.put(3));
+    assert_ne!(a, c);
+    assert_eq!(array.get(a), None);
+    assert_eq!(array[c], 3);
+
+    array.clear();
+    assert!(array.is_empty());
+    assert_eq!(
+        array.try_get(b),
+        Err(LookupError::Stale { current_version: 1 })
+    );
+    let d = array.put(4).unwrap();
+    assert!(d != b && d != c);
+    assert_eq!(array.into_iter().collect::<Vec<_>>(), [(d, 4)]);
+}
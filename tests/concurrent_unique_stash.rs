@@ -0,0 +1,132 @@
+extern crate stash;
+use stash::{ConcurrentUniqueStash, Tag};
+
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn put_get_take() {
+    let stash = ConcurrentUniqueStash::new();
+    assert!(stash.is_empty());
+    let a = stash.put("a");
+    let b = stash.put("b");
+    assert_eq!(stash.len(), 2);
+    assert_eq!(*stash.get(a).unwrap(), "a");
+    assert_eq!(stash.get(b).unwrap().tag(), b);
+
+    assert_eq!(stash.take(a), Some("a"));
+    assert_eq!(stash.take(a), None);
+    assert!(stash.get(a).is_none());
+    assert!(!stash.contains(a));
+
+    // The slot is reused with a new tag.
+    let c = stash.put("c");
+    assert_ne!(a, c);
+    assert!(stash.get(a).is_none());
+    assert_eq!(*stash.get(c).unwrap(), "c");
+    assert_eq!(stash.len(), 2);
+
+    let mut stash = stash;
+    *stash.get_mut(c).unwrap() = "d";
+    assert_eq!(*stash.get(c).unwrap(), "d");
+}
+
+#[test]
+fn grows() {
+    let stash = ConcurrentUniqueStash::new();
+    let tags: Vec<Tag> = (0..10_000).map(|i| stash.put(i)).collect();
+    for (i, &tag) in tags.iter().enumerate() {
+        assert_eq!(*stash.get(tag).unwrap(), i);
+    }
+    assert_eq!(stash.len(), 10_000);
+}
+
+struct Counted<'a>(&'a AtomicUsize);
+
+impl<'a> Drop for Counted<'a> {
+    fn drop(&mut self) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn remove_with_guard() {
+    let drops = AtomicUsize::new(0);
+    let stash = ConcurrentUniqueStash::new();
+    let tag = stash.put(Counted(&drops));
+    let guard = stash.get(tag).unwrap();
+    assert!(stash.remove(tag));
+    assert!(!stash.remove(tag));
+    assert!(stash.get(tag).is_none());
+    assert_eq!(stash.len(), 0);
+    // Still alive.
+    assert_eq!(drops.load(Ordering::SeqCst), 0);
+    drop(guard);
+    assert_eq!(drops.load(Ordering::SeqCst), 1);
+
+    // Values left in the stash are dropped with it.
+    stash.put(Counted(&drops));
+    stash.put(Counted(&drops));
+    drop(stash);
+    assert_eq!(drops.load(Ordering::SeqCst), 3);
+}
+
+#[test]
+fn take_waits_for_guards() {
+    let stash = ConcurrentUniqueStash::new();
+    let tag = stash.put(String::from("value"));
+    thread::scope(|s| {
+        let guard = stash.get(tag).unwrap();
+        let taker = s.spawn(|| stash.take(tag));
+        thread::sleep(Duration::from_millis(10));
+        assert!(stash.get(tag).is_none());
+        assert_eq!(*guard, "value");
+        drop(guard);
+        assert_eq!(taker.join().unwrap().as_deref(), Some("value"));
+    });
+}
+
+#[test]
+fn threads() {
+    const THREADS: usize = 8;
+    const OPS: usize = 10_000;
+
+    let stash = ConcurrentUniqueStash::new();
+    let tags: Vec<Vec<Tag>> = thread::scope(|s| {
+        let workers: Vec<_> = (0..THREADS)
+            .map(|t| {
+                let stash = &stash;
+                s.spawn(move || {
+                    let mut tags = Vec::new();
+                    let mut live = Vec::new();
+                    for i in 0..OPS {
+                        let tag = stash.put((t, i));
+                        tags.push(tag);
+                        live.push(tag);
+                        if i % 3 != 0 {
+                            let tag = live.swap_remove(i % live.len());
+                            assert_eq!(stash.get(tag).map(|g| g.0), Some(t));
+                            if i % 2 == 0 {
+                                assert!(stash.take(tag).is_some());
+                            } else {
+                                assert!(stash.remove(tag));
+                            }
+                        }
+                    }
+                    for tag in live {
+                        assert_eq!(stash.take(tag).map(|v| v.0), Some(t));
+                    }
+                    tags
+                })
+            })
+            .collect();
+        workers.into_iter().map(|w| w.join().unwrap()).collect()
+    });
+    assert!(stash.is_empty());
+
+    // Tags are never reused.
+    let unique: HashSet<Tag> = tags.iter().flatten().copied().collect();
+    assert_eq!(unique.len(), THREADS * OPS);
+}
@@ -0,0 +1,105 @@
+#![cfg(feature = "std")]
+extern crate stash;
+use stash::{Interner, Stash};
+
+use std::hash::{BuildHasher, Hasher};
+
+#[test]
+fn intern_lookup_resolve() {
+    let mut interner = Interner::new();
+    let a = interner.intern(String::from("a"));
+    let b = interner.intern(String::from("b"));
+    assert_ne!(a, b);
+    assert_eq!(interner.intern(String::from("a")), a);
+    assert_eq!(interner.len(), 2);
+    assert_eq!(interner.ref_count(a), Some(2));
+    assert_eq!(interner.ref_count(b), Some(1));
+
+    assert_eq!(interner.lookup("a"), Some(a));
+    assert_eq!(interner.lookup("c"), None);
+    assert_eq!(interner.resolve(b).map(String::as_str), Some("b"));
+    assert_eq!(interner[a], "a");
+    assert_eq!(interner.resolve(5), None);
+    assert!(interner.contains_key(a));
+
+    let mut values: Vec<_> = interner.iter().map(|(k, v)| (k, v.as_str())).collect();
+    values.sort();
+    assert_eq!(values, [(a, "a"), (b, "b")]);
+}
+
+#[test]
+fn release() {
+    let mut interner = Interner::new();
+    let a = interner.intern("a");
+    interner.intern("a");
+    let b = interner.intern("b");
+
+    assert_eq!(interner.release(a), None);
+    assert_eq!(interner.release(a), Some("a"));
+    assert_eq!(interner.release(a), None);
+    assert_eq!(interner.lookup("a"), None);
+    assert!(!interner.contains_key(a));
+    assert_eq!(interner.len(), 1);
+
+    interner.intern("b");
+    assert_eq!(interner.remove(b), Some("b"));
+    assert_eq!(interner.remove(b), None);
+    assert!(interner.is_empty());
+}
+
+#[test]
+fn same_keys_as_stash() {
+    let mut interner = Interner::new();
+    let mut stash = Stash::new();
+    for word in "the quick brown fox jumps over the lazy dog".split(' ') {
+        if interner.lookup(word).is_none() {
+            assert_eq!(interner.intern(word), stash.put(word));
+        } else {
+            interner.intern(word);
+        }
+    }
+    let fox = interner.lookup("fox").unwrap();
+    interner.release(fox);
+    stash.take(fox);
+    let cat = interner.intern("cat");
+    assert_eq!(cat, stash.put("cat"));
+    assert_eq!(cat, fox);
+    assert_eq!(interner.intern("cat"), cat);
+}
+
+/// A hasher that sends every value to the same bucket.
+#[derive(Default, Clone)]
+struct Collide;
+
+impl BuildHasher for Collide {
+    type Hasher = Collide;
+    fn build_hasher(&self) -> Collide {
+        Collide
+    }
+}
+
+impl Hasher for Collide {
+    fn finish(&self) -> u64 {
+        0
+    }
+    fn write(&mut self, _: &[u8]) {}
+}
+
+#[test]
+fn collisions() {
+    let mut interner: Interner<u32, usize, Collide> = Interner::default();
+    let keys: Vec<usize> = (0..10).map(|i| interner.intern(i)).collect();
+    for (i, &key) in keys.iter().enumerate() {
+        assert_eq!(interner.lookup(&(i as u32)), Some(key));
+    }
+    // Unlink from the head, the middle, and the tail of the chain.
+    for &i in &[9, 4, 0] {
+        assert_eq!(interner.release(keys[i]), Some(i as u32));
+        assert_eq!(interner.lookup(&(i as u32)), None);
+    }
+    for i in (1..9).filter(|&i| i != 4) {
+        assert_eq!(interner.lookup(&i), Some(keys[i as usize]));
+    }
+    assert_eq!(interner.intern(4), keys[0]);
+    assert_eq!(interner.lookup(&4), Some(keys[0]));
+}
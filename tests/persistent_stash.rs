@@ -0,0 +1,95 @@
+extern crate stash;
+use stash::{PersistentStash, Stash};
+
+#[test]
+fn same_keys_as_stash() {
+    let mut stash = Stash::new();
+    let mut persistent = PersistentStash::new();
+    let mut keys = Vec::new();
+    // A simple LCG so the sequence of puts and takes is deterministic.
+    let mut state = 1u32;
+    for i in 0..1000 {
+        state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+        if keys.is_empty() || state % 3 < 2 {
+            let key = stash.put(i);
+            assert_eq!(persistent.put(i), key);
+            keys.push(key);
+        } else {
+            let key = keys.swap_remove(state as usize % keys.len());
+            assert_eq!(persistent.take(key), stash.take(key));
+        }
+        assert_eq!(persistent.len(), stash.len());
+        assert_eq!(persistent.next_index(), stash.next_index());
+    }
+    assert!(persistent.iter().eq(stash.iter()));
+    assert!(persistent.iter().rev().eq(stash.iter().rev()));
+    assert!(persistent.values().eq(stash.values()));
+    assert_eq!(persistent.iter().len(), stash.len());
+}
+
+#[test]
+fn snapshot() {
+    let mut stash = PersistentStash::new();
+    let keys: Vec<usize> = (0..200).map(|i| stash.put(i)).collect();
+    let snapshot = stash.snapshot();
+
+    stash[keys[3]] = 1000;
+    assert_eq!(stash.take(keys[150]), Some(150));
+    assert_eq!(stash.put(2000), keys[150]);
+    stash.put(3000);
+
+    assert_eq!(snapshot.len(), 200);
+    assert_eq!(snapshot[keys[3]], 3);
+    assert_eq!(snapshot[keys[150]], 150);
+    assert!(snapshot.values().cloned().eq(0..200));
+
+    assert_eq!(stash.len(), 201);
+    assert_eq!(stash[keys[3]], 1000);
+    assert_eq!(stash[keys[150]], 2000);
+
+    let mut fork = snapshot.clone();
+    fork.clear();
+    assert!(fork.is_empty());
+    assert_eq!(fork.put(1), 0);
+    assert_eq!(snapshot.len(), 200);
+}
+
+#[test]
+fn from_stash() {
+    let mut stash = Stash::new();
+    let keys: Vec<usize> = stash.extend(0..100).collect();
+    for &k in &[keys[70], keys[3], keys[64]] {
+        stash.take(k);
+    }
+    let mut persistent = PersistentStash::from(stash.clone());
+    assert!(persistent.iter().eq(stash.iter()));
+    for i in 0..5 {
+        assert_eq!(persistent.put(i), stash.put(i));
+    }
+    assert_eq!(persistent.get(1000), None);
+    assert_eq!(persistent.take(1000), None);
+}
+
+#[test]
+fn deep_tree() {
+    // Enough slots for several levels of branches.
+    let mut stash = Stash::new();
+    let mut persistent = PersistentStash::new();
+    for i in 0..10_000 {
+        assert_eq!(persistent.put(i), stash.put(i));
+    }
+    let snapshot = persistent.snapshot();
+    for key in (0..10_000).step_by(7) {
+        assert_eq!(persistent.take(key), stash.take(key));
+    }
+    for i in 0..5000 {
+        assert_eq!(persistent.put(i), stash.put(i));
+    }
+    assert!(persistent.iter().eq(stash.iter()));
+    assert!(snapshot.values().cloned().eq(0..10_000));
+
+    let converted = PersistentStash::from(stash.clone());
+    assert!(converted.iter().eq(stash.iter()));
+    assert!(converted.iter().rev().eq(stash.iter().rev()));
+    assert_eq!(converted.next_index(), stash.next_index());
+}
@@ -0,0 +1,67 @@
+extern crate stash;
+use stash::{RcStash, Stash};
+
+#[test]
+fn retain_release() {
+    let mut stash = RcStash::new();
+    let a = stash.put("a");
+    let b = stash.put("b");
+    assert_eq!(stash.ref_count(a), Some(1));
+    assert!(stash.retain(a));
+    assert!(stash.retain(a));
+    assert_eq!(stash.ref_count(a), Some(3));
+
+    assert_eq!(stash.release(a), None);
+    assert_eq!(stash.release(a), None);
+    assert_eq!(stash.get(a), Some(&"a"));
+    assert_eq!(stash.release(a), Some("a"));
+    assert!(!stash.contains_key(a));
+    assert_eq!(stash.ref_count(a), None);
+    assert_eq!(stash.len(), 1);
+
+    // The slot is back on the free list.
+    assert_eq!(stash.put("c"), a);
+    stash.retain(b);
+    assert_eq!(stash.take(b), Some("b"));
+    assert_eq!(stash.values().copied().collect::<Vec<_>>(), ["c"]);
+    assert_eq!(format!("{:?}", stash), r#"{0: "c"}"#);
+}
+
+#[test]
+fn same_keys_as_stash() {
+    let mut rc = RcStash::new();
+    let mut stash = Stash::new();
+    let keys: Vec<usize> = (0..8).map(|i| rc.put(i)).collect();
+    stash.extend(0..8).count();
+    for &key in &keys[2..5] {
+        rc.retain(key);
+        assert_eq!(rc.release(key), None);
+        assert_eq!(rc.release(key), stash.take(key));
+    }
+    for i in 8..12 {
+        assert_eq!(rc.put(i), stash.put(i));
+    }
+    assert!(rc.iter().eq(stash.iter()));
+}
+
+#[test]
+#[cfg_attr(
+    debug_assertions,
+    should_panic(expected = "released a vacant RcStash key")
+)]
+fn release_vacant() {
+    let mut stash = RcStash::new();
+    let a = stash.put(1);
+    assert_eq!(stash.release(a), Some(1));
+    assert_eq!(stash.release(a), None);
+}
+
+#[test]
+#[cfg_attr(
+    debug_assertions,
+    should_panic(expected = "retained a vacant RcStash key")
+)]
+fn retain_vacant() {
+    let mut stash: RcStash<i32> = RcStash::new();
+    assert!(!stash.retain(0));
+}
@@ -0,0 +1,117 @@
+#![cfg(feature = "std")]
+extern crate stash;
+use stash::{Index, ShardedStash};
+
+use std::collections::HashSet;
+use std::thread;
+
+#[test]
+fn put_get_take() {
+    let mut stash = ShardedStash::with_shards(3);
+    assert_eq!(stash.shards(), 4);
+    assert!(stash.is_empty());
+
+    let a = stash.put("a");
+    let b = stash.put("b");
+    assert_ne!(a, b);
+    assert_eq!(stash.len(), 2);
+    assert_eq!(*stash.get(a).unwrap(), "a");
+    *stash.get(b).unwrap() = "c";
+    assert_eq!(*stash.get(b).unwrap(), "c");
+    *stash.get_mut(b).unwrap() = "d";
+
+    assert_eq!(stash.take(a), Some("a"));
+    assert_eq!(stash.take(a), None);
+    assert!(!stash.contains_key(a));
+    assert!(stash.contains_key(b));
+    // Keys are reused within a shard.
+    assert_eq!(stash.put("e"), a);
+
+    assert!(stash.get(usize::MAX).is_none());
+    assert_eq!(stash.take(usize::MAX), None);
+}
+
+/// An index type that only has 16 bits.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+struct Small(u16);
+
+impl Index for Small {
+    const BITS: u32 = 16;
+
+    fn from_usize(idx: usize) -> Self {
+        Small(idx as u16)
+    }
+
+    fn into_usize(self) -> usize {
+        self.0 as usize
+    }
+}
+
+#[test]
+fn shard_bits() {
+    let stash: ShardedStash<i32, Small> = ShardedStash::with_shards(4);
+    let key = stash.put(1);
+    // The shard is in the top two bits, the index within the shard in the others.
+    assert_eq!(key.0 & 0x3fff, 0);
+    assert_eq!(stash.put(2).0, key.0 + 1);
+    assert_eq!(stash.take(key), Some(1));
+}
+
+/// Restricts its range with `try_from_usize` but not `BITS`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Unbounded(u16);
+
+impl Index for Unbounded {
+    fn from_usize(idx: usize) -> Self {
+        Self::try_from_usize(idx).expect("index type overflowing!")
+    }
+
+    fn try_from_usize(idx: usize) -> Option<Self> {
+        if idx > u16::MAX as usize {
+            return None;
+        }
+        Some(Unbounded(idx as u16))
+    }
+
+    fn into_usize(self) -> usize {
+        self.0 as usize
+    }
+}
+
+#[test]
+#[should_panic(expected = "can't represent")]
+fn unrepresentable_bits() {
+    let _: ShardedStash<i32, Unbounded> = ShardedStash::with_shards(4);
+}
+
+#[test]
+fn threads() {
+    let stash = ShardedStash::with_shards(4);
+    let keys: Vec<Vec<usize>> = thread::scope(|s| {
+        let workers: Vec<_> = (0..8)
+            .map(|t| {
+                let stash = &stash;
+                s.spawn(move || {
+                    let keys: Vec<usize> = (0..1000).map(|i| stash.put((t, i))).collect();
+                    for &key in keys.iter().step_by(2) {
+                        assert_eq!(stash.take(key).map(|v| v.0), Some(t));
+                    }
+                    keys
+                })
+            })
+            .collect();
+        workers.into_iter().map(|w| w.join().unwrap()).collect()
+    });
+    assert_eq!(stash.len(), 8 * 500);
+    let live: HashSet<usize> = keys
+        .iter()
+        .flat_map(|keys| keys.iter().skip(1).step_by(2))
+        .copied()
+        .collect();
+    assert_eq!(live.len(), 8 * 500);
+    for (t, keys) in keys.iter().enumerate() {
+        for &key in keys.iter().skip(1).step_by(2) {
+            assert_eq!(stash.get(key).unwrap().0, t);
+        }
+    }
+}
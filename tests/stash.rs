@@ -1,6 +1,14 @@
 extern crate bincode;
+#[cfg(feature = "rayon")]
+extern crate rayon;
+#[cfg(feature = "serialization")]
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
 extern crate stash;
-use stash::Stash;
+use stash::diff::{Change, PatchError};
+use stash::stash::journal::{JournaledStash, Op};
+use stash::{LookupError, Stash};
 
 #[test]
 fn iter() {
@@ -41,6 +49,404 @@ fn get() {
     assert_eq!(stash[indices[2]], 1);
 }
 
+#[test]
+fn contains_key() {
+    let mut stash = Stash::new();
+    assert!(!stash.contains_key(stash.next_index()));
+    let a = stash.put(1);
+    assert!(stash.contains_key(a));
+    assert!(!stash.contains_key(a + 1));
+    stash.take(a);
+    assert!(!stash.contains_key(a));
+}
+
+#[test]
+fn from_iter_and_extend() {
+    fn fill<C: Extend<i32>>(c: &mut C) {
+        c.extend(vec![3, 4]);
+    }
+
+    let mut stash: Stash<i32> = (0..3).collect();
+    assert_eq!(stash.len(), 3);
+    stash.take(1);
+    fill(&mut stash);
+    assert_eq!(stash.len(), 4);
+    assert_eq!(stash[1], 3);
+    assert_eq!(stash[3], 4);
+
+    let stash: Stash<&str> = vec![(4, "a"), (1, "b"), (4, "c")].into_iter().collect();
+    assert_eq!(stash.len(), 2);
+    assert_eq!(stash[1], "b");
+    assert_eq!(stash[4], "c");
+    assert!(!stash.contains_key(0));
+}
+
+#[test]
+fn keys() {
+    let mut stash: Stash<i32> = (0..4).collect();
+    stash.take(1);
+    assert_eq!(stash.keys().len(), 3);
+    assert_eq!(stash.keys().collect::<Vec<_>>(), [0, 2, 3]);
+    assert_eq!(stash.keys().rev().collect::<Vec<_>>(), [3, 2, 0]);
+    for index in stash.keys_snapshot() {
+        stash.take(index);
+    }
+    assert!(stash.is_empty());
+    stash.put(5);
+    assert_eq!(stash.clone().into_keys().collect::<Vec<_>>(), [3]);
+}
+
+#[test]
+#[should_panic(expected = "stash index overflowing!")]
+fn insert_at_overflow() {
+    let mut stash = Stash::new();
+    stash.insert_at(usize::MAX, 0);
+}
+
+#[test]
+fn insert_at() {
+    let mut stash = Stash::new();
+    let keys: Vec<usize> = stash.extend(0..4).collect();
+    stash.take(keys[1]);
+    stash.take(keys[3]);
+    stash.take(keys[0]);
+    // Free list: 0, 3, 1.
+    assert_eq!(stash.insert_at(3, 30), None);
+    assert_eq!(stash.insert_at(2, 20), Some(2));
+    assert_eq!(stash.insert_at(6, 60), None);
+    assert_eq!(stash.len(), 3);
+    assert_eq!(stash[6], 60);
+    assert_eq!(stash.get(5), None);
+    // Existing vacant slots first, then the new ones in ascending order.
+    let keys: Vec<usize> = stash.extend(0..5).collect();
+    assert_eq!(keys, [0, 1, 4, 5, 7]);
+
+    // Growing when the target is the next slot to be pushed.
+    let mut stash = Stash::new();
+    stash.put(0);
+    assert_eq!(stash.insert_at(1, 1), None);
+    assert_eq!(stash.put(2), 2);
+}
+
+#[test]
+fn journal() {
+    let mut primary = JournaledStash::new(Stash::new(), Vec::new());
+    let mut replica = Stash::new();
+    let mut keys = Vec::new();
+    let mut state = 7u32;
+    for i in 0..500 {
+        state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+        match (state >> 8) % 10 {
+            0..=2 if !keys.is_empty() => {
+                let key = keys.swap_remove(state as usize % keys.len());
+                primary.take(key).unwrap();
+            }
+            3 => {
+                let key = (state >> 16) as usize % (primary.len() + 5);
+                if primary.insert_at(key, i).is_none() {
+                    keys.push(key);
+                }
+            }
+            4 if i % 100 == 0 => {
+                primary.clear();
+                keys.clear();
+            }
+            _ => keys.push(primary.put(i)),
+        }
+        for op in primary.sink_mut().drain(..) {
+            replica.apply(op).unwrap();
+        }
+        assert!(primary.iter().eq(replica.iter()));
+        assert_eq!(primary.next_index(), replica.next_index());
+    }
+
+    // Nothing is recorded for failed takes.
+    primary.take(10_000);
+    assert!(primary.sink().is_empty());
+
+    let next = replica.next_index();
+    let err = replica
+        .apply(Op::Put {
+            index: next + 1,
+            value: 0,
+        })
+        .unwrap_err();
+    assert_eq!(
+        err.into_op(),
+        Op::Put {
+            index: next + 1,
+            value: 0
+        }
+    );
+    assert!(replica.apply(Op::Take { index: next }).is_err());
+    assert_eq!(replica.next_index(), next);
+}
+
+/// Check that two stashes hold the same values and will assign the same keys.
+fn assert_same_future(a: &Stash<i32>, b: &Stash<i32>) {
+    assert!(a.iter().eq(b.iter()));
+    let (mut a, mut b) = (a.clone(), b.clone());
+    for i in 0..(a.len() + 10) as i32 {
+        assert_eq!(a.put(i), b.put(i));
+    }
+}
+
+#[test]
+fn transaction() {
+    let mut stash = Stash::new();
+    let keys: Vec<usize> = stash.extend(0..10).collect();
+    for &k in &[keys[7], keys[2], keys[9], keys[5]] {
+        stash.take(k);
+    }
+    let original = stash.clone();
+
+    let result: Result<(), ()> = stash.transaction(|tx| {
+        let a = tx.put(100);
+        tx.put(101);
+        assert_eq!(tx.take(keys[3]), Some(3));
+        assert_eq!(tx.take(keys[3]), None);
+        *tx.get_mut(keys[4]).unwrap() = 40;
+        *tx.get_mut(keys[4]).unwrap() = 41;
+        for i in 0..5 {
+            tx.put(200 + i);
+        }
+        *tx.get_mut(a).unwrap() = 1000;
+        assert_eq!(tx.take(a), Some(1000));
+        tx.take(keys[0]);
+        assert_eq!(tx.get(keys[4]), Some(&41));
+        assert_eq!(tx.len(), 10);
+        Err(())
+    });
+    assert!(result.is_err());
+    assert_same_future(&stash, &original);
+
+    // Committed.
+    let mut expected = stash.clone();
+    let key = stash
+        .transaction(|tx| -> Result<_, ()> {
+            tx.take(keys[1]);
+            Ok(tx.put(1))
+        })
+        .unwrap();
+    expected.take(keys[1]);
+    assert_eq!(expected.put(1), key);
+    assert_same_future(&stash, &expected);
+}
+
+#[test]
+fn transaction_panic() {
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    let mut stash = Stash::new();
+    let keys: Vec<usize> = stash.extend(0..5).collect();
+    stash.take(keys[1]);
+    let original = stash.clone();
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        let _: Result<(), ()> = stash.transaction(|tx| {
+            tx.put(10);
+            tx.put(11);
+            tx.take(keys[4]);
+            panic!("oops");
+        });
+    }));
+    assert!(result.is_err());
+    assert_same_future(&stash, &original);
+}
+
+#[test]
+fn transaction_clones_once() {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    #[derive(Debug, PartialEq)]
+    struct Counted(i32, Rc<Cell<usize>>);
+    impl Clone for Counted {
+        fn clone(&self) -> Self {
+            self.1.set(self.1.get() + 1);
+            Counted(self.0, self.1.clone())
+        }
+    }
+
+    let clones = Rc::new(Cell::new(0));
+    let mut stash = Stash::new();
+    let a = stash.put(Counted(1, clones.clone()));
+    let result: Result<(), ()> = stash.transaction(|tx| {
+        for i in 0..10 {
+            tx.get_mut(a).unwrap().0 = i;
+        }
+        let b = tx.put(Counted(2, clones.clone()));
+        for i in 0..10 {
+            tx.get_mut(b).unwrap().0 = i;
+        }
+        Err(())
+    });
+    assert!(result.is_err());
+    assert_eq!(clones.get(), 1);
+    assert_eq!(stash[a].0, 1);
+    assert_eq!(stash.len(), 1);
+}
+
+#[test]
+fn diff_and_patch() {
+    let mut primary = Stash::new();
+    let mut replica = Stash::new();
+    let mut keys = Vec::new();
+    let mut state = 3u32;
+    for round in 0..50 {
+        let before = primary.clone();
+        for i in 0..20 {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            match (state >> 8) % 4 {
+                0 | 1 if !keys.is_empty() => {
+                    let key = keys.swap_remove(state as usize % keys.len());
+                    primary.take(key);
+                }
+                2 if !keys.is_empty() => {
+                    primary[keys[state as usize % keys.len()]] = round * 100 + i;
+                }
+                _ => keys.push(primary.put(round * 100 + i)),
+            }
+        }
+        if round % 10 == 9 {
+            primary.clear();
+            keys.clear();
+        }
+        replica.apply_patch(before.diff(&primary)).unwrap();
+        assert!(replica.structurally_eq(&primary));
+        assert_same_future(&replica, &primary);
+    }
+    assert!(primary.diff(&replica).is_empty());
+}
+
+#[test]
+fn patch_errors() {
+    let mut a = Stash::new();
+    a.extend(0..4).count();
+    let mut b = a.clone();
+    b.take(1);
+    b[2] = 20;
+    let patch = a.diff(&b);
+    assert_eq!(
+        patch,
+        [
+            Change::Removed { index: 1 },
+            Change::Modified {
+                index: 2,
+                value: 20
+            },
+            Change::FreeList {
+                len: 4,
+                order: vec![1]
+            },
+        ]
+    );
+
+    // Applying twice conflicts and leaves the stash untouched.
+    let mut c = a.clone();
+    c.apply_patch(patch.clone()).unwrap();
+    assert_eq!(
+        c.apply_patch(patch.clone()),
+        Err(PatchError::Conflict { index: 1 })
+    );
+    assert_same_future(&c, &b);
+
+    // Missing free list.
+    let mut c = a.clone();
+    assert_eq!(
+        c.apply_patch(patch[..2].to_vec()),
+        Err(PatchError::InvalidFreeList)
+    );
+    assert_same_future(&c, &a);
+
+    // Free list that doesn't match the vacant slots.
+    for order in [vec![], vec![0], vec![1, 1], vec![1, 3]] {
+        let mut patch = patch.clone();
+        patch[2] = Change::FreeList { len: 4, order };
+        assert_eq!(
+            a.clone().apply_patch(patch),
+            Err(PatchError::InvalidFreeList)
+        );
+    }
+
+    // Out of order.
+    let mut reversed = patch.clone();
+    reversed.swap(0, 1);
+    assert_eq!(
+        a.clone().apply_patch(reversed),
+        Err(PatchError::Conflict { index: 1 })
+    );
+
+    // New slots that are neither added nor vacant.
+    let huge = Change::FreeList {
+        len: usize::MAX,
+        order: vec![1],
+    };
+    assert_eq!(
+        a.clone()
+            .apply_patch(vec![Change::Removed { index: 1 }, huge]),
+        Err(PatchError::InvalidFreeList)
+    );
+}
+
+fn hash<T: std::hash::Hash>(value: &T) -> u64 {
+    use std::hash::{BuildHasher, BuildHasherDefault};
+    BuildHasherDefault::<std::collections::hash_map::DefaultHasher>::default().hash_one(value)
+}
+
+#[test]
+fn equality() {
+    let mut a = Stash::new();
+    let keys: Vec<usize> = a.extend(0..4).collect();
+    let mut b = a.clone();
+    assert_eq!(a, b);
+    assert!(a.structurally_eq(&b));
+
+    // Trailing vacant slots are ignored.
+    b.take(keys[3]);
+    a.take(keys[3]);
+    let mut c: Stash<i32> = Stash::new();
+    c.extend(0..3).count();
+    assert_eq!(a, c);
+    assert_eq!(hash(&a), hash(&c));
+    assert!(!a.structurally_eq(&c));
+
+    // Same mapping, different free list order.
+    a.take(keys[0]);
+    a.take(keys[1]);
+    b.take(keys[1]);
+    b.take(keys[0]);
+    assert_eq!(a, b);
+    assert_eq!(hash(&a), hash(&b));
+    assert!(!a.structurally_eq(&b));
+
+    b[keys[2]] = 20;
+    assert_ne!(a, b);
+    assert_ne!(hash(&a), hash(&b));
+    b[keys[2]] = 2;
+    a.put(5);
+    assert_ne!(a, b);
+}
+
+#[test]
+fn lookup_errors() {
+    let mut stash = Stash::new();
+    let a = stash.put(1);
+    let b = stash.put(2);
+    assert_eq!(stash.try_get(a), Ok(&1));
+    assert_eq!(stash.try_take(a), Ok(1));
+    assert_eq!(stash.try_get(a), Err(LookupError::Vacant));
+    assert_eq!(stash.try_get_mut(a), Err(LookupError::Vacant));
+    assert_eq!(stash.try_take(a), Err(LookupError::Vacant));
+    assert_eq!(stash.try_get(b + 1), Err(LookupError::OutOfBounds));
+    assert_eq!(stash.try_take(b + 1), Err(LookupError::OutOfBounds));
+    *stash.try_get_mut(b).unwrap() = 3;
+    assert_eq!(stash[b], 3);
+    assert_eq!(stash.len(), 1);
+    // Failed takes must not disturb the free list.
+    assert_eq!(stash.put(4), a);
+    assert_eq!(stash.put(5), b + 1);
+}
+
 #[test]
 fn clear_zero() {
     let mut stash1 = Stash::new();
@@ -131,3 +537,309 @@ fn serialize_full() {
     let i = stash2.put(42);
     assert_eq!(stash2.get(i), Some(&42));
 }
+
+#[cfg(feature = "serialization")]
+#[derive(Serialize, Deserialize)]
+struct Exact(#[serde(with = "stash::stash::exact")] Stash<i32, usize>);
+
+#[cfg(feature = "serialization")]
+#[test]
+fn serialize_exact() {
+    let mut stash1 = Stash::new();
+    for a in 0..10 {
+        stash1.put(a);
+    }
+    stash1.take(3);
+    stash1.take(9);
+    stash1.take(0);
+    stash1.take(5);
+
+    let bytes = bincode::serialize(&Exact(stash1.clone())).unwrap();
+    let Exact(mut stash2) = bincode::deserialize(&bytes).unwrap();
+
+    assert_eq!(stash1.len(), stash2.len());
+    let vec1: Vec<_> = stash1.iter().collect();
+    let vec2: Vec<_> = stash2.iter().collect();
+    assert_eq!(vec1, vec2);
+
+    // The restored stash must assign exactly the same keys.
+    for a in 0..6 {
+        assert_eq!(stash1.put(a), stash2.put(a));
+    }
+}
+
+#[cfg(feature = "serialization")]
+#[test]
+fn serialize_exact_invalid() {
+    let mut stash = Stash::new();
+    for a in 0..4 {
+        stash.put(a);
+    }
+    stash.take(1);
+    stash.take(2);
+
+    let bytes = bincode::serialize(&(1u32, &stash, vec![1usize, 1])).unwrap();
+    assert!(bincode::deserialize::<Exact>(&bytes).is_err());
+    let bytes = bincode::serialize(&(1u32, &stash, vec![1usize, 3])).unwrap();
+    assert!(bincode::deserialize::<Exact>(&bytes).is_err());
+    let bytes = bincode::serialize(&(1u32, &stash, vec![2usize])).unwrap();
+    assert!(bincode::deserialize::<Exact>(&bytes).is_err());
+    let bytes = bincode::serialize(&(2u32, &stash, vec![2usize, 1])).unwrap();
+    assert!(bincode::deserialize::<Exact>(&bytes).is_err());
+    let bytes = bincode::serialize(&(1u32, &stash, vec![2usize, 1])).unwrap();
+    assert!(bincode::deserialize::<Exact>(&bytes).is_ok());
+}
+
+#[cfg(feature = "serialization")]
+#[test]
+fn serialize_exact_default_format() {
+    let mut stash1 = Stash::new();
+    for a in 0..6 {
+        stash1.put(a);
+    }
+    stash1.take(4);
+    stash1.take(1);
+
+    // Data written in the default format before switching to the exact one.
+    let json = serde_json::to_string(&stash1).unwrap();
+    let Exact(mut stash2) = serde_json::from_str(&json).unwrap();
+    let mut stash3: Stash<i32, usize> = serde_json::from_str(&json).unwrap();
+    assert!(stash2.iter().eq(stash1.iter()));
+    for a in 0..3 {
+        assert_eq!(stash2.put(a), stash3.put(a));
+    }
+
+    let json = serde_json::to_string(&Sparse(stash1.clone())).unwrap();
+    let Exact(stash2) = serde_json::from_str(&json).unwrap();
+    assert!(stash2.iter().eq(stash1.iter()));
+
+    let Exact(stash) = serde_json::from_str("[5,null,7]").unwrap();
+    assert_eq!(stash.iter().collect::<Vec<_>>(), [(0, &5), (2, &7)]);
+    let Exact(stash) = serde_json::from_str("[1]").unwrap();
+    assert_eq!(stash.iter().collect::<Vec<_>>(), [(0, &1)]);
+    let Exact(stash) = serde_json::from_str("[null,-1]").unwrap();
+    assert_eq!(stash.iter().collect::<Vec<_>>(), [(1, &-1)]);
+    let Exact(stash) = serde_json::from_str("[]").unwrap();
+    assert!(stash.is_empty());
+
+    // The exact format still round-trips.
+    let json = serde_json::to_string(&Exact(stash1.clone())).unwrap();
+    assert_eq!(
+        json,
+        r#"{"version":1,"stash":[0,null,2,3,null,5],"free_list":[1,4]}"#
+    );
+    let Exact(mut stash2) = serde_json::from_str(&json).unwrap();
+    for a in 0..3 {
+        assert_eq!(stash2.put(a), stash1.put(a));
+    }
+    assert!(serde_json::from_str::<Exact>(r#"{"version":2,"stash":[],"free_list":[]}"#).is_err());
+    assert!(serde_json::from_str::<Exact>(r#"{"stash":[],"free_list":[]}"#).is_err());
+
+    // A sequence is always a stash in the default format, whatever the values look like.
+    #[derive(Deserialize)]
+    struct ExactValue(#[serde(with = "stash::stash::exact")] Stash<serde_json::Value, usize>);
+    let ExactValue(stash) = serde_json::from_str("[1,[null,2],[5]]").unwrap();
+    assert_eq!(stash.len(), 3);
+    assert_eq!(stash[1], serde_json::json!([null, 2]));
+}
+
+#[cfg(feature = "serialization")]
+#[test]
+fn serialize_sparse() {
+    let mut stash1 = Stash::new();
+    for a in 0..6 {
+        stash1.put(a);
+    }
+    for i in 0..5 {
+        stash1.take(i);
+    }
+    stash1.put(6);
+
+    // The sparse format is opt-in.
+    let json = serde_json::to_string(&stash1).unwrap();
+    assert_eq!(json, "[null,null,null,null,6,5]");
+    let json = serde_json::to_string(&Sparse(stash1.clone())).unwrap();
+    assert_eq!(json, r#"{"len":6,"entries":{"4":6,"5":5}}"#);
+    let stash2: Stash<i32, usize> = serde_json::from_str(&json).unwrap();
+    let vec1: Vec<_> = stash1.iter().collect();
+    let vec2: Vec<_> = stash2.iter().collect();
+    assert_eq!(vec1, vec2);
+
+    // The dense format is still accepted.
+    let stash3: Stash<i32, usize> = serde_json::from_str("[null,1,null]").unwrap();
+    assert_eq!(stash3.iter().collect::<Vec<_>>(), vec![(1, &1)]);
+
+    // Trailing vacant slots are preserved.
+    let mut stash4: Stash<i32, usize> = serde_json::from_str(r#"{"len":3,"entries":{}}"#).unwrap();
+    assert!(stash4.is_empty());
+    let mut keys: Vec<_> = (0..4).map(|a| stash4.put(a)).collect();
+    keys.sort();
+    assert_eq!(keys, vec![0, 1, 2, 3]);
+
+    assert!(serde_json::from_str::<Stash<i32, usize>>(r#"{"len":3,"entries":{"3":1}}"#).is_err());
+}
+
+#[cfg(feature = "serialization")]
+#[derive(Serialize, Deserialize)]
+struct Sparse(#[serde(with = "stash::stash::sparse")] Stash<i32, usize>);
+
+#[cfg(feature = "serialization")]
+#[test]
+fn serialize_sparse_binary() {
+    let mut stash1 = Stash::new();
+    for a in 0..10 {
+        stash1.put(a);
+    }
+    stash1.take(0);
+    stash1.take(3);
+    stash1.take(9);
+
+    let bytes = bincode::serialize(&Sparse(stash1.clone())).unwrap();
+    let Sparse(stash2) = bincode::deserialize(&bytes).unwrap();
+
+    assert_eq!(stash1.len(), stash2.len());
+    let vec1: Vec<_> = stash1.iter().collect();
+    let vec2: Vec<_> = stash2.iter().collect();
+    assert_eq!(vec1, vec2);
+}
+
+#[cfg(feature = "serialization")]
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct TinyIndex(u8);
+
+#[cfg(feature = "serialization")]
+impl stash::Index for TinyIndex {
+    fn from_usize(idx: usize) -> Self {
+        Self::try_from_usize(idx).unwrap()
+    }
+
+    fn try_from_usize(idx: usize) -> Option<Self> {
+        if idx > u8::MAX as usize {
+            return None;
+        }
+        Some(TinyIndex(idx as u8))
+    }
+
+    fn into_usize(self) -> usize {
+        self.0 as usize
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct NarrowIndex(u8);
+
+impl stash::Index for NarrowIndex {
+    const BITS: u32 = 8;
+
+    fn from_usize(idx: usize) -> Self {
+        assert!(idx <= u8::MAX as usize);
+        NarrowIndex(idx as u8)
+    }
+
+    fn into_usize(self) -> usize {
+        self.0 as usize
+    }
+}
+
+#[test]
+fn default_try_from_usize() {
+    use stash::Index;
+
+    assert_eq!(NarrowIndex::try_from_usize(255), Some(NarrowIndex(255)));
+    assert_eq!(NarrowIndex::try_from_usize(256), None);
+    assert_eq!(usize::try_from_usize(usize::MAX), Some(usize::MAX));
+}
+
+#[cfg(feature = "serialization")]
+#[test]
+fn deserialize_index_overflow() {
+    let mut stash = Stash::new();
+    for a in 0..256 {
+        stash.put(a);
+    }
+    let bytes = bincode::serialize(&stash).unwrap();
+    let small: Stash<i32, TinyIndex> = bincode::deserialize(&bytes).unwrap();
+    assert_eq!(small.len(), 256);
+
+    stash.put(256);
+    let bytes = bincode::serialize(&stash).unwrap();
+    assert!(bincode::deserialize::<Stash<i32, TinyIndex>>(&bytes).is_err());
+
+    assert!(serde_json::from_str::<Stash<i32, TinyIndex>>(r#"{"len":1000,"entries":{}}"#).is_err());
+}
+
+#[cfg(feature = "serialization")]
+#[test]
+fn deserialize_limits() {
+    use bincode::Options;
+    use stash::Limits;
+
+    let mut stash = Stash::new();
+    for a in 0..10 {
+        stash.put(a);
+    }
+    stash.take(5);
+    let bytes = bincode::options().serialize(&stash).unwrap();
+    let deserialize = |limits| {
+        let mut de = bincode::Deserializer::from_slice(&bytes, bincode::options());
+        Stash::<i32>::deserialize_with_limits(&mut de, limits)
+    };
+    assert!(deserialize(Limits::new()).is_ok());
+    assert!(deserialize(Limits::new().max_slots(10).max_len(9)).is_ok());
+    assert!(deserialize(Limits::new().max_slots(9)).is_err());
+    assert!(deserialize(Limits::new().max_len(8)).is_err());
+
+    let mut de = serde_json::Deserializer::from_str(r#"{"len":100000000000,"entries":{}}"#);
+    assert!(Stash::<i32>::deserialize_with_limits(&mut de, Limits::new().max_slots(100)).is_err());
+
+    // Entries are counted as they're read, duplicates included.
+    let json = r#"{"len":1,"entries":{"0":1,"0":2,"0":3}}"#;
+    let mut de = serde_json::Deserializer::from_str(json);
+    let err = Stash::<i32>::deserialize_with_limits(&mut de, Limits::new().max_len(2)).unwrap_err();
+    assert!(err.to_string().contains("more than 2 entries"));
+}
+
+#[cfg(feature = "serialization")]
+#[test]
+fn deserialize_huge_sparse_len() {
+    use stash::Limits;
+
+    // Without explicit limits, the sparse format can't describe more slots than the default cap.
+    let json = r#"{"len":4000000000,"entries":{}}"#;
+    assert!(serde_json::from_str::<Stash<i32>>(json).is_err());
+    let json = r#"{"len":1048577,"entries":{"5":1}}"#;
+    assert!(serde_json::from_str::<Stash<i32>>(json).is_err());
+
+    // Explicit limits lift the cap.
+    let mut de = serde_json::Deserializer::from_str(json);
+    let stash = Stash::<i32>::deserialize_with_limits(&mut de, Limits::new()).unwrap();
+    assert_eq!(stash.len(), 1);
+    assert_eq!(stash[5], 1);
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn par_iter() {
+    use rayon::prelude::*;
+
+    let mut stash: Stash<usize> = (0..1000).collect();
+    for i in (0..1000).step_by(3) {
+        stash.take(i);
+    }
+
+    let mut pairs: Vec<(usize, usize)> = stash.par_iter().map(|(i, &v)| (i, v)).collect();
+    pairs.sort_unstable();
+    assert_eq!(
+        pairs,
+        stash.iter().map(|(i, &v)| (i, v)).collect::<Vec<_>>()
+    );
+
+    stash.par_iter_mut().for_each(|(i, v)| *v += i);
+    stash.par_values_mut().for_each(|v| *v += 1);
+    assert!(stash.iter().all(|(i, &v)| v == 2 * i + 1));
+
+    assert_eq!((&stash).into_par_iter().count(), stash.len());
+    let mut pairs: Vec<(usize, usize)> = stash.clone().into_par_iter().collect();
+    pairs.sort_unstable();
+    assert_eq!(pairs, stash.into_iter().collect::<Vec<_>>());
+}
@@ -1,6 +1,14 @@
 extern crate bincode;
+#[cfg(feature = "rayon")]
+extern crate rayon;
+#[cfg(feature = "serialization")]
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
 extern crate stash;
-use stash::{Tag, UniqueStash};
+use stash::diff::{Change, PatchError};
+use stash::unique_stash::{TagCodec, TagParseError, TagParseErrorKind, TOKEN_LEN};
+use stash::{LookupError, Stash, Tag, UniqueStash};
 
 #[test]
 fn string_conversions() {
@@ -21,6 +29,116 @@ fn string_format() {
     assert!("909/0990/".parse::<Tag>().is_err());
     assert!("/909/0990/".parse::<Tag>().is_err());
     assert!("/909/0990".parse::<Tag>().is_err());
+    assert!("+909/990".parse::<Tag>().is_err());
+
+    let kind = |s: &str| s.parse::<Tag>().unwrap_err().kind();
+    assert_eq!(kind("0909/990"), TagParseErrorKind::NonCanonical);
+    assert_eq!(kind("909 /990"), TagParseErrorKind::InvalidChar);
+    assert_eq!(kind("+909/990"), TagParseErrorKind::InvalidChar);
+    assert_eq!(kind("909//990"), TagParseErrorKind::InvalidFormat);
+    assert_eq!(kind("909"), TagParseErrorKind::InvalidFormat);
+    assert_eq!(kind("/990"), TagParseErrorKind::InvalidFormat);
+    assert_eq!(kind("1/18446744073709551616"), TagParseErrorKind::Overflow);
+    assert_eq!(
+        "909".parse::<Tag>(),
+        Err(TagParseError::new(TagParseErrorKind::InvalidFormat))
+    );
+    assert_eq!(
+        TagParseError::from(TagParseErrorKind::Overflow).kind(),
+        TagParseErrorKind::Overflow
+    );
+    assert!("1/18446744073709551615".parse::<Tag>().is_ok());
+}
+
+#[test]
+fn base32_format() {
+    let mut stash = UniqueStash::new();
+    for i in 0..100 {
+        let tag = stash.put(i);
+        let encoded = tag.base32().to_string();
+        assert_eq!(Tag::from_base32(&encoded).unwrap(), tag);
+        assert!(encoded
+            .bytes()
+            .all(|c| c.is_ascii_digit() || c.is_ascii_uppercase()));
+        if i % 3 == 0 {
+            stash.take(tag);
+        }
+    }
+
+    // Version 1 is in the high bits.
+    let tag: Tag = "5/1".parse().unwrap();
+    assert_eq!(tag.base32().to_string(), "G000000000005");
+    assert_eq!(Tag::from_base32("G000000000005").unwrap(), tag);
+    assert_eq!(Tag::from_base32("0").unwrap(), "0/0".parse().unwrap());
+    assert_eq!(Tag::from_base32("Z").unwrap(), "31/0".parse().unwrap());
+
+    let max: Tag = "18446744073709551615/18446744073709551615".parse().unwrap();
+    assert_eq!(max.base32().to_string(), "7ZZZZZZZZZZZZZZZZZZZZZZZZZ");
+    assert_eq!(Tag::from_base32("7ZZZZZZZZZZZZZZZZZZZZZZZZZ").unwrap(), max);
+
+    let kind = |s: &str| Tag::from_base32(s).unwrap_err().kind();
+    assert_eq!(kind(""), TagParseErrorKind::InvalidFormat);
+    assert_eq!(kind("05"), TagParseErrorKind::NonCanonical);
+    assert_eq!(kind("z"), TagParseErrorKind::NonCanonical);
+    assert_eq!(kind("O"), TagParseErrorKind::NonCanonical);
+    assert_eq!(kind("I"), TagParseErrorKind::NonCanonical);
+    assert_eq!(kind("L"), TagParseErrorKind::NonCanonical);
+    assert_eq!(kind("U"), TagParseErrorKind::InvalidChar);
+    assert_eq!(kind("5/1"), TagParseErrorKind::InvalidChar);
+    assert_eq!(
+        kind("8ZZZZZZZZZZZZZZZZZZZZZZZZZ"),
+        TagParseErrorKind::Overflow
+    );
+    assert_eq!(
+        kind("7ZZZZZZZZZZZZZZZZZZZZZZZZZZ"),
+        TagParseErrorKind::Overflow
+    );
+}
+
+#[test]
+fn tag_codec() {
+    let codec = TagCodec::new(*b"0123456789abcdef");
+    let mut stash = UniqueStash::new();
+    let t1 = stash.put(1);
+    stash.take(t1);
+    let t2 = stash.put(2);
+    assert_eq!(t1.to_string(), "0/0");
+    assert_eq!(t2.to_string(), "0/1");
+
+    let tok1 = codec.encode(t1);
+    let tok2 = codec.encode(t2);
+    assert_eq!(tok1.len(), TOKEN_LEN);
+    assert_ne!(tok1, tok2);
+    assert_eq!(tok1, codec.encode(t1));
+    assert!(tok1
+        .bytes()
+        .all(|c| c.is_ascii_alphanumeric() || c == b'-' || c == b'_'));
+
+    assert_eq!(codec.decode(&tok1).unwrap(), t1);
+    assert_eq!(codec.decode(&tok2).unwrap(), t2);
+    assert_eq!(stash[codec.decode(&tok2).unwrap()], 2);
+
+    // Wrong key.
+    let other = TagCodec::new(*b"0123456789abcdeg");
+    assert_eq!(
+        other.decode(&tok1).unwrap_err().kind(),
+        TagParseErrorKind::Unauthenticated
+    );
+
+    // Tampered.
+    let mut bytes = tok1.as_bytes().to_vec();
+    bytes[3] = if bytes[3] == b'A' { b'B' } else { b'A' };
+    assert!(codec.decode(std::str::from_utf8(&bytes).unwrap()).is_err());
+
+    // Malformed.
+    assert_eq!(
+        codec.decode("").unwrap_err().kind(),
+        TagParseErrorKind::InvalidFormat
+    );
+    assert!(codec.decode(&tok1[1..]).is_err());
+    assert!(codec.decode(&format!("{}A", tok1)).is_err());
+    assert!(codec.decode(&tok1.replace(&tok1[..1], "=")).is_err());
+    assert!(codec.decode(&t1.to_string()).is_err());
 }
 
 #[test]
@@ -62,6 +180,385 @@ fn get() {
     assert_eq!(stash[indices[2]], 1);
 }
 
+#[test]
+fn lookup_errors() {
+    let mut stash = UniqueStash::new();
+    let t1 = stash.put(1);
+    let out_of_bounds: Tag = "1/0".parse().unwrap();
+    assert_eq!(stash.try_get(out_of_bounds), Err(LookupError::OutOfBounds));
+    assert_eq!(stash.try_take(out_of_bounds), Err(LookupError::OutOfBounds));
+
+    let future: Tag = "0/1".parse().unwrap();
+    assert_eq!(stash.try_get(future), Err(LookupError::FutureVersion));
+    assert_eq!(stash.try_take(t1), Ok(1));
+
+    // The slot is vacant and waiting for version 1, which hasn't been issued yet.
+    assert_eq!(stash.try_get(future), Err(LookupError::FutureVersion));
+    assert_eq!(stash.try_take(future), Err(LookupError::FutureVersion));
+    assert_eq!(
+        stash.try_get(t1),
+        Err(LookupError::Stale { current_version: 1 })
+    );
+
+    let t2 = stash.put(2);
+    assert_eq!(t2, future);
+    assert_eq!(
+        stash.try_take(t1),
+        Err(LookupError::Stale { current_version: 1 })
+    );
+    assert_eq!(
+        stash.try_get_mut(t1),
+        Err(LookupError::Stale { current_version: 1 })
+    );
+    *stash.try_get_mut(t2).unwrap() = 3;
+    assert_eq!(stash.try_get(t2), Ok(&3));
+    assert_eq!(stash.len(), 1);
+}
+
+#[test]
+fn unchecked() {
+    let mut stash = UniqueStash::new();
+    let t1 = stash.put(1);
+    let t2 = stash.put(2);
+    unsafe {
+        assert_eq!(*stash.get_unchecked(t1), 1);
+        *stash.get_unchecked_mut(t2) = 3;
+        assert_eq!(stash.take_unchecked(t2), 3);
+    }
+    assert_eq!(stash.len(), 1);
+    assert_eq!(stash.get(t2), None);
+    let t3 = stash.put(4);
+    assert_eq!(t3.to_string(), "1/1");
+    assert_eq!(stash[t3], 4);
+}
+
+#[test]
+fn next_tag() {
+    let mut stash = UniqueStash::new();
+    for i in 0..10 {
+        let next = stash.next_tag();
+        assert!(!stash.contains(next));
+        let tag = stash.put(i);
+        assert_eq!(next, tag);
+        assert!(stash.contains(tag));
+        assert_eq!(
+            stash.current_tag(tag.to_string().split('/').next().unwrap().parse().unwrap()),
+            Some(tag)
+        );
+        if i % 3 == 1 {
+            stash.take(tag);
+            assert!(!stash.contains(tag));
+        }
+    }
+    assert_eq!(stash.next_tag().to_string(), "7/0");
+    assert_eq!(stash.current_tag(7), None);
+    let tag = stash.current_tag(3).unwrap();
+    assert_eq!(tag.to_string(), "3/1");
+    stash.take(tag);
+    assert_eq!(stash.current_tag(3), None);
+    assert_eq!(stash.next_tag().to_string(), "3/2");
+    stash.clear();
+    let next = stash.next_tag();
+    assert_eq!(stash.put(0), next);
+}
+
+#[test]
+fn from_stash() {
+    let mut stash = Stash::new();
+    let keys: Vec<usize> = stash.extend(0..6).collect();
+    for &k in &[keys[4], keys[1], keys[3]] {
+        stash.take(k);
+    }
+    let mut expected = stash.clone();
+
+    let mut unique = UniqueStash::from(stash);
+    assert_eq!(unique.len(), 3);
+    for (tag, &value) in &unique {
+        assert_eq!(tag.to_string(), format!("{}/0", value));
+    }
+    for i in 0..5 {
+        let tag = unique.put(10 + i);
+        assert_eq!(tag.to_string(), format!("{}/0", expected.put(10 + i)));
+    }
+}
+
+#[test]
+fn into_stash() {
+    let mut unique = UniqueStash::new();
+    let tags: Vec<Tag> = unique.extend(0..6).collect();
+    for &t in &[tags[2], tags[5], tags[0]] {
+        unique.take(t);
+    }
+    let mut expected = unique.clone();
+
+    let (mut stash, versions): (Stash<_>, _) = unique.into_stash();
+    assert_eq!(versions, [1, 0, 1, 0, 0, 1]);
+    assert_eq!(stash.len(), 3);
+    assert_eq!(stash[1], 1);
+    assert_eq!(stash.next_index(), 0);
+
+    let key = stash.put(10);
+    let mut unique = UniqueStash::from_stash(stash, versions);
+    let tag = expected.put(10);
+    assert_eq!(tag.to_string(), format!("{}/1", key));
+    assert_eq!(unique[tag], 10);
+    // Stale tags stay stale.
+    assert_eq!(unique.get(tags[2]), None);
+    for i in 0..4 {
+        assert_eq!(unique.put(i), expected.put(i));
+    }
+}
+
+#[test]
+#[should_panic]
+fn from_stash_version_mismatch() {
+    let mut stash: Stash<i32> = Stash::new();
+    stash.put(1);
+    UniqueStash::from_stash(stash, vec![]);
+}
+
+#[test]
+fn diff_and_patch() {
+    let mut primary = UniqueStash::new();
+    let mut replica = UniqueStash::new();
+    let mut tags = Vec::new();
+    let mut state = 3u32;
+    for round in 0..50 {
+        let before = primary.clone();
+        for i in 0..20 {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            match (state >> 8) % 4 {
+                0 | 1 if !tags.is_empty() => {
+                    let tag = tags.swap_remove(state as usize % tags.len());
+                    primary.take(tag);
+                }
+                2 if !tags.is_empty() => {
+                    primary[tags[state as usize % tags.len()]] = round * 100 + i;
+                }
+                _ => tags.push(primary.put(round * 100 + i)),
+            }
+        }
+        if round % 10 == 9 {
+            primary.clear();
+            tags.clear();
+        }
+        replica.apply_patch(before.diff(&primary)).unwrap();
+        assert!(replica.structurally_eq(&primary));
+        let (mut p, mut r) = (primary.clone(), replica.clone());
+        for i in 0..30 {
+            assert_eq!(p.put(i), r.put(i));
+        }
+    }
+    assert!(primary.diff(&replica).is_empty());
+}
+
+#[test]
+fn patch_replaced_value() {
+    let mut a = UniqueStash::new();
+    let t1 = a.put(1);
+    let mut b = a.clone();
+    b.take(t1);
+    let t2 = b.put(2);
+    let patch = a.diff(&b);
+    assert_eq!(
+        patch,
+        [
+            Change::Removed { index: t1 },
+            Change::Added {
+                index: t2,
+                value: 2
+            },
+        ]
+    );
+    a.apply_patch(patch.clone()).unwrap();
+    assert_eq!(a[t2], 2);
+    assert_eq!(a.get(t1), None);
+    assert_eq!(a.apply_patch(patch), Err(PatchError::Conflict { index: 0 }));
+}
+
+#[test]
+fn patch_version_rollback() {
+    let mut a = UniqueStash::new();
+    let t1 = a.put(1);
+    a.take(t1);
+    let t2 = a.put(2);
+
+    // Reusing the slot with a version it has already handed out.
+    let rollback = vec![
+        Change::Removed { index: t2 },
+        Change::Added {
+            index: t1,
+            value: 3,
+        },
+    ];
+    assert_eq!(
+        a.apply_patch(rollback),
+        Err(PatchError::Conflict { index: 0 })
+    );
+
+    // Freeing the slot with the version of the removed value.
+    let rollback = vec![
+        Change::Removed { index: t2 },
+        Change::FreeList {
+            len: 1,
+            order: vec![t2],
+        },
+    ];
+    assert_eq!(a.apply_patch(rollback), Err(PatchError::InvalidFreeList));
+    assert_eq!(a[t2], 2);
+
+    let mut b = a.clone();
+    b.take(t2);
+    a.apply_patch(a.diff(&b)).unwrap();
+    let t3 = a.put(3);
+    assert!(t3 != t1 && t3 != t2);
+
+    // Huge free lists are rejected up front.
+    let huge = Change::FreeList {
+        len: usize::MAX,
+        order: vec![],
+    };
+    assert_eq!(a.apply_patch(vec![huge]), Err(PatchError::InvalidFreeList));
+}
+
+#[test]
+fn patch_shrink() {
+    let mut a = UniqueStash::new();
+    let t1 = a.put(1);
+
+    // Truncating the stash would forget the slot's version and reuse `t1`.
+    let patch = a.diff(&UniqueStash::new());
+    assert_eq!(a.apply_patch(patch), Err(PatchError::InvalidFreeList));
+    assert_eq!(a[t1], 1);
+    assert!(a.take(t1).is_some());
+    assert!(a.put(2) != t1);
+}
+
+#[test]
+fn clear() {
+    let mut stash = UniqueStash::new();
+    let tags: Vec<Tag> = stash.extend(0..5).collect();
+    stash.take(tags[2]);
+    stash.clear();
+    assert!(stash.is_empty());
+    for &tag in &tags {
+        assert_eq!(stash.get(tag), None);
+    }
+    let new: Vec<Tag> = stash.extend(0..6).collect();
+    assert_eq!(stash.len(), 6);
+    for (i, tag) in new.iter().enumerate() {
+        assert_eq!(stash[*tag], i);
+        assert!(!tags.contains(tag));
+    }
+}
+
+#[test]
+fn keys() {
+    let mut stash = UniqueStash::new();
+    let tags: Vec<Tag> = stash.extend(0..4).collect();
+    stash.take(tags[1]);
+    assert_eq!(stash.keys().len(), 3);
+    assert_eq!(
+        stash.keys().collect::<Vec<_>>(),
+        [tags[0], tags[2], tags[3]]
+    );
+    assert_eq!(stash.keys().next_back(), Some(tags[3]));
+    for tag in stash.tags_snapshot() {
+        stash.take(tag);
+    }
+    assert!(stash.is_empty());
+    let tag = stash.put(5);
+    assert_eq!(stash.into_keys().collect::<Vec<_>>(), [tag]);
+}
+
+#[test]
+fn range() {
+    let mut stash = UniqueStash::new();
+    let tags: Vec<Tag> = stash.extend(0..6).collect();
+    stash.take(tags[2]);
+    assert_eq!(tags[4].index(), 4);
+
+    let values = |r: stash::unique_stash::Range<'_, i32>| r.map(|(_, v)| *v).collect::<Vec<_>>();
+    assert_eq!(values(stash.range(1..4)), [1, 3]);
+    assert_eq!(values(stash.range(1..=4)), [1, 3, 4]);
+    assert_eq!(values(stash.range(..)), [0, 1, 3, 4, 5]);
+    assert_eq!(values(stash.range(4..100)), [4, 5]);
+    assert!(values(stash.range(100..)).is_empty());
+    assert_eq!(values(stash.iter_from(tags[3].index() + 1)), [4, 5]);
+    assert_eq!(stash.range(..).next_back(), Some((tags[5], &5)));
+}
+
+#[test]
+fn cursor() {
+    let mut stash = UniqueStash::new();
+    let tags: Vec<Tag> = stash.extend(0..6).collect();
+
+    let mut cursor = stash.cursor_mut(1);
+    assert_eq!(cursor.position(), 1);
+    assert_eq!(cursor.remove_current(), Some(1));
+    assert_eq!(cursor.current(), Some((tags[2], &2)));
+    *cursor.current_mut().unwrap().1 = 20;
+    cursor.move_next();
+    assert_eq!(cursor.remove_current(), Some(3));
+    assert_eq!(cursor.remove_current(), Some(4));
+    assert_eq!(cursor.position(), 5);
+    assert_eq!(cursor.remove_current(), Some(5));
+    assert_eq!(cursor.current(), None);
+    assert_eq!(cursor.remove_current(), None);
+    cursor.move_next();
+    assert_eq!(cursor.position(), 6);
+
+    assert!(stash.values().eq(&[0, 20]));
+    let mut cursor = stash.cursor(1);
+    assert_eq!(cursor.current(), Some((tags[2], &20)));
+    cursor.move_next();
+    assert_eq!(cursor.current(), None);
+    assert_eq!(stash.cursor(100).current(), None);
+}
+
+#[test]
+fn from_iter_and_extend() {
+    fn fill<C: Extend<i32>>(c: &mut C) {
+        c.extend(vec![3, 4]);
+    }
+
+    let mut stash: UniqueStash<i32> = (0..3).collect();
+    assert_eq!(stash.len(), 3);
+    let tag = stash.next_tag();
+    fill(&mut stash);
+    assert_eq!(stash.len(), 5);
+    assert_eq!(stash[tag], 3);
+}
+
+#[test]
+fn equality() {
+    let mut a = UniqueStash::new();
+    let tags: Vec<Tag> = a.extend(0..3).collect();
+    let mut b = a.clone();
+    assert_eq!(a, b);
+    assert!(a.structurally_eq(&b));
+
+    // Same mapping, different versions for the next tag.
+    let t = a.put(3);
+    a.take(t);
+    b.put(3);
+    b.take(t);
+    assert_eq!(a, b);
+    assert!(a.structurally_eq(&b));
+    let t = a.put(3);
+    a.take(t);
+    assert_eq!(a, b);
+    assert!(!a.structurally_eq(&b));
+    assert_ne!(a.next_tag(), b.next_tag());
+
+    // Same values, different tags.
+    let mut c = b.clone();
+    c.take(tags[0]);
+    let t = c.put(0);
+    assert_eq!(t.to_string(), "0/1");
+    assert_ne!(b, c);
+}
+
 #[test]
 fn no_reuse() {
     let mut stash = UniqueStash::new();
@@ -164,3 +661,225 @@ fn serialize_full() {
     let i = stash2.put(42);
     assert_eq!(stash2.get(i), Some(&42));
 }
+
+#[cfg(feature = "serialization")]
+#[derive(Serialize, Deserialize)]
+struct Exact(#[serde(with = "stash::unique_stash::exact")] UniqueStash<i32>);
+
+#[cfg(feature = "serialization")]
+#[test]
+fn serialize_exact() {
+    let mut stash1 = UniqueStash::new();
+    let mut tags = Vec::new();
+    for a in 0..10 {
+        tags.push(stash1.put(a));
+    }
+    stash1.take(tags[3]);
+    stash1.take(tags[9]);
+    stash1.take(tags[0]);
+    stash1.take(tags[5]);
+
+    let bytes = bincode::serialize(&Exact(stash1.clone())).unwrap();
+    let Exact(mut stash2) = bincode::deserialize(&bytes).unwrap();
+
+    assert_eq!(stash1.len(), stash2.len());
+    let vec1: Vec<_> = stash1.iter().collect();
+    let vec2: Vec<_> = stash2.iter().collect();
+    assert_eq!(vec1, vec2);
+
+    // The restored stash must assign exactly the same tags.
+    for a in 0..6 {
+        assert_eq!(stash1.put(a), stash2.put(a));
+    }
+}
+
+#[cfg(feature = "serialization")]
+#[test]
+fn serialize_exact_default_format() {
+    let mut stash1 = UniqueStash::new();
+    let tags: Vec<Tag> = (0..6).map(|a| stash1.put(a)).collect();
+    stash1.take(tags[4]);
+    stash1.take(tags[1]);
+
+    // Data written in the default format before switching to the exact one.
+    let json = serde_json::to_string(&stash1).unwrap();
+    let Exact(mut stash2) = serde_json::from_str(&json).unwrap();
+    let mut stash3: UniqueStash<i32> = serde_json::from_str(&json).unwrap();
+    assert!(stash2.iter().eq(stash1.iter()));
+    for a in 0..3 {
+        assert_eq!(stash2.put(a), stash3.put(a));
+    }
+
+    let json = serde_json::to_string(&Sparse(stash1.clone())).unwrap();
+    let Exact(stash2) = serde_json::from_str(&json).unwrap();
+    assert!(stash2.iter().eq(stash1.iter()));
+
+    let Exact(stash) = serde_json::from_str("[[1,null],[2,7]]").unwrap();
+    assert_eq!(stash.values().collect::<Vec<_>>(), [&7]);
+    let Exact(stash) = serde_json::from_str("[]").unwrap();
+    assert!(stash.is_empty());
+
+    // The exact format still round-trips.
+    let json = serde_json::to_string(&Exact(stash1.clone())).unwrap();
+    assert!(json.starts_with(r#"{"version":1,"stash":[[0,0],[1,null],"#));
+    let Exact(mut stash2) = serde_json::from_str(&json).unwrap();
+    for a in 0..3 {
+        assert_eq!(stash2.put(a), stash1.put(a));
+    }
+    let json = r#"{"version":1,"stash":[],"free_list":[],"len":0}"#;
+    assert!(serde_json::from_str::<Exact>(json).is_err());
+}
+
+#[cfg(feature = "serialization")]
+#[test]
+fn serialize_sparse() {
+    let mut stash1 = UniqueStash::new();
+    let mut tags = Vec::new();
+    for a in 0..3 {
+        tags.push(stash1.put(a));
+    }
+    stash1.take(tags[0]);
+    stash1.take(tags[1]);
+    stash1.put(3);
+
+    // The sparse format is opt-in.
+    let json = serde_json::to_string(&stash1).unwrap();
+    assert_eq!(json, "[[1,null],[1,3],[0,2]]");
+    let json = serde_json::to_string(&Sparse(stash1.clone())).unwrap();
+    assert_eq!(
+        json,
+        r#"{"len":3,"entries":{"1/1":3,"2/0":2},"vacant":["0/1"]}"#
+    );
+
+    // Unused vacant slots are left out.
+    let unused = r#"{"len":100001,"entries":{"100000/0":1},"vacant":[]}"#;
+    let sparse: UniqueStash<i32> = serde_json::from_str(unused).unwrap();
+    assert_eq!(serde_json::to_string(&Sparse(sparse)).unwrap(), unused);
+    let mut stash2: UniqueStash<i32> = serde_json::from_str(&json).unwrap();
+    let vec1: Vec<_> = stash1.iter().collect();
+    let vec2: Vec<_> = stash2.iter().collect();
+    assert_eq!(vec1, vec2);
+
+    // Vacant slots keep their versions so tags are never reused.
+    assert_eq!(stash2.put(4).to_string(), "0/1");
+
+    // The dense format is still accepted.
+    let stash3: UniqueStash<i32> = serde_json::from_str("[[1,null],[2,7]]").unwrap();
+    assert_eq!(stash3.iter().next().unwrap().0.to_string(), "1/2");
+
+    assert!(serde_json::from_str::<UniqueStash<i32>>(
+        r#"{"len":1,"entries":{"0/0":1},"vacant":["0/1"]}"#
+    )
+    .is_err());
+}
+
+#[cfg(feature = "serialization")]
+#[derive(Serialize, Deserialize)]
+struct Sparse(#[serde(with = "stash::unique_stash::sparse")] UniqueStash<i32>);
+
+#[cfg(feature = "serialization")]
+#[test]
+fn serialize_sparse_binary() {
+    let mut stash1 = UniqueStash::new();
+    let mut tags = Vec::new();
+    for a in 0..10 {
+        tags.push(stash1.put(a));
+    }
+    stash1.take(tags[0]);
+    stash1.take(tags[3]);
+    stash1.take(tags[9]);
+
+    let bytes = bincode::serialize(&Sparse(stash1.clone())).unwrap();
+    let Sparse(mut stash2) = bincode::deserialize(&bytes).unwrap();
+
+    assert_eq!(stash1.len(), stash2.len());
+    let vec1: Vec<_> = stash1.iter().collect();
+    let vec2: Vec<_> = stash2.iter().collect();
+    assert_eq!(vec1, vec2);
+    let i = stash2.put(42);
+    assert_eq!(stash2.get(i), Some(&42));
+}
+
+#[cfg(feature = "serialization")]
+#[test]
+fn deserialize_limits() {
+    use stash::Limits;
+
+    let json = r#"{"len":4,"entries":{"0/0":1,"2/3":2},"vacant":["1/1"]}"#;
+    let deserialize = |limits| {
+        let mut de = serde_json::Deserializer::from_str(json);
+        UniqueStash::<i32>::deserialize_with_limits(&mut de, limits)
+    };
+    assert!(deserialize(Limits::new()).is_ok());
+    assert!(deserialize(Limits::new().max_slots(4).max_len(2)).is_ok());
+    assert!(deserialize(Limits::new().max_slots(3)).is_err());
+    assert!(deserialize(Limits::new().max_len(1)).is_err());
+
+    // Vacant tags count against the limits as they're read.
+    let json = r#"{"len":3,"entries":{},"vacant":["0/1","1/1","2/1","0/2"]}"#;
+    let mut de = serde_json::Deserializer::from_str(json);
+    let err = UniqueStash::<i32>::deserialize_with_limits(&mut de, Limits::new().max_slots(3))
+        .unwrap_err();
+    assert!(err.to_string().contains("more than 3 slots"));
+
+    // Without explicit limits, the sparse format can't describe more slots than the default cap.
+    let json = r#"{"len":4000000000,"entries":{},"vacant":[]}"#;
+    assert!(serde_json::from_str::<UniqueStash<i32>>(json).is_err());
+}
+
+#[cfg(feature = "serialization")]
+#[test]
+fn serialize_tag() {
+    let mut stash = UniqueStash::new();
+    let tag1 = stash.put(1);
+    stash.take(tag1);
+    let tag2 = stash.put(2);
+    assert_eq!(tag2.to_string(), "0/1");
+
+    let json = serde_json::to_string(&tag2).unwrap();
+    assert_eq!(json, r#""0/1""#);
+    assert_eq!(serde_json::from_str::<Tag>(&json).unwrap(), tag2);
+    // The old struct form is still accepted.
+    assert_eq!(
+        serde_json::from_str::<Tag>(r#"{"idx":0,"ver":1}"#).unwrap(),
+        tag2
+    );
+    assert!(serde_json::from_str::<Tag>(r#""00/1""#).is_err());
+
+    // Binary formats keep the old struct form, whatever the integer encoding.
+    let bytes = bincode::serialize(&tag2).unwrap();
+    assert_eq!(bincode::serialize(&(0u64, 1u64)).unwrap(), bytes);
+    assert_eq!(bincode::deserialize::<Tag>(&bytes).unwrap(), tag2);
+
+    use bincode::Options;
+    let bytes = bincode::options().serialize(&tag2).unwrap();
+    assert_eq!(bincode::options().serialize(&(0u64, 1u64)).unwrap(), bytes);
+    assert_eq!(bincode::options().deserialize::<Tag>(&bytes).unwrap(), tag2);
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn par_iter() {
+    use rayon::prelude::*;
+
+    let mut stash: UniqueStash<usize> = (0..1000).collect();
+    for tag in stash.tags_snapshot().into_iter().step_by(3) {
+        stash.take(tag);
+    }
+    stash.put(0);
+
+    let mut pairs: Vec<(Tag, usize)> = stash.par_iter().map(|(t, &v)| (t, v)).collect();
+    pairs.sort_unstable_by_key(|(t, _)| t.index());
+    assert_eq!(
+        pairs,
+        stash.iter().map(|(t, &v)| (t, v)).collect::<Vec<_>>()
+    );
+
+    stash.par_iter_mut().for_each(|(t, v)| *v = t.index());
+    stash.par_values_mut().for_each(|v| *v += 1);
+    assert!(stash.iter().all(|(t, &v)| v == t.index() + 1));
+
+    let mut pairs: Vec<(Tag, usize)> = stash.clone().into_par_iter().collect();
+    pairs.sort_unstable_by_key(|(t, _)| t.index());
+    assert_eq!(pairs, stash.into_iter().collect::<Vec<_>>());
+}